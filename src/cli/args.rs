@@ -1,31 +1,65 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, Parser, Subcommand};
 use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+use crate::core::config::{ConflictPolicy, TimestampPosition};
+use crate::core::types::ChecksumAlgorithm;
+use crate::operations::auto::{parse_budget, AUTO_COMMAND};
+use crate::operations::bench::BENCH_LEVELS;
+use crate::operations::{AutoGoal, ConvertTarget};
+use crate::utils::crypto::parse_recipients;
+use crate::utils::retention::{parse_max_age, validate_stamp_pattern};
+use crate::utils::settings::Settings;
+use crate::utils::split::parse_size;
+use crate::utils::template::{stamp_placeholder, validate_template};
+use crate::utils::timestamp::validate_timestamp_format;
+
+/// Format of new archives when none is given
+const DEFAULT_COMMAND: &str = "tgz";
+
+/// Compression level when none is given
+const DEFAULT_LEVEL: u8 = 6;
+
+#[derive(Parser, Debug, Default)]
 #[command(name = "jcz")]
 #[command(author = "JCZ Contributors")]
 #[command(version)]
 #[command(about = "Just Compress Zip - A unified compression utility", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct CliArgs {
+    /// Subcommand; without one, the flags below select the operation
+    #[command(subcommand)]
+    pub action: Option<CliAction>,
+
     /// Decompress mode
     #[arg(short = 'd', long)]
     pub decompress: bool,
 
-    /// Force overwrite without prompting
-    #[arg(short = 'f', long)]
-    pub force: bool,
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
 
-    /// Compression command
-    #[arg(short = 'c', long, default_value = "tgz")]
-    pub command: String,
+    #[command(flatten)]
+    pub output: OutputArgs,
 
-    /// Compression level (1-9)
-    #[arg(short = 'l', long, default_value = "6")]
-    pub level: u8,
+    /// Convert compressed files to another format (gzip, bzip2, xz, tgz, tbz2, txz)
+    #[arg(long, value_name = "FORMAT")]
+    pub convert: Option<String>,
 
-    /// Move compressed file to specified directory
-    #[arg(short = 'C', long)]
-    pub move_to: Option<PathBuf>,
+    /// Check that converted files decompress to the original content
+    #[arg(long, requires = "convert")]
+    pub verify: bool,
+
+    /// Append inputs to an existing TAR archive (.tar, .tar.gz, .tar.bz2, .tar.xz)
+    #[arg(long, value_name = "ARCHIVE", group = "tar_edit")]
+    pub append: Option<PathBuf>,
+
+    /// Add inputs to an existing TAR archive, replacing members that are newer on disk
+    #[arg(long, value_name = "ARCHIVE", group = "tar_edit")]
+    pub update: Option<PathBuf>,
+
+    /// Delete the named members from an existing TAR archive
+    #[arg(long, value_name = "ARCHIVE", group = "tar_edit")]
+    pub delete: Option<PathBuf>,
 
     /// Collect files into archive (with parent directory)
     #[arg(short = 'a', long)]
@@ -35,27 +69,1214 @@ pub struct CliArgs {
     #[arg(short = 'A', long)]
     pub collect_flat: Option<String>,
 
-    /// Timestamp option: 0=none, 1=date, 2=datetime, 3=nanoseconds
+    /// Store a MANIFEST.json with each member's size, mode and hash in collections
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// Compress or decompress every file below directories individually
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// Remove archives after successful decompression
+    #[arg(long)]
+    pub remove_archive: bool,
+
+    /// age identity file for decrypting archives encrypted to a public key (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub identity: Vec<PathBuf>,
+
+    /// Refuse to decompress archives that do not match their checksum sidecar or sums file
+    #[arg(long)]
+    pub verify_checksum: bool,
+
+    /// Back up a directory with only the changes since the snapshot in STATE, then update it
+    #[arg(long, value_name = "STATE")]
+    pub incremental: Option<PathBuf>,
+
+    /// Back up a directory with the changes since the full backup recorded in STATE
+    #[arg(long, value_name = "STATE")]
+    pub differential: Option<PathBuf>,
+
+    /// With -d, restore a directory from a full backup and the archives that follow it, in order
+    #[arg(long, value_name = "DIR")]
+    pub restore: Option<PathBuf>,
+
+    /// Store inputs in a deduplicating repository as one snapshot, packing new chunks with -c
+    #[arg(long, value_name = "REPO")]
+    pub dedup: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub file_list: FileListArgs,
+
+    /// Input files or directories
+    #[arg(required_unless_present = "files_from")]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Subcommands available alongside the flag-based interface
+#[derive(Subcommand, Debug)]
+pub enum CliAction {
+    /// Compress each input into its own archive
+    Compress(CompressArgs),
+
+    /// Decompress archives, peeling every compression and encryption layer
+    Decompress(DecompressArgs),
+
+    /// Collect inputs into one archive
+    Collect(CollectArgs),
+
+    /// List the members of archives
+    List(InspectArgs),
+
+    /// Check that archives decompress and read through, without extracting them
+    Test(InspectArgs),
+
+    /// Show the layers of archives with their sizes, levels and header details
+    Info(InfoArgs),
+
+    /// Measure ratio, speed and memory of each format at a range of levels
+    Bench(BenchArgs),
+
+    /// Remove staging directories and partial outputs left by interrupted runs
+    Cleanup {
+        /// Directories to scan [default: current and temporary directories]
+        dirs: Vec<PathBuf>,
+    },
+
+    /// List, extract or verify snapshots of a deduplicating repository
+    Dedup {
+        #[command(subcommand)]
+        command: DedupCommand,
+    },
+}
+
+/// Options for where outputs go and how existing ones are handled
+#[derive(Args, Debug, Default)]
+pub struct OutputArgs {
+    /// Force overwrite without prompting (same as --on-conflict overwrite)
+    #[arg(short = 'f', long)]
+    pub force: bool,
+
+    /// Policy for existing outputs: ask, overwrite, skip, rename, fail
+    #[arg(long, value_name = "POLICY", conflicts_with = "force")]
+    pub on_conflict: Option<String>,
+
+    /// Move outputs to this directory
+    #[arg(short = 'C', long, value_name = "DIR")]
+    pub move_to: Option<PathBuf>,
+
+    /// Write the archive or extracted output to this path (a directory for several inputs)
+    #[arg(short = 'o', long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Flush outputs to disk before renaming them into place
+    #[arg(long)]
+    pub fsync: bool,
+
+    /// Print the outputs a run would write, without creating directories or files
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print the --dry-run plan as JSON
+    #[arg(long, requires = "dry_run")]
+    pub json: bool,
+
+    /// Apply a named profile from the configuration files (or JCZ_PROFILE)
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+}
+
+/// Options for reading inputs from a list instead of the command line
+#[derive(Args, Debug, Default)]
+pub struct FileListArgs {
+    /// Read more inputs from FILE, one per line ('-' for stdin)
+    #[arg(short = 'T', long, value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+
+    /// Inputs in --files-from are separated by NUL characters instead of newlines
+    #[arg(long, requires = "files_from")]
+    pub null: bool,
+}
+
+/// Options for the format, naming and protection of new archives
+#[derive(Args, Debug)]
+pub struct ArchiveArgs {
+    /// Format: gzip, bzip2, xz, tar, tgz, tbz2, txz, or auto to pick one for each input by sampling it
+    #[arg(
+        short = 'c',
+        long,
+        visible_alias = "format",
+        value_name = "FORMAT",
+        default_value = DEFAULT_COMMAND
+    )]
+    pub command: String,
+
+    /// Compression level (1-9) [default: 6]
+    #[arg(short = 'l', long)]
+    pub level: Option<u8>,
+
+    /// With -c auto, pick each format for: smallest, fastest or budget [default: smallest]
+    #[arg(long, value_name = "GOAL")]
+    pub auto_goal: Option<String>,
+
+    /// With -c auto, the longest compressing an input may take (e.g. 500ms, 2s, 1m)
+    #[arg(long, value_name = "TIME")]
+    pub time_budget: Option<String>,
+
+    /// Compress inputs even if they are compressed already (by extension or content)
+    #[arg(long)]
+    pub force_compress: bool,
+
+    /// Also skip inputs whose content looks incompressible, judged by its entropy
+    #[arg(long, conflicts_with = "force_compress")]
+    pub skip_incompressible: bool,
+
+    /// Leave out files and directories matching a pattern, e.g. '*.log' (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Timestamp option: 0=none, 1=date, 2=datetime, 3=datetime with nanoseconds
     #[arg(short = 't', long, default_value = "0")]
     pub timestamp: u8,
 
+    /// Timestamp output names with a strftime pattern, e.g. '%Y-%m-%dT%H%M%SZ'
+    #[arg(long, value_name = "PATTERN")]
+    pub timestamp_format: Option<String>,
+
+    /// Format timestamps in UTC instead of local time
+    #[arg(long)]
+    pub utc: bool,
+
+    /// Where the timestamp goes: prefix, suffix or before-extension [default: suffix]
+    #[arg(long, value_name = "POSITION")]
+    pub timestamp_position: Option<String>,
+
+    /// Take timestamps from each input's modification time instead of the current time
+    #[arg(long)]
+    pub timestamp_from_mtime: bool,
+
+    /// Name outputs from a template, e.g. '{stem}-{host}-{date}.{ext}'
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_template: Option<String>,
+
+    /// After compressing, keep only the newest N timestamped outputs of each input
+    #[arg(long, value_name = "N")]
+    pub keep_last: Option<usize>,
+
+    /// Keep the newest output of each of the last N days
+    #[arg(long, value_name = "N")]
+    pub keep_daily: Option<usize>,
+
+    /// Keep the newest output of each of the last N weeks
+    #[arg(long, value_name = "N")]
+    pub keep_weekly: Option<usize>,
+
+    /// Keep the newest output of each of the last N months
+    #[arg(long, value_name = "N")]
+    pub keep_monthly: Option<usize>,
+
+    /// Remove timestamped outputs older than this (e.g. 12h, 30d, 8w)
+    #[arg(long, value_name = "AGE")]
+    pub max_age: Option<String>,
+
+    /// List the outputs the retention options would remove, without removing them
+    #[arg(long)]
+    pub prune_dry_run: bool,
+
+    /// Remove source files or directories after successful compression or conversion
+    #[arg(long)]
+    pub remove_source: bool,
+
+    /// Split outputs into volumes of this size (e.g. 4G, 500M): name.001, name.002, ...
+    #[arg(long, value_name = "SIZE")]
+    pub split_size: Option<String>,
+
+    /// Encrypt outputs with a passphrase (prompted, or from --passphrase-file or JCZ_PASSPHRASE)
+    #[arg(long)]
+    pub encrypt: bool,
+
+    /// Encrypt outputs to an age public key instead of a passphrase (repeatable)
+    #[arg(long, value_name = "KEY")]
+    pub recipient: Vec<String>,
+
+    /// Read the passphrase from the first line of a file
+    #[arg(long, value_name = "FILE")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Write a checksum of each output: sha256 or blake3 (name.sha256, or SHA256SUMS for several)
+    #[arg(long, value_name = "ALGORITHM")]
+    pub checksum: Option<String>,
+
+    /// Write byte-identical archives for identical inputs (timestamps from SOURCE_DATE_EPOCH)
+    #[arg(long)]
+    pub reproducible: bool,
+}
+
+impl ArchiveArgs {
+    /// The compression level given, or the default
+    pub fn level(&self) -> u8 {
+        self.level.unwrap_or(DEFAULT_LEVEL)
+    }
+}
+
+impl Default for ArchiveArgs {
+    fn default() -> Self {
+        Self {
+            command: DEFAULT_COMMAND.to_string(),
+            level: None,
+            auto_goal: None,
+            time_budget: None,
+            force_compress: false,
+            skip_incompressible: false,
+            exclude: Vec::new(),
+            timestamp: 0,
+            timestamp_format: None,
+            utc: false,
+            timestamp_position: None,
+            timestamp_from_mtime: false,
+            output_template: None,
+            keep_last: None,
+            keep_daily: None,
+            keep_weekly: None,
+            keep_monthly: None,
+            max_age: None,
+            prune_dry_run: false,
+            remove_source: false,
+            split_size: None,
+            encrypt: false,
+            recipient: Vec::new(),
+            passphrase_file: None,
+            checksum: None,
+            reproducible: false,
+        }
+    }
+}
+
+/// Arguments of `jcz compress`
+#[derive(Args, Debug)]
+pub struct CompressArgs {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Compress every file below directories individually
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// Back up a directory with only the changes since the snapshot in STATE, then update it
+    #[arg(long, value_name = "STATE")]
+    pub incremental: Option<PathBuf>,
+
+    /// Back up a directory with the changes since the full backup recorded in STATE
+    #[arg(long, value_name = "STATE")]
+    pub differential: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub file_list: FileListArgs,
+
     /// Input files or directories
-    #[arg(required = true)]
+    #[arg(required_unless_present = "files_from")]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Arguments of `jcz collect`
+#[derive(Args, Debug)]
+pub struct CollectArgs {
+    /// Name of the archive, without extension
+    pub name: String,
+
+    /// Store the inputs at the top of the archive instead of below NAME
+    #[arg(long)]
+    pub flat: bool,
+
+    /// Store a MANIFEST.json with each member's size, mode and hash
+    #[arg(long)]
+    pub manifest: bool,
+
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub file_list: FileListArgs,
+
+    /// Input files or directories
+    #[arg(required_unless_present = "files_from")]
+    pub inputs: Vec<PathBuf>,
+}
+
+/// Arguments of `jcz decompress`
+#[derive(Args, Debug)]
+pub struct DecompressArgs {
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Decompress every archive below directories individually
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+
+    /// Remove archives after successful decompression
+    #[arg(long)]
+    pub remove_archive: bool,
+
+    /// Read the passphrase from the first line of a file
+    #[arg(long, value_name = "FILE")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// age identity file for archives encrypted to a public key (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub identity: Vec<PathBuf>,
+
+    /// Refuse archives that do not match their checksum sidecar or sums file
+    #[arg(long)]
+    pub verify_checksum: bool,
+
+    /// Restore a directory from a full backup and the archives that follow it, in order
+    #[arg(long, value_name = "DIR")]
+    pub restore: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub file_list: FileListArgs,
+
+    /// Archives to decompress
+    #[arg(required_unless_present = "files_from")]
     pub inputs: Vec<PathBuf>,
 }
 
+/// Arguments of `jcz list`, `jcz test` and `jcz info`
+#[derive(Args, Debug)]
+pub struct InspectArgs {
+    /// Read the passphrase from the first line of a file
+    #[arg(long, value_name = "FILE")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// age identity file for archives encrypted to a public key (repeatable)
+    #[arg(long, value_name = "FILE")]
+    pub identity: Vec<PathBuf>,
+
+    /// Archives to inspect
+    #[arg(required = true)]
+    pub archives: Vec<PathBuf>,
+}
+
+/// Arguments of `jcz info`
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    #[command(flatten)]
+    pub inspect: InspectArgs,
+
+    /// Print the details as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Arguments of `jcz bench`
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Formats to benchmark, comma-separated: gzip, bzip2, xz [default: all three]
+    #[arg(
+        short = 'c',
+        long = "format",
+        value_name = "FORMAT",
+        value_delimiter = ','
+    )]
+    pub formats: Vec<String>,
+
+    /// Levels to run each format at, e.g. 6, 1,6,9 or 1-9
+    #[arg(short = 'l', long, value_name = "LEVELS", default_value = BENCH_LEVELS)]
+    pub levels: String,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Files to benchmark
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+impl CliAction {
+    /// Turn `compress`, `decompress` and `collect` into the equivalent flags
+    ///
+    /// Other subcommands are returned unchanged. Option ids are the same in
+    /// both forms, so configuration settings apply to either.
+    pub fn into_flags(self) -> Result<CliArgs, Box<CliAction>> {
+        let args = match self {
+            CliAction::Compress(compress) => CliArgs {
+                archive: compress.archive,
+                output: compress.output,
+                recursive: compress.recursive,
+                incremental: compress.incremental,
+                differential: compress.differential,
+                file_list: compress.file_list,
+                inputs: compress.inputs,
+                ..CliArgs::default()
+            },
+            CliAction::Decompress(decompress) => CliArgs {
+                decompress: true,
+                archive: ArchiveArgs {
+                    passphrase_file: decompress.passphrase_file,
+                    ..ArchiveArgs::default()
+                },
+                output: decompress.output,
+                recursive: decompress.recursive,
+                remove_archive: decompress.remove_archive,
+                identity: decompress.identity,
+                verify_checksum: decompress.verify_checksum,
+                restore: decompress.restore,
+                file_list: decompress.file_list,
+                inputs: decompress.inputs,
+                ..CliArgs::default()
+            },
+            CliAction::Collect(collect) => {
+                let (name, name_flat) = if collect.flat {
+                    (None, Some(collect.name))
+                } else {
+                    (Some(collect.name), None)
+                };
+                CliArgs {
+                    archive: collect.archive,
+                    output: collect.output,
+                    collect: name,
+                    collect_flat: name_flat,
+                    manifest: collect.manifest,
+                    file_list: collect.file_list,
+                    inputs: collect.inputs,
+                    ..CliArgs::default()
+                }
+            }
+            action => return Err(Box::new(action)),
+        };
+
+        Ok(args)
+    }
+}
+
+/// Operations on a repository written with --dedup
+#[derive(Subcommand, Debug)]
+pub enum DedupCommand {
+    /// List the snapshots, or the entries of one snapshot
+    List {
+        /// Repository directory
+        repo: PathBuf,
+
+        /// Snapshot to list the entries of
+        snapshot: Option<String>,
+    },
+
+    /// Restore the files of a snapshot
+    Extract {
+        /// Repository directory
+        repo: PathBuf,
+
+        /// Snapshot to extract
+        snapshot: String,
+
+        /// Directory to extract into [default: current directory]
+        #[arg(short = 'C', long, value_name = "DIR")]
+        target: Option<PathBuf>,
+
+        /// Flush extracted files to disk before renaming them into place
+        #[arg(long)]
+        fsync: bool,
+    },
+
+    /// Check that every chunk is intact and every file rebuilds to its hash
+    Verify {
+        /// Repository directory
+        repo: PathBuf,
+
+        /// Snapshots to check [default: all]
+        snapshots: Vec<String>,
+    },
+}
+
+/// Check whether the option `id` was given on the command line
+///
+/// Subcommands only define some of the options, so unknown ids count as not given.
+fn given_on_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches.try_contains_id(id).unwrap_or(false)
+        && matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Replace `target` with a configured value unless `id` was given on the command line
+fn fill<T>(matches: &ArgMatches, id: &str, target: &mut T, value: Option<T>) {
+    if given_on_command_line(matches, id) {
+        return;
+    }
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
 impl CliArgs {
+    /// Fill in options not given on the command line from configuration settings
+    ///
+    /// Settings only apply where the matching option could be given: archive
+    /// naming and format settings when writing new archives, retention for
+    /// plain compression and collection, and decryption settings with -d.
+    /// Options given on the command line also replace settings they conflict with.
+    pub fn apply_settings(&mut self, settings: Settings, matches: &ArgMatches) {
+        let given = |id: &str| given_on_command_line(matches, id);
+        let edit = self.append.is_some() || self.update.is_some() || self.delete.is_some();
+        let backup = self.incremental.is_some() || self.differential.is_some();
+        let writes_archives = !self.decompress && self.convert.is_none() && !edit;
+        let archive_files = writes_archives && self.dedup.is_none();
+        let retention = archive_files && !self.recursive && !backup;
+        let collecting = self.collect.is_some() || self.collect_flat.is_some();
+        let one_by_one = archive_files && !collecting && !backup;
+
+        fill(
+            matches,
+            "level",
+            &mut self.archive.level,
+            settings.level.map(Some),
+        );
+        fill(matches, "fsync", &mut self.output.fsync, settings.fsync);
+        if !given("force") {
+            fill(
+                matches,
+                "on_conflict",
+                &mut self.output.on_conflict,
+                settings.on_conflict.map(Some),
+            );
+        }
+        if !edit && self.dedup.is_none() && self.restore.is_none() && !given("output") {
+            fill(
+                matches,
+                "move_to",
+                &mut self.output.move_to,
+                settings.move_to.map(Some),
+            );
+        }
+
+        if writes_archives {
+            fill(
+                matches,
+                "command",
+                &mut self.archive.command,
+                settings.command,
+            );
+            if self.archive.command == AUTO_COMMAND && !given("auto_goal") && !given("time_budget")
+            {
+                fill(
+                    matches,
+                    "auto_goal",
+                    &mut self.archive.auto_goal,
+                    settings.auto_goal.map(Some),
+                );
+                fill(
+                    matches,
+                    "time_budget",
+                    &mut self.archive.time_budget,
+                    settings.time_budget.map(Some),
+                );
+            }
+            if !given("timestamp") && !given("timestamp_format") {
+                fill(
+                    matches,
+                    "timestamp",
+                    &mut self.archive.timestamp,
+                    settings.timestamp,
+                );
+                fill(
+                    matches,
+                    "timestamp_format",
+                    &mut self.archive.timestamp_format,
+                    settings.timestamp_format.map(Some),
+                );
+            }
+            if !given("output") {
+                fill(
+                    matches,
+                    "output_template",
+                    &mut self.archive.output_template,
+                    settings.output_template.map(Some),
+                );
+            }
+
+            // Timestamp styles only apply to stamped names
+            let stamped = self.archive.timestamp != 0
+                || self.archive.timestamp_format.is_some()
+                || self.archive.output_template.is_some();
+            if stamped {
+                fill(matches, "utc", &mut self.archive.utc, settings.utc);
+                fill(
+                    matches,
+                    "timestamp_position",
+                    &mut self.archive.timestamp_position,
+                    settings.timestamp_position.map(Some),
+                );
+                fill(
+                    matches,
+                    "timestamp_from_mtime",
+                    &mut self.archive.timestamp_from_mtime,
+                    settings.timestamp_from_mtime,
+                );
+            }
+        }
+
+        if one_by_one && !given("force_compress") && !given("skip_incompressible") {
+            fill(
+                matches,
+                "force_compress",
+                &mut self.archive.force_compress,
+                settings.force_compress,
+            );
+            fill(
+                matches,
+                "skip_incompressible",
+                &mut self.archive.skip_incompressible,
+                settings.skip_incompressible,
+            );
+        }
+
+        if archive_files {
+            fill(
+                matches,
+                "split_size",
+                &mut self.archive.split_size,
+                settings.split_size.map(Some),
+            );
+            fill(
+                matches,
+                "checksum",
+                &mut self.archive.checksum,
+                settings.checksum.map(Some),
+            );
+            if !self.archive.encrypt && self.archive.passphrase_file.is_none() {
+                fill(
+                    matches,
+                    "recipient",
+                    &mut self.archive.recipient,
+                    settings.recipient,
+                );
+            }
+            if !self.archive.encrypt && self.archive.recipient.is_empty() {
+                fill(
+                    matches,
+                    "reproducible",
+                    &mut self.archive.reproducible,
+                    settings.reproducible,
+                );
+            }
+            if collecting {
+                fill(matches, "manifest", &mut self.manifest, settings.manifest);
+            }
+            if !self.archive.remove_source || self.recursive {
+                fill(
+                    matches,
+                    "exclude",
+                    &mut self.archive.exclude,
+                    settings.exclude,
+                );
+            }
+        }
+
+        if retention {
+            fill(
+                matches,
+                "keep_last",
+                &mut self.archive.keep_last,
+                settings.keep_last.map(Some),
+            );
+            fill(
+                matches,
+                "keep_daily",
+                &mut self.archive.keep_daily,
+                settings.keep_daily.map(Some),
+            );
+            fill(
+                matches,
+                "keep_weekly",
+                &mut self.archive.keep_weekly,
+                settings.keep_weekly.map(Some),
+            );
+            fill(
+                matches,
+                "keep_monthly",
+                &mut self.archive.keep_monthly,
+                settings.keep_monthly.map(Some),
+            );
+            fill(
+                matches,
+                "max_age",
+                &mut self.archive.max_age,
+                settings.max_age.map(Some),
+            );
+        }
+
+        if self.decompress {
+            fill(
+                matches,
+                "verify_checksum",
+                &mut self.verify_checksum,
+                settings.verify_checksum,
+            );
+            fill(matches, "identity", &mut self.identity, settings.identity);
+        }
+    }
+
+    /// Goal of -c auto: a time budget implies `budget`, otherwise `smallest`
+    pub fn auto_goal(&self) -> Result<AutoGoal, String> {
+        let budget = match self.archive.time_budget {
+            Some(ref time) => {
+                Some(parse_budget(time).ok_or_else(|| format!("Invalid time budget: {}", time))?)
+            }
+            None => None,
+        };
+        let name = self.archive.auto_goal.as_deref().unwrap_or(match budget {
+            Some(_) => "budget",
+            None => "smallest",
+        });
+
+        AutoGoal::from_name(name, budget).ok_or_else(|| match (name, budget) {
+            ("budget", None) => "--auto-goal budget requires --time-budget".to_string(),
+            ("smallest" | "fastest", Some(_)) => {
+                "--time-budget requires --auto-goal budget".to_string()
+            }
+            _ => format!("Invalid auto goal: {}", name),
+        })
+    }
+
     /// Validate arguments
     pub fn validate(&self) -> Result<(), String> {
         // Validate timestamp option
-        if self.timestamp > 3 {
-            return Err(format!("Invalid timestamp option: {}", self.timestamp));
+        if self.archive.timestamp > 3 {
+            return Err(format!(
+                "Invalid timestamp option: {}",
+                self.archive.timestamp
+            ));
+        }
+        if let Some(ref pattern) = self.archive.timestamp_format {
+            if self.archive.timestamp != 0 {
+                return Err("Cannot specify both -t and --timestamp-format".to_string());
+            }
+            validate_timestamp_format(pattern)?;
+        }
+        if let Some(ref position) = self.archive.timestamp_position {
+            if TimestampPosition::from_name(position).is_none() {
+                return Err(format!("Invalid timestamp position: {}", position));
+            }
+        }
+        let styled = self.archive.utc
+            || self.archive.timestamp_position.is_some()
+            || self.archive.timestamp_from_mtime;
+        let stamped = self.archive.timestamp != 0
+            || self.archive.timestamp_format.is_some()
+            || self.archive.output_template.is_some();
+        if styled && !stamped {
+            return Err(
+                "--utc, --timestamp-position and --timestamp-from-mtime require -t, --timestamp-format or --output-template"
+                    .to_string(),
+            );
         }
 
         // Validate compression command
-        let valid_commands = ["gzip", "bzip2", "xz", "tar", "tgz", "tbz2", "txz"];
-        if !valid_commands.contains(&self.command.as_str()) {
-            return Err(format!("Invalid compression command: {}", self.command));
+        let valid_commands = [
+            "gzip",
+            "bzip2",
+            "xz",
+            "tar",
+            "tgz",
+            "tbz2",
+            "txz",
+            AUTO_COMMAND,
+        ];
+        if !valid_commands.contains(&self.archive.command.as_str()) {
+            return Err(format!(
+                "Invalid compression command: {}",
+                self.archive.command
+            ));
+        }
+
+        // -c auto samples each input to pick its format
+        let auto = self.archive.command == AUTO_COMMAND;
+        if self.archive.auto_goal.is_some() || self.archive.time_budget.is_some() {
+            if !auto {
+                return Err("--auto-goal and --time-budget require -c auto".to_string());
+            }
+            self.auto_goal()?;
+        }
+
+        // Already compressed inputs are only skipped when compressing inputs one by one
+        if self.archive.force_compress || self.archive.skip_incompressible {
+            if self.archive.force_compress && self.archive.skip_incompressible {
+                return Err(
+                    "--force-compress cannot be used with --skip-incompressible".to_string()
+                );
+            }
+            let edit = self.append.is_some() || self.update.is_some() || self.delete.is_some();
+            let backup = self.incremental.is_some() || self.differential.is_some();
+            let collecting = self.collect.is_some() || self.collect_flat.is_some();
+            if self.decompress
+                || self.convert.is_some()
+                || edit
+                || backup
+                || collecting
+                || self.dedup.is_some()
+            {
+                return Err(
+                    "--force-compress and --skip-incompressible only apply when compressing inputs one by one"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Excludes leave entries out of new archives and recursive runs
+        if !self.archive.exclude.is_empty() {
+            if self.decompress || self.convert.is_some() {
+                return Err("--exclude cannot be used with -d or --convert".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--exclude cannot be used with --append, --update or --delete".to_string(),
+                );
+            }
+            if self.dedup.is_some() {
+                return Err("--exclude cannot be used with --dedup".to_string());
+            }
+            // Removing a directory would also remove the entries left out of its archive
+            if self.archive.remove_source && !self.recursive {
+                return Err(
+                    "--exclude cannot be used with --remove-source, except with -r".to_string(),
+                );
+            }
+        }
+
+        // Validate conflict policy
+        if let Some(ref policy) = self.output.on_conflict {
+            if ConflictPolicy::from_name(policy).is_none() {
+                return Err(format!("Invalid conflict policy: {}", policy));
+            }
+        }
+
+        // Source removal only makes sense for compression, archive removal for decompression
+        if self.archive.remove_source && self.decompress {
+            return Err("--remove-source cannot be used with -d, use --remove-archive".to_string());
+        }
+        if self.remove_archive && !self.decompress {
+            return Err("--remove-archive requires -d".to_string());
+        }
+
+        // Conversion replaces the compress/decompress modes
+        if let Some(ref target) = self.convert {
+            if ConvertTarget::from_name(target).is_none() {
+                return Err(format!("Invalid conversion target: {}", target));
+            }
+            if self.decompress || self.recursive {
+                return Err("--convert cannot be used with -d or -r".to_string());
+            }
+            if self.collect.is_some() || self.collect_flat.is_some() {
+                return Err("--convert cannot be used with -a or -A".to_string());
+            }
+        }
+
+        // Archive edits work on a single existing archive
+        if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+            if self.decompress || self.recursive || self.convert.is_some() {
+                return Err(
+                    "--append, --update and --delete cannot be used with -d, -r or --convert"
+                        .to_string(),
+                );
+            }
+            if self.collect.is_some()
+                || self.collect_flat.is_some()
+                || self.output.move_to.is_some()
+            {
+                return Err(
+                    "--append, --update and --delete cannot be used with -a, -A or -C".to_string(),
+                );
+            }
+            if self.archive.remove_source {
+                return Err(
+                    "--remove-source cannot be used with --append, --update or --delete"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Splitting applies to newly written archives
+        if let Some(ref size) = self.archive.split_size {
+            if parse_size(size).is_none_or(|bytes| bytes == 0) {
+                return Err(format!("Invalid split size: {}", size));
+            }
+            if self.decompress || self.convert.is_some() {
+                return Err("--split-size cannot be used with -d or --convert".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--split-size cannot be used with --append, --update or --delete".to_string(),
+                );
+            }
+        }
+
+        // Encryption applies to newly written archives, identities to decryption
+        if self.archive.encrypt || !self.archive.recipient.is_empty() {
+            if self.decompress || self.convert.is_some() {
+                return Err(
+                    "--encrypt and --recipient cannot be used with -d or --convert".to_string(),
+                );
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--encrypt and --recipient cannot be used with --append, --update or --delete"
+                        .to_string(),
+                );
+            }
+            parse_recipients(&self.archive.recipient).map_err(|e| e.to_string())?;
+        }
+        if self.archive.passphrase_file.is_some() && !self.archive.recipient.is_empty() {
+            return Err("--passphrase-file cannot be used with --recipient".to_string());
+        }
+        if !self.identity.is_empty() && !self.decompress {
+            return Err("--identity requires -d".to_string());
+        }
+
+        // Checksums are written for new archives and checked before decompression
+        if let Some(ref algorithm) = self.archive.checksum {
+            if ChecksumAlgorithm::from_name(algorithm).is_none() {
+                return Err(format!("Invalid checksum algorithm: {}", algorithm));
+            }
+            if self.decompress || self.convert.is_some() {
+                return Err("--checksum cannot be used with -d or --convert".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--checksum cannot be used with --append, --update or --delete".to_string(),
+                );
+            }
+        }
+        if self.verify_checksum && !self.decompress {
+            return Err("--verify-checksum requires -d".to_string());
+        }
+
+        // Reproducible output only applies to newly written, unencrypted archives
+        if self.archive.reproducible {
+            if self.decompress || self.convert.is_some() {
+                return Err("--reproducible cannot be used with -d or --convert".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--reproducible cannot be used with --append, --update or --delete".to_string(),
+                );
+            }
+            if self.archive.encrypt || !self.archive.recipient.is_empty() {
+                return Err(
+                    "--reproducible cannot be used with --encrypt or --recipient".to_string(),
+                );
+            }
+        }
+
+        // -o names the output of a single compression, collection or decompression
+        if self.output.output.is_some() {
+            if self.output.move_to.is_some() {
+                return Err("Cannot specify both -o and -C".to_string());
+            }
+            if self.archive.output_template.is_some() {
+                return Err("Cannot specify both -o and --output-template".to_string());
+            }
+            if self.convert.is_some() {
+                return Err("-o cannot be used with --convert".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err("-o cannot be used with --append, --update or --delete".to_string());
+            }
+        }
+
+        // Templates name newly written archives
+        if let Some(ref template) = self.archive.output_template {
+            validate_template(template)?;
+            if self.decompress {
+                return Err("--output-template cannot be used with -d".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--output-template cannot be used with --append, --update or --delete"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Retention prunes earlier timestamped outputs after compressing
+        let keeps = [
+            ("--keep-last", self.archive.keep_last),
+            ("--keep-daily", self.archive.keep_daily),
+            ("--keep-weekly", self.archive.keep_weekly),
+            ("--keep-monthly", self.archive.keep_monthly),
+        ];
+        if let Some((flag, _)) = keeps.iter().find(|(_, count)| *count == Some(0)) {
+            return Err(format!("{} must be at least 1", flag));
+        }
+        if let Some(ref age) = self.archive.max_age {
+            if parse_max_age(age).is_none() {
+                return Err(format!("Invalid maximum age: {}", age));
+            }
+        }
+        let retention =
+            keeps.iter().any(|(_, count)| count.is_some()) || self.archive.max_age.is_some();
+        if self.archive.prune_dry_run && !retention {
+            return Err(
+                "--prune-dry-run requires --keep-last, --keep-daily, --keep-weekly, --keep-monthly or --max-age"
+                    .to_string(),
+            );
+        }
+        if retention {
+            // Pruning finds earlier outputs by extension, which -c auto may change
+            if auto && !self.decompress {
+                return Err("Retention options cannot be used with -c auto".to_string());
+            }
+            if self.decompress || self.convert.is_some() || self.recursive {
+                return Err("Retention options cannot be used with -d, --convert or -r".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "Retention options cannot be used with --append, --update or --delete"
+                        .to_string(),
+                );
+            }
+            // Outputs are dated by the template's placeholder or the timestamp
+            let placeholder = match self.archive.output_template {
+                Some(ref template) => Some(stamp_placeholder(template)?),
+                None => None,
+            };
+            if placeholder != Some("date") {
+                if let Some(ref pattern) = self.archive.timestamp_format {
+                    validate_stamp_pattern(pattern)?;
+                } else if placeholder.is_none() && self.archive.timestamp == 0 {
+                    return Err(
+                        "Retention options require -t, --timestamp-format or --output-template"
+                            .to_string(),
+                    );
+                }
+            }
+            // Flat collections are named after the package alone
+            if self.collect_flat.is_some() && self.archive.output_template.is_none() {
+                return Err("Retention options with -A require --output-template".to_string());
+            }
+        }
+
+        // Incremental backups archive one directory against a snapshot state file
+        if self.incremental.is_some() || self.differential.is_some() {
+            if self.incremental.is_some() && self.differential.is_some() {
+                return Err("Cannot specify both --incremental and --differential".to_string());
+            }
+            if self.decompress || self.convert.is_some() || self.recursive {
+                return Err(
+                    "--incremental and --differential cannot be used with -d, --convert or -r"
+                        .to_string(),
+                );
+            }
+            if self.collect.is_some() || self.collect_flat.is_some() {
+                return Err(
+                    "--incremental and --differential cannot be used with -a or -A".to_string(),
+                );
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--incremental and --differential cannot be used with --append, --update or --delete"
+                        .to_string(),
+                );
+            }
+            if self.archive.remove_source || retention {
+                return Err(
+                    "--incremental and --differential cannot be used with --remove-source or retention options"
+                        .to_string(),
+                );
+            }
+            if !["tar", "tgz", "tbz2", "txz"].contains(&self.archive.command.as_str()) {
+                return Err(format!(
+                    "--incremental and --differential require a TAR format (tar, tgz, tbz2, txz), got: {}",
+                    self.archive.command
+                ));
+            }
+            if self.inputs.len() != 1 {
+                return Err(
+                    "--incremental and --differential back up exactly one directory".to_string(),
+                );
+            }
+        }
+
+        // Restores apply a chain of archives to one directory
+        if self.restore.is_some() {
+            if !self.decompress {
+                return Err("--restore requires -d".to_string());
+            }
+            if self.recursive || self.output.move_to.is_some() || self.output.output.is_some() {
+                return Err("--restore cannot be used with -r, -C or -o".to_string());
+            }
+            if self.remove_archive {
+                return Err("--restore cannot be used with --remove-archive".to_string());
+            }
+        }
+
+        // Dedup mode stores inputs as a snapshot instead of writing an archive
+        if self.dedup.is_some() {
+            if self.decompress || self.convert.is_some() || self.recursive {
+                return Err("--dedup cannot be used with -d, --convert or -r".to_string());
+            }
+            if self.append.is_some() || self.update.is_some() || self.delete.is_some() {
+                return Err(
+                    "--dedup cannot be used with --append, --update or --delete".to_string()
+                );
+            }
+            if self.incremental.is_some() || self.differential.is_some() || retention {
+                return Err(
+                    "--dedup cannot be used with --incremental, --differential or retention options"
+                        .to_string(),
+                );
+            }
+            if self.output.move_to.is_some()
+                || self.output.output.is_some()
+                || self.archive.split_size.is_some()
+            {
+                return Err("--dedup cannot be used with -C, -o or --split-size".to_string());
+            }
+            if self.archive.encrypt
+                || !self.archive.recipient.is_empty()
+                || self.archive.checksum.is_some()
+            {
+                return Err(
+                    "--dedup cannot be used with --encrypt, --recipient or --checksum".to_string(),
+                );
+            }
+            if self.manifest || self.archive.remove_source || self.archive.reproducible {
+                return Err(
+                    "--dedup cannot be used with --manifest, --remove-source or --reproducible"
+                        .to_string(),
+                );
+            }
+            if !["gzip", "bzip2", "xz"].contains(&self.archive.command.as_str()) {
+                return Err(format!(
+                    "--dedup packs chunks with a single-file format (gzip, bzip2, xz), got: {}",
+                    self.archive.command
+                ));
+            }
+            if self.inputs.len() > 1 && self.collect.is_none() && self.collect_flat.is_none() {
+                return Err("--dedup with several inputs requires -a or -A".to_string());
+            }
+        }
+
+        // Recursive mode works on individual files
+        if self.recursive {
+            if self.collect.is_some() || self.collect_flat.is_some() {
+                return Err("-r cannot be used with -a or -A".to_string());
+            }
+            if !self.decompress && !["gzip", "bzip2", "xz"].contains(&self.archive.command.as_str())
+            {
+                return Err(format!(
+                    "-r requires a single-file format (gzip, bzip2, xz), got: {}",
+                    self.archive.command
+                ));
+            }
+        }
+
+        if self.manifest && self.collect.is_none() && self.collect_flat.is_none() {
+            return Err("--manifest requires -a or -A".to_string());
         }
 
         // Check that collect and collect_flat are not both specified
@@ -1,40 +1,344 @@
-use std::path::PathBuf;
+use clap::ArgMatches;
+use std::env;
+use std::path::{Path, PathBuf};
 
-use crate::cli::args::CliArgs;
-use crate::core::config::{CollectionConfig, CollectionMode, CompressionConfig, TimestampOption};
+use crate::cli::args::{BenchArgs, CliAction, CliArgs, DedupCommand, InfoArgs, InspectArgs};
+use crate::compressors::detect_format;
+use crate::core::config::{
+    CollectionConfig, CollectionMode, CompressionConfig, ConflictPolicy, EncryptionKeys,
+    OutputTemplate, RetentionPolicy, SkipPolicy, TimestampOption, TimestampPosition,
+    TimestampStyle,
+};
 use crate::core::error::{JcError, JcResult};
-use crate::core::types::{CompoundFormat, CompressionFormat};
-use crate::operations::{collect_and_compress, compound, compress, decompress};
-use crate::utils::{error, validate_input_files, validate_move_to};
+use crate::core::types::{ChecksumAlgorithm, CompoundFormat, CompressionFormat};
+use crate::operations::auto::AUTO_COMMAND;
+use crate::operations::bench::{parse_levels, BENCH_FORMATS};
+use crate::operations::{
+    archive_info, backup_incremental, bench_files, choose_format, choose_formats, cleanup_stale,
+    collect_and_compress, compound, compress, compress_auto, convert, decompress, extract_snapshot,
+    list_archive, list_repository, modify_archive, plan_auto, plan_collection, plan_compress,
+    plan_convert, plan_decompress, plan_dedup, plan_edit, plan_incremental, plan_recursive,
+    plan_restore, prune_outputs, recursive, recursive_files, render_table, restore_chain,
+    store_snapshot, test_archives, verify_repository, AutoChoice, ConvertTarget, Plan, TarEdit,
+};
+use crate::utils::checksum::merge_sidecars;
+use crate::utils::crypto::{is_encrypted, read_passphrase_file, PASSPHRASE_ENV};
+use crate::utils::prompt::prompt_passphrase;
+use crate::utils::retention::parse_max_age;
+use crate::utils::settings::{load_settings, PROFILE_ENV};
+use crate::utils::split::parse_size;
+use crate::utils::{
+    check_move_to, error, info, read_file_list, validate_input_files, validate_move_to, warn,
+};
+
+/// Environment variable with the timestamp for reproducible outputs
+const SOURCE_DATE_EPOCH_ENV: &str = "SOURCE_DATE_EPOCH";
+
+/// Fill in options not given on the command line from configuration files,
+/// the selected profile and `JCZ_*` environment variables
+pub fn configure(mut args: CliArgs, matches: &ArgMatches) -> JcResult<CliArgs> {
+    // compress, decompress and collect run as the equivalent flags; other
+    // subcommands take their own arguments only
+    let mut matches = matches;
+    if let Some(action) = args.action.take() {
+        match action.into_flags() {
+            Ok(flags) => {
+                args = flags;
+                matches = matches.subcommand().map(|(_, m)| m).unwrap_or(matches);
+            }
+            Err(action) => {
+                args.action = Some(*action);
+                return Ok(args);
+            }
+        }
+    }
+
+    let profile = args
+        .output
+        .profile
+        .clone()
+        .or_else(|| env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()));
+    let settings = load_settings(profile.as_deref(), &env::current_dir()?)?;
+    args.apply_settings(settings, matches);
+
+    if let Some(ref list) = args.file_list.files_from {
+        let listed = read_file_list(list, args.file_list.null)?;
+        args.inputs.extend(listed);
+    }
+
+    Ok(args)
+}
 
 /// Execute the appropriate command based on CLI arguments
-pub fn execute(args: CliArgs) -> JcResult<()> {
+pub fn execute(mut args: CliArgs) -> JcResult<()> {
+    if let Some(action) = args.action {
+        let action = match action.into_flags() {
+            Ok(flags) => return execute(flags),
+            Err(action) => *action,
+        };
+        return match action {
+            CliAction::List(inspect) => handle_list(inspect),
+            CliAction::Test(inspect) => handle_test(inspect),
+            CliAction::Info(info) => handle_info(info),
+            CliAction::Bench(bench) => handle_bench(bench),
+            CliAction::Cleanup { dirs } => handle_cleanup(dirs),
+            CliAction::Dedup { command } => handle_dedup(command),
+            CliAction::Compress(_) | CliAction::Decompress(_) | CliAction::Collect(_) => {
+                unreachable!("converted to flags")
+            }
+        };
+    }
+
     // Validate arguments
     args.validate().map_err(JcError::Other)?;
 
     // Build configuration
-    let timestamp = TimestampOption::from_u8(args.timestamp)
-        .ok_or(JcError::InvalidTimestampOption(args.timestamp))?;
+    let timestamp = match args.archive.timestamp_format {
+        Some(ref pattern) => TimestampOption::Custom(pattern.clone()),
+        None => TimestampOption::from_u8(args.archive.timestamp)
+            .ok_or(JcError::InvalidTimestampOption(args.archive.timestamp))?,
+    };
+    let position = match args.archive.timestamp_position {
+        Some(ref position) => TimestampPosition::from_name(position)
+            .ok_or_else(|| JcError::Other(format!("Invalid timestamp position: {}", position)))?,
+        None => TimestampPosition::default(),
+    };
 
+    let level_given = args.archive.level.is_some();
     let config = CompressionConfig::new()
-        .with_level(args.level)
+        .with_level(args.archive.level())
         .with_timestamp(timestamp)
-        .with_force(args.force);
+        .with_timestamp_style(TimestampStyle {
+            utc: args.archive.utc,
+            position,
+            from_mtime: args.archive.timestamp_from_mtime,
+        })
+        .with_force(args.output.force)
+        .with_fsync(args.output.fsync)
+        .with_remove_input(args.archive.remove_source || args.remove_archive);
+
+    // Inputs that are compressed already are left alone unless forced
+    let config = config
+        .with_skip(if args.archive.force_compress {
+            SkipPolicy::Never
+        } else if args.archive.skip_incompressible {
+            SkipPolicy::Incompressible
+        } else {
+            SkipPolicy::Compressed
+        })
+        .with_exclude(args.archive.exclude.clone());
+
+    let config = if let Some(ref policy) = args.output.on_conflict {
+        let policy = ConflictPolicy::from_name(policy)
+            .ok_or_else(|| JcError::Other(format!("Invalid conflict policy: {}", policy)))?;
+        config.with_on_conflict(policy)
+    } else {
+        config
+    };
+
+    let config = if let Some(ref size) = args.archive.split_size {
+        let bytes = parse_size(size)
+            .ok_or_else(|| JcError::Other(format!("Invalid split size: {}", size)))?;
+        config.with_split_size(bytes)
+    } else {
+        config
+    };
+
+    let config = if let Some(ref algorithm) = args.archive.checksum {
+        let algorithm = ChecksumAlgorithm::from_name(algorithm)
+            .ok_or_else(|| JcError::Other(format!("Invalid checksum algorithm: {}", algorithm)))?;
+        config.with_checksum(algorithm)
+    } else {
+        config
+    }
+    .with_verify_checksum(args.verify_checksum);
+
+    let config = if args.archive.reproducible {
+        config.with_reproducible(source_date_epoch()?)
+    } else {
+        config
+    };
 
-    let config = if let Some(ref move_to) = args.move_to {
-        validate_move_to(move_to)?;
+    let config = if let Some(ref template) = args.archive.output_template {
+        config.with_output_template(OutputTemplate::new(template))
+    } else {
+        config
+    };
+
+    let retain = args.archive.keep_last.is_some()
+        || args.archive.keep_daily.is_some()
+        || args.archive.keep_weekly.is_some()
+        || args.archive.keep_monthly.is_some()
+        || args.archive.max_age.is_some();
+    let config = if retain {
+        config.with_retention(RetentionPolicy {
+            keep_last: args.archive.keep_last,
+            keep_daily: args.archive.keep_daily,
+            keep_weekly: args.archive.keep_weekly,
+            keep_monthly: args.archive.keep_monthly,
+            max_age: args.archive.max_age.as_deref().and_then(parse_max_age),
+            dry_run: args.archive.prune_dry_run,
+        })
+    } else {
+        config
+    };
+
+    let config = if let Some(ref move_to) = args.output.move_to {
+        check_destination(move_to, args.output.dry_run)?;
         config.with_move_to(move_to.clone())
     } else {
         config
     };
 
+    // Archive edits take member names rather than files for --delete
+    if let Some(archive) = args.delete {
+        let members = args
+            .inputs
+            .iter()
+            .map(|m| m.to_string_lossy().to_string())
+            .collect();
+        if args.output.dry_run {
+            return print_plan(
+                plan_edit(&archive, "delete", &args.inputs),
+                args.output.json,
+            );
+        }
+        return handle_modify(&archive, TarEdit::Delete(members), level_given, config);
+    }
+
     // Validate input files
-    let inputs = validate_input_files(args.inputs)?;
+    let inputs = validate_input_files(args.inputs.clone())?;
     let input_paths: Vec<PathBuf> = inputs.iter().map(|f| f.real_path.clone()).collect();
 
-    if args.decompress {
+    // -o names a single output, and acts like -C when it is a directory
+    let config = match args.output.output.clone() {
+        Some(output) if output.is_dir() => {
+            check_destination(&output, args.output.dry_run)?;
+            config.with_move_to(output)
+        }
+        Some(output) => {
+            let collection = args.collect.is_some() || args.collect_flat.is_some();
+            if args.recursive || (input_paths.len() > 1 && !collection) {
+                return Err(JcError::Other(format!(
+                    "-o must be an existing directory when there are several outputs: {}",
+                    output.display()
+                )));
+            }
+            if config.timestamp != TimestampOption::None {
+                return Err(JcError::Other(
+                    "-t and --timestamp-format cannot be used when -o names a file".to_string(),
+                ));
+            }
+            if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+                if !parent.is_dir() {
+                    return Err(JcError::NotADirectory(parent.to_path_buf()));
+                }
+            }
+            config.with_output(output)
+        }
+        None => config,
+    };
+
+    // -c auto samples the inputs to settle on a format for each, or on one
+    // compound format for a collection
+    let mut choices = None;
+    let edit = args.append.is_some() || args.update.is_some();
+    if args.archive.command == AUTO_COMMAND && !args.decompress && args.convert.is_none() && !edit {
+        let goal = args.auto_goal().map_err(JcError::Other)?;
+        if args.collect.is_some() || args.collect_flat.is_some() {
+            let choice = choose_format(&input_paths, true, goal, args.archive.level())?;
+            info!("Chose {}", choice.describe());
+            args.archive.command = choice.command.to_string();
+        } else {
+            choices = Some(choose_formats(
+                &input_paths,
+                goal,
+                args.archive.level(),
+                config.skip,
+            ));
+        }
+    }
+
+    // Plan the run without touching anything, not even asking for a passphrase
+    let encrypt = args.archive.encrypt || !args.archive.recipient.is_empty();
+    if args.output.dry_run {
+        let config = config.with_encrypt(encrypt);
+        let plan = match choices {
+            Some(ref choices) => plan_auto(choices, &config)?,
+            None => plan_run(&args, &input_paths, &config)?,
+        };
+        return print_plan(plan, args.output.json);
+    }
+
+    // Passphrases are only asked for when they will be used: to encrypt
+    // without recipients, or to decompress an encrypted archive, listed or
+    // found below a directory with -r
+    let prompt = if args.decompress {
+        let archives = if args.recursive {
+            recursive_files(&input_paths, None, &config)?
+                .into_iter()
+                .map(|(file, _)| file)
+                .collect()
+        } else {
+            input_paths.clone()
+        };
+        args.identity.is_empty() && archives.iter().any(|p| is_encrypted(p))
+    } else {
+        encrypt && args.archive.recipient.is_empty()
+    };
+    let passphrase = if args.decompress || prompt {
+        read_passphrase(
+            args.archive.passphrase_file.as_deref(),
+            prompt,
+            !args.decompress,
+        )?
+    } else {
+        None
+    };
+
+    let config = config.with_encrypt(encrypt).with_keys(EncryptionKeys {
+        passphrase,
+        recipients: args.archive.recipient,
+        identities: args.identity,
+    });
+
+    if let Some(archive) = args.append {
+        // Append to an existing archive
+        handle_modify(&archive, TarEdit::Append(input_paths), level_given, config)
+    } else if let Some(archive) = args.update {
+        // Update an existing archive
+        handle_modify(&archive, TarEdit::Update(input_paths), level_given, config)
+    } else if let Some(ref target) = args.convert {
+        // Format conversion mode
+        handle_convert(input_paths, target, args.verify, config)
+    } else if args.recursive {
+        // Recursive per-file mode
+        handle_recursive(input_paths, args.decompress, &args.archive.command, config)
+    } else if let Some(ref target) = args.restore {
+        // Restore a chain of full and incremental backups
+        handle_restore(&input_paths, target, config)
+    } else if args.decompress {
         // Decompression mode
         handle_decompress(input_paths, config)
+    } else if let Some(state) = args.incremental.as_ref().or(args.differential.as_ref()) {
+        // Incremental or differential backup of one directory
+        handle_incremental(
+            &input_paths[0],
+            &args.archive.command,
+            state,
+            args.differential.is_some(),
+            config,
+        )
+    } else if let Some(ref repo) = args.dedup {
+        // Store the inputs in a deduplicating repository
+        handle_dedup_store(
+            &input_paths,
+            repo,
+            &args.archive.command,
+            args.collect.as_deref(),
+            args.collect_flat.as_deref(),
+            config,
+        )
     } else if args.collect.is_some() || args.collect_flat.is_some() {
         // Collection mode
         let mode = if args.collect.is_some() {
@@ -45,73 +349,294 @@ pub fn execute(args: CliArgs) -> JcResult<()> {
 
         let package_name = args.collect.or(args.collect_flat).unwrap();
 
-        handle_collection(input_paths, &args.command, package_name, mode, config)
+        handle_collection(
+            input_paths,
+            &args.archive.command,
+            package_name,
+            mode,
+            args.manifest,
+            config,
+        )
+    } else if let Some(choices) = choices {
+        // Compression with the formats -c auto chose
+        handle_auto_compress(choices, config)
     } else {
         // Standard compression mode
-        handle_compress(input_paths, &args.command, config)
+        handle_compress(input_paths, &args.archive.command, config)
     }
 }
 
-fn handle_decompress(inputs: Vec<PathBuf>, config: CompressionConfig) -> JcResult<()> {
-    let results = decompress::decompress_files(inputs, config);
+/// Check an output directory; a dry run must not create it or write a probe file
+fn check_destination(dir: &Path, dry_run: bool) -> JcResult<()> {
+    if dry_run {
+        check_move_to(dir)?;
+        Ok(())
+    } else {
+        validate_move_to(dir)
+    }
+}
 
-    // Check for errors
+/// Work out what the run would do, following the same dispatch as `execute`
+fn plan_run(args: &CliArgs, inputs: &[PathBuf], config: &CompressionConfig) -> JcResult<Plan> {
+    if let Some(ref archive) = args.append {
+        Ok(plan_edit(archive, "append", inputs))
+    } else if let Some(ref archive) = args.update {
+        Ok(plan_edit(archive, "update", inputs))
+    } else if let Some(ref target) = args.convert {
+        let target = ConvertTarget::from_name(target)
+            .ok_or_else(|| JcError::InvalidCommand(target.to_string()))?;
+        plan_convert(inputs, target, config)
+    } else if args.recursive {
+        plan_recursive(inputs, args.decompress, &args.archive.command, config)
+    } else if let Some(ref target) = args.restore {
+        plan_restore(inputs, target)
+    } else if args.decompress {
+        plan_decompress(inputs, config)
+    } else if let Some(state) = args.incremental.as_ref().or(args.differential.as_ref()) {
+        plan_incremental(
+            &inputs[0],
+            &args.archive.command,
+            state,
+            args.differential.is_some(),
+            config,
+        )
+    } else if let Some(ref repo) = args.dedup {
+        let (name, _) = dedup_name(
+            inputs,
+            args.collect.as_deref(),
+            args.collect_flat.as_deref(),
+        );
+        plan_dedup(inputs, repo, &name, config)
+    } else if let Some(name) = args.collect.as_ref().or(args.collect_flat.as_ref()) {
+        let mode = if args.collect.is_some() {
+            CollectionMode::WithParent
+        } else {
+            CollectionMode::Flat
+        };
+        plan_collection(inputs, &args.archive.command, name, mode, config)
+    } else {
+        plan_compress(inputs, &args.archive.command, config)
+    }
+}
+
+fn print_plan(plan: Plan, json: bool) -> JcResult<()> {
+    let text = plan.render(json)?;
+    if !text.is_empty() {
+        println!("{}", text);
+    }
+    info!(
+        "Dry run: {} output(s) planned, nothing was written",
+        plan.outputs.len()
+    );
+    Ok(())
+}
+
+/// Get the time reproducible archives are dated with
+///
+/// Follows the reproducible-builds convention: `SOURCE_DATE_EPOCH` if set,
+/// otherwise the Unix epoch itself.
+fn source_date_epoch() -> JcResult<i64> {
+    match env::var(SOURCE_DATE_EPOCH_ENV) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .filter(|epoch| *epoch >= 0)
+            .ok_or_else(|| JcError::Other(format!("Invalid {}: {}", SOURCE_DATE_EPOCH_ENV, value))),
+        _ => Ok(0),
+    }
+}
+
+/// Get the passphrase from a file, the environment or, if allowed, a prompt
+fn read_passphrase(file: Option<&Path>, prompt: bool, confirm: bool) -> JcResult<Option<String>> {
+    if let Some(file) = file {
+        return read_passphrase_file(file).map(Some);
+    }
+
+    if let Some(passphrase) = env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        return Ok(Some(passphrase));
+    }
+
+    if prompt {
+        prompt_passphrase(confirm).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Report per-input results, treating skipped inputs as non-fatal
+///
+/// Inputs left alone as not worth compressing are named again
+/// in a summary at the end of the batch.
+fn report_results(results: Vec<JcResult<PathBuf>>, operation: &str) -> JcResult<()> {
     let mut had_errors = false;
+    let mut incompressible = Vec::new();
     for result in results {
-        if let Err(e) = result {
-            error!("Decompression failed: {}", e);
-            had_errors = true;
+        match result {
+            Ok(_) => {}
+            Err(JcError::Skipped(path)) => {
+                info!("Skipped {}: output already exists", path.display());
+            }
+            Err(JcError::Incompressible { path, reason }) => {
+                info!("Skipped {}: {}", path.display(), reason);
+                incompressible.push(path.display().to_string());
+            }
+            Err(e) => {
+                error!("{} failed: {}", operation, e);
+                had_errors = true;
+            }
         }
     }
 
+    if !incompressible.is_empty() {
+        info!(
+            "Left {} input(s) uncompressed, --force-compress compresses them anyway: {}",
+            incompressible.len(),
+            incompressible.join(", ")
+        );
+    }
+
     if had_errors {
-        Err(JcError::Other(
-            "Some files failed to decompress".to_string(),
-        ))
+        Err(JcError::Other(format!(
+            "Some files failed to {}",
+            operation.to_lowercase()
+        )))
     } else {
         Ok(())
     }
 }
 
-fn handle_compress(inputs: Vec<PathBuf>, command: &str, config: CompressionConfig) -> JcResult<()> {
-    // Determine if simple or compound format
-    if let Some(compound) = CompoundFormat::from_str(command) {
-        // Compound format (tgz, tbz2, txz)
-        let results = compound::compress_compound_batch(inputs, compound, config);
+fn handle_decompress(inputs: Vec<PathBuf>, config: CompressionConfig) -> JcResult<()> {
+    let results = decompress::decompress_files(inputs, config);
+    report_results(results, "Decompress")
+}
 
-        let mut had_errors = false;
-        for result in results {
-            if let Err(e) = result {
-                error!("Compression failed: {}", e);
-                had_errors = true;
-            }
-        }
+/// Collect the checksum sidecars of a batch into one sums file per directory
+fn write_sums(results: &[JcResult<PathBuf>], config: &CompressionConfig) -> JcResult<()> {
+    if let Some(algorithm) = config.checksum {
+        let outputs: Vec<PathBuf> = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .cloned()
+            .collect();
+        merge_sidecars(&outputs, algorithm, config.fsync)?;
+    }
 
-        if had_errors {
-            Err(JcError::Other("Some files failed to compress".to_string()))
-        } else {
-            Ok(())
+    Ok(())
+}
+
+/// Remove earlier timestamped outputs of each input the retention policy expires
+fn apply_retention(
+    inputs: &[PathBuf],
+    results: &[JcResult<PathBuf>],
+    extension: &str,
+    config: &CompressionConfig,
+) -> JcResult<()> {
+    if config.retention.is_none() {
+        return Ok(());
+    }
+
+    for (input, result) in inputs.iter().zip(results) {
+        if let Ok(output) = result {
+            prune_outputs(input, output, extension, config)?;
         }
+    }
+
+    Ok(())
+}
+
+fn handle_compress(inputs: Vec<PathBuf>, command: &str, config: CompressionConfig) -> JcResult<()> {
+    let batch = inputs.len() > 1;
+    let batch_config = config.clone();
+    let names = inputs.clone();
+
+    // Determine if simple or compound format
+    let (results, extension) = if let Some(compound) = CompoundFormat::from_str(command) {
+        // Compound format (tgz, tbz2, txz)
+        let extension = config.output_extension(compound.extension());
+        (
+            compound::compress_compound_batch(inputs, compound, config),
+            extension,
+        )
     } else {
         // Simple format (gzip, bzip2, xz, tar)
         let format = CompressionFormat::from_name(command)
             .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?;
 
-        let results = compress::compress_files(inputs, format, config);
+        let extension = config.output_extension(format.extension());
+        (compress::compress_files(inputs, format, config), extension)
+    };
 
-        let mut had_errors = false;
-        for result in results {
-            if let Err(e) = result {
-                error!("Compression failed: {}", e);
-                had_errors = true;
-            }
-        }
+    if batch {
+        write_sums(&results, &batch_config)?;
+    }
+    apply_retention(&names, &results, &extension, &batch_config)?;
 
-        if had_errors {
-            Err(JcError::Other("Some files failed to compress".to_string()))
-        } else {
-            Ok(())
-        }
+    report_results(results, "Compress")
+}
+
+fn handle_auto_compress(
+    choices: Vec<JcResult<AutoChoice>>,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    let batch = choices.len() > 1;
+    let batch_config = config.clone();
+
+    let results = compress_auto(choices, config);
+    if batch {
+        write_sums(&results, &batch_config)?;
+    }
+
+    report_results(results, "Compress")
+}
+
+fn handle_convert(
+    inputs: Vec<PathBuf>,
+    target: &str,
+    verify: bool,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    let target = ConvertTarget::from_name(target)
+        .ok_or_else(|| JcError::InvalidCommand(target.to_string()))?;
+
+    let results = convert::convert_files(inputs, target, verify, config);
+    report_results(results, "Convert")
+}
+
+fn handle_modify(
+    archive: &Path,
+    edit: TarEdit,
+    level_given: bool,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    // A compressed archive is recompressed at -l, whatever level it was written at
+    if !level_given && detect_format(archive).is_some_and(|f| f != CompressionFormat::Tar) {
+        warn!(
+            "{} is recompressed at the default level {}; pass -l to keep another level",
+            archive.display(),
+            config.level
+        );
+    }
+    modify_archive(archive, &edit, &config)?;
+    Ok(())
+}
+
+fn handle_recursive(
+    inputs: Vec<PathBuf>,
+    decompress: bool,
+    command: &str,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    if decompress {
+        let results = recursive::decompress_recursive(inputs, config)?;
+        report_results(results, "Decompress")
+    } else {
+        let format = CompressionFormat::from_name(command)
+            .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?;
+        let sums_config = config.clone();
+        let results = recursive::compress_recursive(inputs, format, config)?;
+        write_sums(&results, &sums_config)?;
+        report_results(results, "Compress")
     }
 }
 
@@ -120,18 +645,219 @@ fn handle_collection(
     command: &str,
     package_name: String,
     mode: CollectionMode,
+    manifest: bool,
     config: CompressionConfig,
 ) -> JcResult<()> {
     let compound = CompoundFormat::from_str(command)
         .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?;
 
+    let extension = config.output_extension(compound.extension());
+    let name = PathBuf::from(&package_name);
     let collection_config = CollectionConfig {
-        base: config,
+        base: config.clone(),
         package_name,
         mode,
+        manifest,
+    };
+
+    match collect_and_compress(inputs, compound, collection_config) {
+        Ok(output) => apply_retention(&[name], &[Ok(output)], &extension, &config),
+        Err(JcError::Skipped(path)) => {
+            info!("Skipped {}: output already exists", path.display());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn handle_incremental(
+    input: &Path,
+    command: &str,
+    state: &Path,
+    differential: bool,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    match backup_incremental(input, command, state, differential, &config) {
+        Ok(_) => Ok(()),
+        Err(JcError::Skipped(path)) => {
+            info!("Skipped {}: output already exists", path.display());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn handle_restore(archives: &[PathBuf], target: &Path, config: CompressionConfig) -> JcResult<()> {
+    restore_chain(archives, target, &config)?;
+    Ok(())
+}
+
+/// Name and layout of a dedup snapshot
+///
+/// A single input is stored under its own name, like its archive would be.
+fn dedup_name(
+    inputs: &[PathBuf],
+    collect: Option<&str>,
+    collect_flat: Option<&str>,
+) -> (String, CollectionMode) {
+    match (collect, collect_flat) {
+        (Some(name), _) => (name.to_string(), CollectionMode::WithParent),
+        (None, Some(name)) => (name.to_string(), CollectionMode::Flat),
+        (None, None) => (
+            inputs[0]
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            CollectionMode::Flat,
+        ),
+    }
+}
+
+fn handle_dedup_store(
+    inputs: &[PathBuf],
+    repo: &Path,
+    command: &str,
+    collect: Option<&str>,
+    collect_flat: Option<&str>,
+    config: CompressionConfig,
+) -> JcResult<()> {
+    let format = CompressionFormat::from_name(command)
+        .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?;
+
+    let (name, mode) = dedup_name(inputs, collect, collect_flat);
+    match store_snapshot(inputs, repo, &name, mode, format, &config) {
+        Ok(_) => Ok(()),
+        Err(JcError::Skipped(path)) => {
+            info!("Skipped {}: output already exists", path.display());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn handle_dedup(command: DedupCommand) -> JcResult<()> {
+    match command {
+        DedupCommand::List { repo, snapshot } => list_repository(&repo, snapshot.as_deref()),
+        DedupCommand::Extract {
+            repo,
+            snapshot,
+            target,
+            fsync,
+        } => {
+            let target = target.unwrap_or_else(|| PathBuf::from("."));
+            let config = CompressionConfig::new().with_fsync(fsync);
+            extract_snapshot(&repo, &snapshot, &target, &config)?;
+            Ok(())
+        }
+        DedupCommand::Verify { repo, snapshots } => verify_repository(&repo, &snapshots),
+    }
+}
+
+/// Build the configuration of `list`, `test` and `info`, which only need keys
+/// to open encrypted archives
+fn inspect_config(inspect: &InspectArgs) -> JcResult<CompressionConfig> {
+    let prompt = inspect.identity.is_empty() && inspect.archives.iter().any(|p| is_encrypted(p));
+    let passphrase = read_passphrase(inspect.passphrase_file.as_deref(), prompt, false)?;
+
+    Ok(CompressionConfig::new().with_keys(EncryptionKeys {
+        passphrase,
+        recipients: Vec::new(),
+        identities: inspect.identity.clone(),
+    }))
+}
+
+fn handle_list(inspect: InspectArgs) -> JcResult<()> {
+    let config = inspect_config(&inspect)?;
+    let several = inspect.archives.len() > 1;
+    for (i, archive) in inspect.archives.iter().enumerate() {
+        let members = list_archive(archive, &config)?;
+        if several {
+            if i > 0 {
+                println!();
+            }
+            println!("{}:", archive.display());
+        }
+        for member in members {
+            println!("{}", member);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_test(inspect: InspectArgs) -> JcResult<()> {
+    let config = inspect_config(&inspect)?;
+    let results = test_archives(inspect.archives, config);
+    for path in results.iter().filter_map(|r| r.as_ref().ok()) {
+        println!("{}: OK", path.display());
+    }
+    report_results(results, "Test")
+}
+
+fn handle_info(info: InfoArgs) -> JcResult<()> {
+    let config = inspect_config(&info.inspect)?;
+    let mut reports = Vec::new();
+    for archive in &info.inspect.archives {
+        reports.push(archive_info(archive, &config)?);
+    }
+
+    if info.json {
+        let json = serde_json::to_string_pretty(&reports)
+            .map_err(|e| JcError::Other(format!("Failed to serialize archive info: {}", e)))?;
+        println!("{}", json);
+    } else {
+        let texts: Vec<String> = reports.iter().map(|r| r.render()).collect();
+        println!("{}", texts.join("\n\n"));
+    }
+
+    Ok(())
+}
+
+fn handle_bench(bench: BenchArgs) -> JcResult<()> {
+    let formats = if bench.formats.is_empty() {
+        BENCH_FORMATS.to_vec()
+    } else {
+        bench
+            .formats
+            .iter()
+            .map(|name| {
+                CompressionFormat::from_name(name)
+                    .filter(|format| BENCH_FORMATS.contains(format))
+                    .ok_or_else(|| JcError::InvalidCommand(name.to_string()))
+            })
+            .collect::<JcResult<_>>()?
+    };
+    let levels = parse_levels(&bench.levels)
+        .ok_or_else(|| JcError::Other(format!("Invalid levels: {}", bench.levels)))?;
+
+    let results = bench_files(&bench.files, &formats, &levels)?;
+    if results.is_empty() {
+        return Err(JcError::Other(format!(
+            "No format accepts the levels {}",
+            bench.levels
+        )));
+    }
+
+    if bench.json {
+        let json = serde_json::to_string_pretty(&results)
+            .map_err(|e| JcError::Other(format!("Failed to serialize benchmark results: {}", e)))?;
+        println!("{}", json);
+    } else {
+        println!("{}", render_table(&results));
+    }
+
+    Ok(())
+}
+
+fn handle_cleanup(dirs: Vec<PathBuf>) -> JcResult<()> {
+    let dirs = if dirs.is_empty() {
+        vec![PathBuf::from("."), env::temp_dir()]
+    } else {
+        dirs
     };
 
-    collect_and_compress(inputs, compound, collection_config)?;
+    let removed = cleanup_stale(&dirs)?;
+    info!("Removed {} stale item(s)", removed.len());
 
     Ok(())
 }
@@ -2,4 +2,4 @@ pub mod args;
 pub mod commands;
 
 pub use args::CliArgs;
-pub use commands::execute;
+pub use commands::{configure, execute};
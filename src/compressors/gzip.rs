@@ -1,15 +1,18 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 use crate::core::compressor::Compressor;
 use crate::core::config::CompressionConfig;
 use crate::core::error::{JcError, JcResult};
-use crate::utils::{copy_to_dir, debug, generate_output_filename, info, move_file_if_needed};
+use crate::utils::stream::{run_into_writer, verify_with};
+use crate::utils::{
+    copy_to_dir, debug, generate_output_filename, info, move_file_if_needed, resolve_conflict,
+    resolve_output_path, OutputWriter,
+};
 
 /// GZIP compressor implementation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct GzipCompressor;
 
 impl GzipCompressor {
@@ -43,7 +46,9 @@ impl Compressor for GzipCompressor {
     fn compress(&self, input: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
         self.validate_input(input)?;
 
-        let output_path = generate_output_filename(input, "gz", config.timestamp)?;
+        let output_path = generate_output_filename(input, &config.output_extension("gz"), config)?;
+        let output_path = resolve_output_path(&output_path, &config.move_to)?;
+        let (output_path, reservation) = resolve_conflict(&output_path, config.on_conflict)?;
         info!(
             "Compressing {} to {} with gzip",
             input.display(),
@@ -51,35 +56,22 @@ impl Compressor for GzipCompressor {
         );
         debug!("Compression level: {}", config.level);
 
-        // Create output file with buffered writer
-        let output_file = File::create(&output_path)?;
-        let mut writer = BufWriter::new(output_file);
+        // Create the output, split into volumes if requested
+        let mut writer = OutputWriter::new(&output_path, reservation, config)?;
 
         // Execute gzip command
         let mut cmd = Command::new("gzip");
-        cmd.arg(format!("-{}", config.level))
-            .arg("--keep")
-            .arg("--stdout")
-            .arg(input)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        debug!("Executing: {:?}", cmd);
-
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| JcError::Other(format!("Failed to spawn gzip: {}", e)))?;
-
-        // Stream stdout to output file
-        if let Some(mut stdout) = child.stdout.take() {
-            std::io::copy(&mut stdout, &mut writer)?;
+        cmd.arg(format!("-{}", config.level));
+        if config.reproducible.is_some() {
+            // Leave the input's name and mtime out of the header
+            cmd.arg("--no-name").env_remove("GZIP");
         }
+        cmd.arg("--keep").arg("--stdout").arg(input);
 
+        // Stream stdout to the output file
+        let output = run_into_writer(cmd, &mut writer)?;
         writer.flush()?;
 
-        // Wait for process and check exit status
-        let output = child.wait_with_output()?;
-
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(JcError::CompressionFailed {
@@ -88,8 +80,8 @@ impl Compressor for GzipCompressor {
             });
         }
 
-        // Move to destination if specified
-        let final_path = move_file_if_needed(&output_path, &config.move_to)?;
+        // Rename into place now that the output is complete
+        let final_path = writer.commit(config.fsync)?;
 
         info!("Compressed file: {}", final_path.display());
         Ok(final_path)
@@ -132,6 +124,26 @@ impl Compressor for GzipCompressor {
         Ok(final_path)
     }
 
+    fn compress_command(&self, level: u8) -> Option<Command> {
+        let mut cmd = Command::new("gzip");
+        cmd.arg(format!("-{}", level)).arg("--stdout");
+        Some(cmd)
+    }
+
+    fn decompress_command(&self) -> Option<Command> {
+        let mut cmd = Command::new("gzip");
+        cmd.arg("-d").arg("--stdout");
+        Some(cmd)
+    }
+
+    fn verify(&self, path: &Path, config: &CompressionConfig) -> JcResult<()> {
+        debug!("Verifying {} with gzip", path.display());
+
+        let mut cmd = Command::new("gzip");
+        cmd.arg("-t");
+        verify_with(cmd, path, &config.keys)
+    }
+
     fn supports_levels(&self) -> bool {
         true
     }
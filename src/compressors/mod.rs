@@ -7,6 +7,7 @@ use std::path::Path;
 
 use crate::core::compressor::Compressor;
 use crate::core::types::CompressionFormat;
+use crate::utils::split::first_volume_base;
 
 pub use bzip2::Bzip2Compressor;
 pub use gzip::GzipCompressor;
@@ -24,7 +25,13 @@ pub fn create_compressor(format: CompressionFormat) -> Box<dyn Compressor> {
 }
 
 /// Detect compression format from file extension
+///
+/// The first volume of a split archive (`.001`) reports the format of the set.
 pub fn detect_format(path: &Path) -> Option<CompressionFormat> {
+    if let Some(base) = first_volume_base(path) {
+        return detect_format(&base);
+    }
+
     path.extension()
         .and_then(|ext| ext.to_str())
         .and_then(CompressionFormat::from_extension)
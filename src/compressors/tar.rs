@@ -1,13 +1,19 @@
+use std::collections::HashSet;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::core::compressor::{Compressor, MultiFileCompressor};
 use crate::core::config::CompressionConfig;
 use crate::core::error::{JcError, JcResult};
-use crate::utils::{copy_to_dir, debug, generate_output_filename, info, move_file_if_needed};
+use crate::utils::stream::{run_into_writer, run_with_input, verify_with};
+use crate::utils::{
+    copy_to_dir, debug, generate_output_filename, info, move_file_if_needed, resolve_conflict,
+    resolve_output_path, AtomicOutput, OutputWriter, Reservation,
+};
 
 /// TAR archiver implementation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TarCompressor;
 
 impl TarCompressor {
@@ -30,7 +36,9 @@ impl Compressor for TarCompressor {
             return Err(JcError::FileNotFound(input.to_path_buf()));
         }
 
-        let output_path = generate_output_filename(input, "tar", config.timestamp)?;
+        let output_path = generate_output_filename(input, &config.output_extension("tar"), config)?;
+        let output_path = resolve_output_path(&output_path, &config.move_to)?;
+        let (output_path, reservation) = resolve_conflict(&output_path, config.on_conflict)?;
         info!(
             "Creating TAR archive {} from {}",
             output_path.display(),
@@ -39,34 +47,21 @@ impl Compressor for TarCompressor {
 
         // Build tar command - if input has no parent, use current directory
         let mut cmd = Command::new("tar");
+        self.reproducible_args(&mut cmd, config);
+        self.exclude_args(&mut cmd, config);
 
         if let Some(parent) = input.parent().filter(|p| !p.as_os_str().is_empty()) {
             let basename = input
                 .file_name()
                 .ok_or_else(|| JcError::Other("Invalid filename".to_string()))?;
             cmd.arg("-C").arg(parent);
-            cmd.arg("-cf").arg(&output_path).arg(basename);
+            cmd.arg("-cf").arg("-").arg(basename);
         } else {
             // No parent or empty parent, just use the input path directly
-            cmd.arg("-cf").arg(&output_path).arg(input);
+            cmd.arg("-cf").arg("-").arg(input);
         }
 
-        debug!("Executing: {:?}", cmd);
-
-        let output = cmd
-            .output()
-            .map_err(|e| JcError::Other(format!("Failed to execute tar: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(JcError::CompressionFailed {
-                tool: "tar".to_string(),
-                stderr: stderr.to_string(),
-            });
-        }
-
-        // Move to destination if specified
-        let final_path = move_file_if_needed(&output_path, &config.move_to)?;
+        let final_path = self.write_archive(cmd, &output_path, reservation, config)?;
 
         info!("Created TAR archive: {}", final_path.display());
         Ok(final_path)
@@ -112,6 +107,22 @@ impl Compressor for TarCompressor {
         Ok(final_path)
     }
 
+    fn compress_command(&self, _level: u8) -> Option<Command> {
+        None // TAR archives files, it doesn't compress streams
+    }
+
+    fn decompress_command(&self) -> Option<Command> {
+        None
+    }
+
+    fn verify(&self, path: &Path, config: &CompressionConfig) -> JcResult<()> {
+        debug!("Verifying TAR archive {}", path.display());
+
+        let mut cmd = Command::new("tar");
+        cmd.arg("-tf").arg("-");
+        verify_with(cmd, path, &config.keys)
+    }
+
     fn supports_levels(&self) -> bool {
         false // TAR doesn't support compression levels
     }
@@ -169,61 +180,299 @@ impl TarCompressor {
             });
         }
 
-        // TAR extracts files, so we need to find what was extracted
-        // List the directory to find extracted content
-        use std::fs;
-        let entries: Vec<_> = fs::read_dir(working_dir)
-            .map_err(|e| JcError::Io(e))?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path() != work_input) // Exclude the tar file itself
-            .collect();
-
-        // If we found exactly one entry, use that
-        if entries.len() == 1 {
-            let extracted_path = entries[0].path();
-            debug!("Extracted to: {}", extracted_path.display());
-            return Ok(extracted_path);
+        extracted_output(&work_input, working_dir)
+    }
+
+    /// Extract the final output `input` in `working_dir` without a copy of it
+    ///
+    /// The archive is streamed into `tar` on stdin, joining split volumes and
+    /// decrypting on the way; `layer` is the path the archive itself would
+    /// have in `working_dir`, which names the result like `decompress_in_dir`.
+    pub fn extract_stream_in_dir(
+        &self,
+        input: &Path,
+        layer: &Path,
+        working_dir: &Path,
+        config: &CompressionConfig,
+    ) -> JcResult<PathBuf> {
+        debug!(
+            "Extracting TAR stream {} in working dir {}",
+            input.display(),
+            working_dir.display()
+        );
+
+        let mut cmd = Command::new("tar");
+        cmd.arg("-x").arg("-C").arg(working_dir).arg("-f").arg("-");
+
+        let output = run_with_input(cmd, input, &config.keys)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(JcError::DecompressionFailed {
+                tool: "tar".to_string(),
+                stderr: stderr.to_string(),
+            });
         }
 
-        // Multiple files extracted - check if there's a common parent directory
-        // or a directory with the same base name as the tar file
-        let tar_base_name = work_input
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
-
-        // Check if there's a directory with the tar's base name
-        for entry in &entries {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
-                    if dir_name == tar_base_name {
-                        debug!("Extracted to directory: {}", path.display());
-                        return Ok(path);
-                    }
+        extracted_output(layer, working_dir)
+    }
+}
+
+/// Find what extracting the archive at `work_input` left in `working_dir`
+fn extracted_output(work_input: &Path, working_dir: &Path) -> JcResult<PathBuf> {
+    // TAR extracts files, so we need to find what was extracted
+    // List the directory to find extracted content
+    use std::fs;
+    let entries: Vec<_> = fs::read_dir(working_dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path() != work_input) // Exclude the tar file itself
+        .collect();
+
+    // If we found exactly one entry, use that
+    if entries.len() == 1 {
+        let extracted_path = entries[0].path();
+        debug!("Extracted to: {}", extracted_path.display());
+        return Ok(extracted_path);
+    }
+
+    // Multiple files extracted - check if there's a common parent directory
+    // or a directory with the same base name as the tar file
+    let tar_base_name = work_input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+
+    // Check if there's a directory with the tar's base name
+    for entry in &entries {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
+                if dir_name == tar_base_name {
+                    debug!("Extracted to directory: {}", path.display());
+                    return Ok(path);
                 }
             }
         }
+    }
+
+    // If we have multiple files but no matching directory, just return the working directory
+    // This happens when tar extracts multiple loose files
+    if !entries.is_empty() {
+        // Remove the tar file itself to avoid copying it to the destination
+        use std::fs;
+        let _ = fs::remove_file(work_input);
+
+        debug!(
+            "Extracted {} files to: {}",
+            entries.len(),
+            working_dir.display()
+        );
+        return Ok(working_dir.to_path_buf());
+    }
+
+    // Fallback: assume filename without .tar extension (original behavior)
+    let output_path = work_input.with_extension("");
+    debug!("Extracted to (fallback): {}", output_path.display());
+    Ok(output_path)
+}
+
+impl TarCompressor {
+    /// Add the options that make archives independent of the filesystem,
+    /// the user running tar and the time of the run
+    fn reproducible_args(&self, cmd: &mut Command, config: &CompressionConfig) {
+        if let Some(epoch) = config.reproducible {
+            cmd.arg("--sort=name")
+                .arg("--format=gnu")
+                .arg("--owner=0")
+                .arg("--group=0")
+                .arg("--numeric-owner")
+                .arg(format!("--mtime=@{}", epoch))
+                .arg("--clamp-mtime")
+                .env_remove("TAR_OPTIONS");
+        }
+    }
 
-        // If we have multiple files but no matching directory, just return the working directory
-        // This happens when tar extracts multiple loose files
-        if !entries.is_empty() {
-            // Remove the tar file itself to avoid copying it to the destination
-            use std::fs;
-            let _ = fs::remove_file(&work_input);
-
-            debug!(
-                "Extracted {} files to: {}",
-                entries.len(),
-                working_dir.display()
-            );
-            return Ok(working_dir.to_path_buf());
+    /// Leave out the entries matching the configured exclude patterns
+    fn exclude_args(&self, cmd: &mut Command, config: &CompressionConfig) {
+        for pattern in &config.exclude {
+            cmd.arg(format!("--exclude={}", pattern));
         }
+    }
 
-        // Fallback: assume filename without .tar extension (original behavior)
-        let output_path = work_input.with_extension("");
-        debug!("Extracted to (fallback): {}", output_path.display());
-        Ok(output_path)
+    /// Stream the archive written by a `tar -cf -` command into `output_path`
+    fn write_archive(
+        &self,
+        cmd: Command,
+        output_path: &Path,
+        reservation: Reservation,
+        config: &CompressionConfig,
+    ) -> JcResult<PathBuf> {
+        let mut writer = OutputWriter::new(output_path, reservation, config)?;
+
+        let output = run_into_writer(cmd, &mut writer)?;
+        writer.flush()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(JcError::CompressionFailed {
+                tool: "tar".to_string(),
+                stderr: stderr.to_string(),
+            });
+        }
+
+        writer.commit(config.fsync)
+    }
+
+    /// Run a tar command and return its standard output
+    fn run_tar(&self, cmd: &mut Command) -> JcResult<String> {
+        debug!("Executing: {:?}", cmd);
+
+        let output = cmd
+            .output()
+            .map_err(|e| JcError::Other(format!("Failed to execute tar: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(JcError::CompressionFailed {
+                tool: "tar".to_string(),
+                stderr: stderr.to_string(),
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Add inputs to a tar command, each relative to its own parent directory
+    fn add_inputs(&self, cmd: &mut Command, inputs: &[PathBuf]) -> JcResult<()> {
+        for input in inputs {
+            let input = std::path::absolute(input)?;
+            let parent = input
+                .parent()
+                .ok_or_else(|| JcError::Other("Invalid input path".to_string()))?;
+            let basename = input
+                .file_name()
+                .ok_or_else(|| JcError::Other("Invalid filename".to_string()))?;
+            cmd.arg("-C").arg(parent).arg(basename);
+        }
+
+        Ok(())
+    }
+
+    /// Apply `edit` to a copy of `archive`, then replace the archive with it
+    fn edit_archive(
+        &self,
+        archive: &Path,
+        config: &CompressionConfig,
+        edit: impl FnOnce(&Path) -> JcResult<()>,
+    ) -> JcResult<PathBuf> {
+        if !archive.is_file() {
+            return Err(JcError::FileNotFound(archive.to_path_buf()));
+        }
+
+        let atomic_output = AtomicOutput::new(archive);
+        std::fs::copy(archive, atomic_output.temp_path())?;
+
+        edit(atomic_output.temp_path())?;
+
+        atomic_output.commit(config.fsync)
+    }
+
+    /// List the member names of an archive
+    pub fn list_members(&self, archive: &Path) -> JcResult<Vec<String>> {
+        let stdout = self.run_tar(Command::new("tar").arg("-tf").arg(archive))?;
+        Ok(stdout.lines().map(str::to_string).collect())
+    }
+
+    /// Append files or directories to an existing archive
+    pub fn append(
+        &self,
+        archive: &Path,
+        inputs: &[PathBuf],
+        config: &CompressionConfig,
+    ) -> JcResult<PathBuf> {
+        info!(
+            "Appending {} item(s) to {}",
+            inputs.len(),
+            archive.display()
+        );
+
+        self.edit_archive(archive, config, |temp| {
+            let mut cmd = Command::new("tar");
+            cmd.arg("-rf").arg(temp);
+            self.add_inputs(&mut cmd, inputs)?;
+            self.run_tar(&mut cmd).map(|_| ())
+        })
+    }
+
+    /// Add inputs that are missing from the archive and replace members that
+    /// are newer on disk
+    ///
+    /// `tar -u` only appends the newer copy, so the older occurrence of each
+    /// replaced member is deleted afterwards to keep a single entry per name.
+    pub fn update(
+        &self,
+        archive: &Path,
+        inputs: &[PathBuf],
+        config: &CompressionConfig,
+    ) -> JcResult<PathBuf> {
+        info!("Updating {} in {}", inputs.len(), archive.display());
+
+        self.edit_archive(archive, config, |temp| {
+            let existing: HashSet<String> = self.list_members(temp)?.into_iter().collect();
+
+            let mut cmd = Command::new("tar");
+            cmd.arg("-uvf").arg(temp);
+            self.add_inputs(&mut cmd, inputs)?;
+            let stdout = self.run_tar(&mut cmd)?;
+
+            let replaced: Vec<&str> = stdout
+                .lines()
+                .filter(|name| existing.contains(*name))
+                .collect();
+
+            for name in stdout.lines() {
+                debug!("Updated member: {}", name);
+            }
+
+            if !replaced.is_empty() {
+                self.run_tar(
+                    Command::new("tar")
+                        .arg("--delete")
+                        .arg("--no-recursion")
+                        .arg("--occurrence=1")
+                        .arg("-f")
+                        .arg(temp)
+                        .args(&replaced),
+                )?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Delete named members (and anything below them) from an archive
+    pub fn delete(
+        &self,
+        archive: &Path,
+        members: &[String],
+        config: &CompressionConfig,
+    ) -> JcResult<PathBuf> {
+        info!(
+            "Deleting {} member(s) from {}",
+            members.len(),
+            archive.display()
+        );
+
+        self.edit_archive(archive, config, |temp| {
+            self.run_tar(
+                Command::new("tar")
+                    .arg("--delete")
+                    .arg("-f")
+                    .arg(temp)
+                    .args(members),
+            )
+            .map(|_| ())
+        })
     }
 }
 
@@ -259,15 +508,25 @@ impl MultiFileCompressor for TarCompressor {
         // This ensures the intermediate TAR file is created in the same isolated
         // temp directory as the input files, avoiding race conditions in tests
         let mut output_path = parent_dir.join(output_name);
-        if !output_path.extension().map_or(false, |e| e == "tar") {
+        if output_path.extension().is_none_or(|e| e != "tar") {
             output_path.set_extension("tar");
         }
+        let output_path = resolve_output_path(&output_path, &config.move_to)?;
+        let (output_path, reservation) = resolve_conflict(&output_path, config.on_conflict)?;
 
         info!("Creating multi-file TAR archive: {}", output_path.display());
 
         let mut cmd = Command::new("tar");
+        self.reproducible_args(&mut cmd, config);
+        self.exclude_args(&mut cmd, config);
         cmd.arg("-C").arg(parent_dir);
-        cmd.arg("-cf").arg(&output_path);
+        cmd.arg("-cf").arg("-");
+
+        // Entries below directories are sorted by tar, the inputs themselves here
+        let mut inputs: Vec<&PathBuf> = inputs.iter().collect();
+        if config.reproducible.is_some() {
+            inputs.sort();
+        }
 
         for input in inputs {
             let basename = input
@@ -276,23 +535,6 @@ impl MultiFileCompressor for TarCompressor {
             cmd.arg(basename);
         }
 
-        debug!("Executing: {:?}", cmd);
-
-        let output = cmd
-            .output()
-            .map_err(|e| JcError::Other(format!("Failed to execute tar: {}", e)))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(JcError::CompressionFailed {
-                tool: "tar".to_string(),
-                stderr: stderr.to_string(),
-            });
-        }
-
-        // Move to destination if specified
-        let final_path = move_file_if_needed(&output_path, &config.move_to)?;
-
-        Ok(final_path)
+        self.write_archive(cmd, &output_path, reservation, config)
     }
 }
@@ -1,15 +1,18 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 use crate::core::compressor::Compressor;
 use crate::core::config::CompressionConfig;
 use crate::core::error::{JcError, JcResult};
-use crate::utils::{copy_to_dir, debug, generate_output_filename, info, move_file_if_needed};
+use crate::utils::stream::{run_into_writer, verify_with};
+use crate::utils::{
+    copy_to_dir, debug, generate_output_filename, info, move_file_if_needed, resolve_conflict,
+    resolve_output_path, OutputWriter,
+};
 
 /// XZ compressor implementation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct XzCompressor;
 
 impl XzCompressor {
@@ -42,7 +45,9 @@ impl Compressor for XzCompressor {
     fn compress(&self, input: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
         self.validate_input(input)?;
 
-        let output_path = generate_output_filename(input, "xz", config.timestamp)?;
+        let output_path = generate_output_filename(input, &config.output_extension("xz"), config)?;
+        let output_path = resolve_output_path(&output_path, &config.move_to)?;
+        let (output_path, reservation) = resolve_conflict(&output_path, config.on_conflict)?;
         info!(
             "Compressing {} to {} with xz",
             input.display(),
@@ -50,31 +55,22 @@ impl Compressor for XzCompressor {
         );
         debug!("Compression level: {}", config.level);
 
-        let output_file = File::create(&output_path)?;
-        let mut writer = BufWriter::new(output_file);
+        let mut writer = OutputWriter::new(&output_path, reservation, config)?;
 
         let mut cmd = Command::new("xz");
-        cmd.arg(format!("-{}", config.level))
-            .arg("--keep")
-            .arg("--stdout")
-            .arg(input)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        debug!("Executing: {:?}", cmd);
-
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| JcError::Other(format!("Failed to spawn xz: {}", e)))?;
-
-        if let Some(mut stdout) = child.stdout.take() {
-            std::io::copy(&mut stdout, &mut writer)?;
+        cmd.arg(format!("-{}", config.level));
+        if config.reproducible.is_some() {
+            // Multi-threaded output depends on the number of cores
+            cmd.arg("--threads=1")
+                .arg("--check=crc64")
+                .env_remove("XZ_DEFAULTS")
+                .env_remove("XZ_OPT");
         }
+        cmd.arg("--keep").arg("--stdout").arg(input);
 
+        let output = run_into_writer(cmd, &mut writer)?;
         writer.flush()?;
 
-        let output = child.wait_with_output()?;
-
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(JcError::CompressionFailed {
@@ -83,7 +79,7 @@ impl Compressor for XzCompressor {
             });
         }
 
-        let final_path = move_file_if_needed(&output_path, &config.move_to)?;
+        let final_path = writer.commit(config.fsync)?;
 
         info!("Compressed file: {}", final_path.display());
         Ok(final_path)
@@ -121,6 +117,26 @@ impl Compressor for XzCompressor {
         Ok(final_path)
     }
 
+    fn compress_command(&self, level: u8) -> Option<Command> {
+        let mut cmd = Command::new("xz");
+        cmd.arg(format!("-{}", level)).arg("--stdout");
+        Some(cmd)
+    }
+
+    fn decompress_command(&self) -> Option<Command> {
+        let mut cmd = Command::new("xz");
+        cmd.arg("-d").arg("--stdout");
+        Some(cmd)
+    }
+
+    fn verify(&self, path: &Path, config: &CompressionConfig) -> JcResult<()> {
+        debug!("Verifying {} with xz", path.display());
+
+        let mut cmd = Command::new("xz");
+        cmd.arg("-t");
+        verify_with(cmd, path, &config.keys)
+    }
+
     fn supports_levels(&self) -> bool {
         true
     }
@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use crate::core::config::CompressionConfig;
 use crate::core::error::JcResult;
@@ -18,6 +19,15 @@ pub trait Compressor: Send + Sync {
     /// Decompress a single file
     fn decompress(&self, input: &Path, config: &CompressionConfig) -> JcResult<PathBuf>;
 
+    /// Build a command that compresses stdin to stdout, if supported
+    fn compress_command(&self, level: u8) -> Option<Command>;
+
+    /// Build a command that decompresses stdin to stdout, if supported
+    fn decompress_command(&self) -> Option<Command>;
+
+    /// Verify the integrity of a file produced by this compressor
+    fn verify(&self, path: &Path, config: &CompressionConfig) -> JcResult<()>;
+
     /// Check if this compressor supports compression levels
     fn supports_levels(&self) -> bool;
 
@@ -1,12 +1,17 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::core::types::{ChecksumAlgorithm, ENCRYPTED_EXTENSION};
 
 /// Timestamp formatting options
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TimestampOption {
-    None,        // 0: No timestamp
-    Date,        // 1: YYYYMMDD
-    DateTime,    // 2: YYYYMMDD_HHMMSS
-    Nanoseconds, // 3: Nanoseconds only
+    None,           // 0: No timestamp
+    Date,           // 1: YYYYMMDD
+    DateTime,       // 2: YYYYMMDD_HHMMSS
+    Nanoseconds,    // 3: YYYYMMDD_HHMMSS_nnnnnnnnn
+    Custom(String), // strftime pattern from --timestamp-format
 }
 
 impl TimestampOption {
@@ -19,6 +24,186 @@ impl TimestampOption {
             _ => None,
         }
     }
+
+    /// strftime pattern for this option, or `None` for no timestamp
+    pub fn pattern(&self) -> Option<&str> {
+        match self {
+            TimestampOption::None => None,
+            TimestampOption::Date => Some("%Y%m%d"),
+            TimestampOption::DateTime => Some("%Y%m%d_%H%M%S"),
+            TimestampOption::Nanoseconds => Some("%Y%m%d_%H%M%S_%9f"),
+            TimestampOption::Custom(pattern) => Some(pattern),
+        }
+    }
+}
+
+/// Where the timestamp goes in an output name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampPosition {
+    /// Before the input name: `20251101_file.txt.gz`
+    Prefix,
+
+    /// After the input name: `file.txt_20251101.gz`
+    #[default]
+    Suffix,
+
+    /// Before the input's own extension: `file_20251101.txt.gz`
+    BeforeExtension,
+}
+
+impl TimestampPosition {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "prefix" => Some(TimestampPosition::Prefix),
+            "suffix" => Some(TimestampPosition::Suffix),
+            "before-extension" => Some(TimestampPosition::BeforeExtension),
+            _ => None,
+        }
+    }
+}
+
+/// How the time in output names is taken and placed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimestampStyle {
+    /// Format the time in UTC rather than local time
+    pub utc: bool,
+
+    /// Where the timestamp goes in the name
+    pub position: TimestampPosition,
+
+    /// Use the input's modification time rather than the current time
+    pub from_mtime: bool,
+}
+
+/// Template for output names, such as `{stem}-{date}.{ext}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    /// Template text with `{placeholder}`s; `{{` and `}}` are literal braces
+    pub pattern: String,
+
+    /// Position of the input in its batch, starting at 1, for `{counter}`
+    pub counter: usize,
+}
+
+impl OutputTemplate {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            counter: 1,
+        }
+    }
+
+    /// Template that renders exactly `name`
+    pub fn literal(name: &str) -> Self {
+        Self::new(&name.replace('{', "{{").replace('}', "}}"))
+    }
+}
+
+/// Which earlier timestamped outputs of the same input to keep
+///
+/// An archive is kept when any `keep_*` rule selects it (or when there are
+/// none) and it is not older than `max_age`. The newest archive is always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep the newest N archives
+    pub keep_last: Option<usize>,
+
+    /// Keep the newest archive of each of the last N days that have one
+    pub keep_daily: Option<usize>,
+
+    /// Keep the newest archive of each of the last N ISO weeks that have one
+    pub keep_weekly: Option<usize>,
+
+    /// Keep the newest archive of each of the last N months that have one
+    pub keep_monthly: Option<usize>,
+
+    /// Remove archives older than this, whatever the `keep_*` rules say
+    pub max_age: Option<Duration>,
+
+    /// Only report what would be removed
+    pub dry_run: bool,
+}
+
+/// How to handle an output path that already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Prompt the user (falls back to `Fail` when stdin is not a terminal)
+    Ask,
+
+    /// Replace the existing output
+    Overwrite,
+
+    /// Leave the existing output alone and skip this input
+    Skip,
+
+    /// Write to a unique name such as `name (1).gz`
+    Rename,
+
+    /// Abort with an error
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ask" => Some(ConflictPolicy::Ask),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "skip" => Some(ConflictPolicy::Skip),
+            "rename" => Some(ConflictPolicy::Rename),
+            "fail" => Some(ConflictPolicy::Fail),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Ask => "ask",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+/// Which inputs compression leaves as they are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SkipPolicy {
+    /// Compress every input
+    #[default]
+    Never,
+
+    /// Skip inputs that are already compressed, by extension or content
+    Compressed,
+
+    /// Also skip inputs whose content looks incompressible
+    Incompressible,
+}
+
+/// Key material for the encryption layer
+#[derive(Clone, Default)]
+pub struct EncryptionKeys {
+    /// Passphrase for passphrase-encrypted outputs and archives
+    pub passphrase: Option<String>,
+
+    /// age public keys (`age1...`) to encrypt outputs to
+    pub recipients: Vec<String>,
+
+    /// age identity files for decrypting recipient-encrypted archives
+    pub identities: Vec<PathBuf>,
+}
+
+impl fmt::Debug for EncryptionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKeys")
+            .field(
+                "passphrase",
+                &self.passphrase.as_ref().map(|_| "<redacted>"),
+            )
+            .field("recipients", &self.recipients)
+            .field("identities", &self.identities)
+            .finish()
+    }
 }
 
 /// Configuration for compression/decompression operations
@@ -30,15 +215,58 @@ pub struct CompressionConfig {
     /// Timestamp option for output filenames
     pub timestamp: TimestampOption,
 
+    /// Time zone, position and source of the timestamp
+    pub timestamp_style: TimestampStyle,
+
+    /// Template for output names, replacing `<input>[_<timestamp>]`
+    pub output_template: Option<OutputTemplate>,
+
     /// Destination directory for output files
     pub move_to: Option<PathBuf>,
 
+    /// Exact path of the output, instead of a name derived from the input
+    pub output: Option<PathBuf>,
+
     /// Show output file size (future feature)
     #[allow(dead_code)]
     pub show_output_size: bool,
 
-    /// Force overwrite without prompting
-    pub force: bool,
+    /// Policy for outputs that already exist
+    pub on_conflict: ConflictPolicy,
+
+    /// Flush outputs to stable storage before renaming them into place
+    pub fsync: bool,
+
+    /// Remove inputs (sources or archives) once their outputs are written and verified
+    pub remove_input: bool,
+
+    /// Split final outputs into volumes of at most this many bytes
+    pub split_size: Option<u64>,
+
+    /// Encrypt final outputs
+    pub encrypt: bool,
+
+    /// Keys for encrypting outputs and decrypting archives
+    pub keys: EncryptionKeys,
+
+    /// Write a checksum sidecar next to each final output
+    pub checksum: Option<ChecksumAlgorithm>,
+
+    /// Check archives against their checksum sidecar before decompressing
+    pub verify_checksum: bool,
+
+    /// Write byte-identical outputs for identical inputs, with every
+    /// timestamp clamped to this Unix time (`SOURCE_DATE_EPOCH`)
+    pub reproducible: Option<i64>,
+
+    /// Remove earlier timestamped outputs of the same inputs after compressing
+    pub retention: Option<RetentionPolicy>,
+
+    /// Inputs that compressing them one by one leaves alone
+    pub skip: SkipPolicy,
+
+    /// Patterns of entries left out of archives and recursive runs
+    pub exclude: Vec<String>,
 }
 
 impl Default for CompressionConfig {
@@ -46,9 +274,23 @@ impl Default for CompressionConfig {
         Self {
             level: 6,
             timestamp: TimestampOption::None,
+            timestamp_style: TimestampStyle::default(),
+            output_template: None,
             move_to: None,
+            output: None,
             show_output_size: false,
-            force: false,
+            on_conflict: ConflictPolicy::Ask,
+            fsync: false,
+            remove_input: false,
+            split_size: None,
+            encrypt: false,
+            keys: EncryptionKeys::default(),
+            checksum: None,
+            verify_checksum: false,
+            reproducible: None,
+            retention: None,
+            skip: SkipPolicy::default(),
+            exclude: Vec::new(),
         }
     }
 }
@@ -68,15 +310,124 @@ impl CompressionConfig {
         self
     }
 
+    pub fn with_timestamp_style(mut self, style: TimestampStyle) -> Self {
+        self.timestamp_style = style;
+        self
+    }
+
+    pub fn with_output_template(mut self, template: OutputTemplate) -> Self {
+        self.output_template = Some(template);
+        self
+    }
+
+    /// Number this config's input within a batch, for `{counter}` in templates
+    pub fn with_counter(mut self, counter: usize) -> Self {
+        if let Some(template) = self.output_template.as_mut() {
+            template.counter = counter;
+        }
+        self
+    }
+
     pub fn with_move_to(mut self, path: PathBuf) -> Self {
         self.move_to = Some(path);
         self
     }
 
+    pub fn with_output(mut self, path: PathBuf) -> Self {
+        self.output = Some(path);
+        self
+    }
+
+    /// Directory final outputs go to, if not next to their inputs
+    pub fn output_dir(&self) -> Option<PathBuf> {
+        match self.output {
+            Some(ref output) => Some(
+                output
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new("."))
+                    .to_path_buf(),
+            ),
+            None => self.move_to.clone(),
+        }
+    }
+
+    /// Shorthand for overwriting existing outputs without prompting
     pub fn with_force(mut self, force: bool) -> Self {
-        self.force = force;
+        if force {
+            self.on_conflict = ConflictPolicy::Overwrite;
+        }
+        self
+    }
+
+    pub fn with_on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.on_conflict = policy;
+        self
+    }
+
+    pub fn with_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn with_remove_input(mut self, remove_input: bool) -> Self {
+        self.remove_input = remove_input;
+        self
+    }
+
+    pub fn with_split_size(mut self, split_size: u64) -> Self {
+        self.split_size = Some(split_size);
+        self
+    }
+
+    pub fn with_encrypt(mut self, encrypt: bool) -> Self {
+        self.encrypt = encrypt;
+        self
+    }
+
+    pub fn with_keys(mut self, keys: EncryptionKeys) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn with_checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some(algorithm);
+        self
+    }
+
+    pub fn with_verify_checksum(mut self, verify: bool) -> Self {
+        self.verify_checksum = verify;
         self
     }
+
+    pub fn with_reproducible(mut self, epoch: i64) -> Self {
+        self.reproducible = Some(epoch);
+        self
+    }
+
+    pub fn with_retention(mut self, policy: RetentionPolicy) -> Self {
+        self.retention = Some(policy);
+        self
+    }
+
+    pub fn with_skip(mut self, policy: SkipPolicy) -> Self {
+        self.skip = policy;
+        self
+    }
+
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Extension of final outputs for a format, including the encryption layer
+    pub fn output_extension(&self, extension: &str) -> String {
+        if self.encrypt {
+            format!("{}.{}", extension, ENCRYPTED_EXTENSION)
+        } else {
+            extension.to_string()
+        }
+    }
 }
 
 /// Collection operation mode
@@ -100,4 +451,7 @@ pub struct CollectionConfig {
 
     /// Collection mode
     pub mode: CollectionMode,
+
+    /// Store a MANIFEST.json describing every member in the archive
+    pub manifest: bool,
 }
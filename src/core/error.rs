@@ -35,6 +35,25 @@ pub enum JcError {
     /// Archive/package name already exists
     NameExists(String),
 
+    /// Output already exists and the conflict policy forbids replacing it
+    OutputExists(PathBuf),
+
+    /// Input skipped because its output already exists
+    Skipped(PathBuf),
+
+    /// Input skipped because compressing it again would gain little
+    Incompressible { path: PathBuf, reason: String },
+
+    /// Volume of a split archive is missing
+    MissingVolume(PathBuf),
+
+    /// Archive does not match its recorded checksum
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+
     /// Move-to directory error
     MoveToError(String),
 
@@ -44,6 +63,15 @@ pub enum JcError {
     /// Decompression tool execution failed
     DecompressionFailed { tool: String, stderr: String },
 
+    /// Integrity check of an output failed
+    VerificationFailed { path: PathBuf, reason: String },
+
+    /// Encrypting an output failed
+    EncryptionFailed(String),
+
+    /// Decrypting an archive failed (wrong key, missing key or tampered data)
+    DecryptionFailed { path: PathBuf, reason: String },
+
     /// I/O error
     Io(io::Error),
 
@@ -99,6 +127,31 @@ impl fmt::Display for JcError {
                     name
                 )
             }
+            JcError::OutputExists(path) => {
+                write!(f, "{} already exists", path.display())
+            }
+            JcError::Skipped(path) => {
+                write!(f, "Skipped existing output: {}", path.display())
+            }
+            JcError::Incompressible { path, reason } => {
+                write!(f, "Skipped {}: {}", path.display(), reason)
+            }
+            JcError::MissingVolume(path) => {
+                write!(f, "Missing volume: {}", path.display())
+            }
+            JcError::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    path.display(),
+                    expected,
+                    actual
+                )
+            }
             JcError::MoveToError(msg) => {
                 write!(f, "Move-to directory error: {}", msg)
             }
@@ -108,6 +161,15 @@ impl fmt::Display for JcError {
             JcError::DecompressionFailed { tool, stderr } => {
                 write!(f, "{} decompression failed: {}", tool, stderr)
             }
+            JcError::VerificationFailed { path, reason } => {
+                write!(f, "Verification of {} failed: {}", path.display(), reason)
+            }
+            JcError::EncryptionFailed(msg) => {
+                write!(f, "Encryption failed: {}", msg)
+            }
+            JcError::DecryptionFailed { path, reason } => {
+                write!(f, "Decryption of {} failed: {}", path.display(), reason)
+            }
             JcError::Io(err) => {
                 write!(f, "I/O error: {}", err)
             }
@@ -7,8 +7,11 @@ pub mod types;
 #[allow(unused_imports)]
 pub use compressor::{Compressor, MultiFileCompressor};
 #[allow(unused_imports)]
-pub use config::{CollectionConfig, CollectionMode, CompressionConfig, TimestampOption};
+pub use config::{
+    CollectionConfig, CollectionMode, CompressionConfig, ConflictPolicy, EncryptionKeys,
+    OutputTemplate, RetentionPolicy, TimestampOption, TimestampPosition, TimestampStyle,
+};
 #[allow(unused_imports)]
 pub use error::{JcError, JcResult};
 #[allow(unused_imports)]
-pub use types::{CompoundFormat, CompressionFormat, InputFile, OperationMode};
+pub use types::{ChecksumAlgorithm, CompoundFormat, CompressionFormat, InputFile, OperationMode};
@@ -1,5 +1,8 @@
 use std::path::PathBuf;
 
+/// Extension added by the encryption layer
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
 /// Compression format/algorithm identifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CompressionFormat {
@@ -88,6 +91,7 @@ impl CompoundFormat {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "tgz" => Some(CompoundFormat::Tgz),
@@ -98,6 +102,42 @@ impl CompoundFormat {
     }
 }
 
+/// Digest algorithm for output checksums
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    /// All algorithms, in the order sidecars are looked up
+    pub const ALL: [ChecksumAlgorithm; 2] = [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Blake3];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(ChecksumAlgorithm::Sha256),
+            "blake3" => Some(ChecksumAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+
+    /// Extension of sidecar files, e.g. `name.tar.gz.sha256`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Name of the file collecting the checksums of a batch
+    pub fn sums_name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "SHA256SUMS",
+            ChecksumAlgorithm::Blake3 => "BLAKE3SUMS",
+        }
+    }
+}
+
 /// Operation mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
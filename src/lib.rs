@@ -8,8 +8,9 @@ pub mod utils;
 // These are exported for external use, so allow dead_code warnings
 #[allow(unused_imports)]
 pub use core::{
-    CollectionConfig, CollectionMode, CompressionConfig, CompressionFormat, Compressor, JcError,
-    JcResult, TimestampOption,
+    CollectionConfig, CollectionMode, CompressionConfig, CompressionFormat, Compressor,
+    ConflictPolicy, JcError, JcResult, OutputTemplate, RetentionPolicy, TimestampOption,
+    TimestampPosition, TimestampStyle,
 };
 
 #[allow(unused_imports)]
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 
 mod cli;
 mod compressors;
@@ -6,18 +6,22 @@ mod core;
 mod operations;
 mod utils;
 
-use cli::{execute, CliArgs};
-use utils::init_logger;
+use cli::{configure, execute, CliArgs};
+use utils::{init_logger, install_signal_handler};
 
 fn main() {
     // Initialize logging
     init_logger();
 
+    // Remove partial outputs if interrupted
+    install_signal_handler();
+
     // Parse command-line arguments
-    let args = CliArgs::parse();
+    let matches = CliArgs::command().get_matches();
+    let args = CliArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    // Execute command
-    match execute(args) {
+    // Apply configuration files and execute command
+    match configure(args, &matches).and_then(execute) {
         Ok(()) => {
             std::process::exit(0);
         }
@@ -0,0 +1,353 @@
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::compressors::create_compressor;
+use crate::core::config::{CompressionConfig, SkipPolicy};
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{CompoundFormat, CompressionFormat};
+use crate::operations::compound::compress_compound;
+use crate::operations::compress::compress_file;
+use crate::utils::probe::check_compressible;
+use crate::utils::{debug, error, info};
+
+/// Compression command that picks a format for each input by sampling it
+pub const AUTO_COMMAND: &str = "auto";
+
+/// Bytes of each input compressed with every candidate format
+const SAMPLE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Outputs within this fraction of the smallest count as equally small, so
+/// incompressible data goes to the fastest format
+const SIZE_TOLERANCE: f64 = 0.01;
+
+/// Formats tried for each input
+const CANDIDATES: [CompressionFormat; 3] = [
+    CompressionFormat::Gzip,
+    CompressionFormat::Bzip2,
+    CompressionFormat::Xz,
+];
+
+/// What `-c auto` picks a format for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoGoal {
+    /// Smallest output
+    Smallest,
+
+    /// Fastest compression
+    Fastest,
+
+    /// Smallest output that is estimated to compress within the budget
+    Budget(Duration),
+}
+
+impl AutoGoal {
+    /// Create a goal from its name; `budget` requires a time budget
+    pub fn from_name(name: &str, budget: Option<Duration>) -> Option<Self> {
+        match (name.to_lowercase().as_str(), budget) {
+            ("smallest", None) => Some(AutoGoal::Smallest),
+            ("fastest", None) => Some(AutoGoal::Fastest),
+            ("budget", Some(budget)) => Some(AutoGoal::Budget(budget)),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a time budget such as `500ms`, `2s` or `1m`
+pub fn parse_budget(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+    let value: u64 = value.parse().ok()?;
+    let budget = match unit {
+        "ms" => Duration::from_millis(value),
+        "s" => Duration::from_secs(value),
+        "m" => Duration::from_secs(value.checked_mul(60)?),
+        "h" => Duration::from_secs(value.checked_mul(60 * 60)?),
+        _ => return None,
+    };
+    (!budget.is_zero()).then_some(budget)
+}
+
+/// One candidate format compressing a sample
+#[derive(Debug, Clone, Copy)]
+pub struct Trial {
+    pub format: CompressionFormat,
+
+    /// Size of the compressed sample
+    pub compressed: u64,
+
+    /// Time taken to compress the sample
+    pub elapsed: Duration,
+
+    /// Time the whole input would take at the same rate
+    pub estimate: Duration,
+}
+
+/// Format picked for an input, with the trial that decided it
+#[derive(Debug, Clone)]
+pub struct AutoChoice {
+    pub inputs: Vec<PathBuf>,
+
+    /// Command to compress the inputs with, e.g. `xz` or `txz`
+    pub command: &'static str,
+
+    /// Bytes compressed by each trial
+    pub sample: u64,
+
+    pub trial: Trial,
+}
+
+impl AutoChoice {
+    /// Describe the choice on one line
+    pub fn describe(&self) -> String {
+        let names: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        format!(
+            "{}: {} ({:.1}% of a {}-byte sample, about {:.2}s in all)",
+            names.join(", "),
+            self.command,
+            ratio(self.trial.compressed, self.sample),
+            self.sample,
+            self.trial.estimate.as_secs_f64()
+        )
+    }
+}
+
+/// Compressed size as a percentage of the original size
+fn ratio(compressed: u64, original: u64) -> f64 {
+    if original == 0 {
+        100.0
+    } else {
+        compressed as f64 * 100.0 / original as f64
+    }
+}
+
+/// Read up to `limit` bytes from the start of `path` into `sample`, and add
+/// the size of the files below it to `total`
+///
+/// Directories contribute their regular files in name order, so a sample
+/// of a tree covers its first files the way a TAR archive would hold them.
+fn read_sample(path: &Path, limit: usize, sample: &mut Vec<u8>, total: &mut u64) -> JcResult<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        entries.sort();
+        for entry in entries {
+            read_sample(&entry, limit, sample, total)?;
+        }
+    } else if metadata.is_file() {
+        *total += metadata.len();
+        let wanted = limit.saturating_sub(sample.len());
+        if wanted > 0 {
+            File::open(path)?.take(wanted as u64).read_to_end(sample)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compress `sample` with `format` through its stream command
+fn run_trial(format: CompressionFormat, sample: &[u8], total: u64, level: u8) -> JcResult<Trial> {
+    let compressor = create_compressor(format);
+    let mut cmd = compressor
+        .compress_command(level)
+        .ok_or_else(|| JcError::Other(format!("{} cannot compress streams", format.name())))?;
+
+    let start = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| JcError::Other(format!("Failed to execute {}: {}", format.name(), e)))?;
+
+    let mut stdin = child.stdin.take();
+    let mut stdout = child.stdout.take();
+    let compressed = thread::scope(|scope| {
+        scope.spawn(move || {
+            if let Some(ref mut stdin) = stdin {
+                // The sample is all there is to feed; a closed pipe shows up as a failed exit
+                let _ = stdin.write_all(sample);
+            }
+        });
+        match stdout {
+            Some(ref mut stdout) => io::copy(stdout, &mut io::sink()),
+            None => Ok(0),
+        }
+    })?;
+
+    let status = child.wait()?;
+    let elapsed = start.elapsed();
+    if !status.success() {
+        return Err(JcError::Other(format!(
+            "{} failed to compress the sample",
+            format.name()
+        )));
+    }
+
+    let estimate = if sample.is_empty() {
+        elapsed
+    } else {
+        elapsed.mul_f64(total as f64 / sample.len() as f64)
+    };
+    debug!(
+        "Sampled {} bytes with {}: {} bytes in {:?}",
+        sample.len(),
+        format.name(),
+        compressed,
+        elapsed
+    );
+
+    Ok(Trial {
+        format,
+        compressed,
+        elapsed,
+        estimate,
+    })
+}
+
+/// Pick the trial that best meets the goal
+///
+/// When no format fits a time budget, the fastest one is used.
+fn pick(trials: &[Trial], goal: AutoGoal) -> Option<Trial> {
+    let fastest = |trials: &[Trial]| trials.iter().min_by_key(|t| t.elapsed).copied();
+    let smallest = |trials: &[Trial]| {
+        let size = trials.iter().map(|t| t.compressed).min()?;
+        let limit = size + (size as f64 * SIZE_TOLERANCE) as u64;
+        let close: Vec<Trial> = trials
+            .iter()
+            .filter(|t| t.compressed <= limit)
+            .copied()
+            .collect();
+        fastest(&close)
+    };
+
+    match goal {
+        AutoGoal::Smallest => smallest(trials),
+        AutoGoal::Fastest => fastest(trials),
+        AutoGoal::Budget(budget) => {
+            let within: Vec<Trial> = trials
+                .iter()
+                .filter(|t| t.estimate <= budget)
+                .copied()
+                .collect();
+            smallest(&within).or_else(|| fastest(trials))
+        }
+    }
+}
+
+/// Compound command for TAR archives compressed with `format`
+fn compound_command(format: CompressionFormat) -> &'static str {
+    match format {
+        CompressionFormat::Gzip => "tgz",
+        CompressionFormat::Bzip2 => "tbz2",
+        _ => "txz",
+    }
+}
+
+/// Sample `inputs` with every candidate format and pick one for the goal
+///
+/// With `archive`, or when the inputs include a directory, the choice is
+/// the matching compound format, e.g. `txz` rather than `xz`.
+pub fn choose_format(
+    inputs: &[PathBuf],
+    archive: bool,
+    goal: AutoGoal,
+    level: u8,
+) -> JcResult<AutoChoice> {
+    let mut sample = Vec::new();
+    let mut total = 0;
+    for input in inputs {
+        read_sample(input, SAMPLE_SIZE, &mut sample, &mut total)?;
+    }
+
+    let mut trials = Vec::new();
+    for format in CANDIDATES {
+        if create_compressor(format).validate_level(level) {
+            trials.push(run_trial(format, &sample, total, level)?);
+        }
+    }
+    let trial = pick(&trials, goal).ok_or(JcError::InvalidCompressionLevel {
+        algorithm: AUTO_COMMAND.to_string(),
+        level,
+    })?;
+
+    let archive = archive || inputs.iter().any(|p| p.is_dir());
+    Ok(AutoChoice {
+        inputs: inputs.to_vec(),
+        command: if archive {
+            compound_command(trial.format)
+        } else {
+            trial.format.name()
+        },
+        sample: sample.len() as u64,
+        trial,
+    })
+}
+
+/// Pick a format for each input on its own, reporting every choice
+///
+/// Inputs are sampled one after another rather than in parallel, since
+/// trials competing for the CPU would time each other and skew the fastest
+/// format and time budget goals. Inputs the skip policy rejects are not
+/// sampled; they come back as `JcError::Incompressible` in place of a
+/// choice, and inputs that cannot be sampled come back as their error.
+pub fn choose_formats(
+    inputs: &[PathBuf],
+    goal: AutoGoal,
+    level: u8,
+    skip: SkipPolicy,
+) -> Vec<JcResult<AutoChoice>> {
+    inputs
+        .iter()
+        .map(|input| {
+            let choice = check_compressible(input, skip)
+                .and_then(|()| choose_format(std::slice::from_ref(input), false, goal, level));
+            match choice {
+                Ok(ref choice) => info!("Chose {}", choice.describe()),
+                Err(JcError::Incompressible { .. }) => {}
+                Err(ref e) => error!("Failed to sample {}: {}", input.display(), e),
+            }
+            choice
+        })
+        .collect()
+}
+
+/// Compress each input with the format chosen for it, passing skipped inputs through
+pub fn compress_auto(
+    choices: Vec<JcResult<AutoChoice>>,
+    config: CompressionConfig,
+) -> Vec<JcResult<PathBuf>> {
+    info!("Compressing {} files with chosen formats", choices.len());
+
+    choices
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let choice = choice?;
+            let input = &choice.inputs[0];
+            let config = config.clone().with_counter(i + 1);
+            let result = match CompoundFormat::from_str(choice.command) {
+                Some(compound) => compress_compound(input, compound, &config),
+                None => compress_file(input, choice.trial.format, &config),
+            };
+            match result {
+                Err(JcError::Skipped(path)) => Err(JcError::Skipped(path)),
+                Err(e) => {
+                    error!("Failed to compress {}: {}", input.display(), e);
+                    Err(e)
+                }
+                ok => ok,
+            }
+        })
+        .collect()
+}
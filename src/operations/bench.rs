@@ -0,0 +1,349 @@
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::compressors::create_compressor;
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::CompressionFormat;
+use crate::utils::{create_decompress_temp_dir, debug, info};
+
+/// Formats benchmarked when none are given
+pub const BENCH_FORMATS: [CompressionFormat; 3] = [
+    CompressionFormat::Gzip,
+    CompressionFormat::Bzip2,
+    CompressionFormat::Xz,
+];
+
+/// Levels benchmarked when none are given
+pub const BENCH_LEVELS: &str = "1,6,9";
+
+/// How one format and level did on one file
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub file: PathBuf,
+    pub format: &'static str,
+    pub level: u8,
+
+    /// Size of the file in bytes
+    pub size: u64,
+
+    /// Size of the compressed output in bytes
+    pub compressed_size: u64,
+
+    /// Compressed size as a percentage of the original size
+    pub ratio: f64,
+
+    /// Original bytes compressed per second, in MB/s
+    pub compress_speed: f64,
+
+    /// Original bytes restored per second, in MB/s
+    pub decompress_speed: f64,
+
+    /// Peak resident memory of the compressor, in KiB
+    pub compress_memory: u64,
+
+    /// Peak resident memory of the decompressor, in KiB
+    pub decompress_memory: u64,
+}
+
+/// Parse levels such as `6`, `1,6,9` or `1-9`
+pub fn parse_levels(s: &str) -> Option<Vec<u8>> {
+    let mut levels = Vec::new();
+    for part in s.split(',') {
+        let part = part.trim();
+        match part.split_once('-') {
+            Some((low, high)) => {
+                let (low, high): (u8, u8) = (low.trim().parse().ok()?, high.trim().parse().ok()?);
+                if low > high {
+                    return None;
+                }
+                levels.extend(low..=high);
+            }
+            None => levels.push(part.parse().ok()?),
+        }
+    }
+
+    levels.sort_unstable();
+    levels.dedup();
+    Some(levels)
+}
+
+/// Megabytes per second for `bytes` processed in `elapsed`
+fn speed(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64().max(1e-9)
+}
+
+/// Interval between readings of a running tool's memory
+const MEMORY_POLL: Duration = Duration::from_millis(1);
+
+/// Longest the input is held back waiting to see the tool start, in case
+/// it runs under another name
+const EXEC_WAIT: Duration = Duration::from_millis(100);
+
+/// Peak resident memory of a process in KiB, once it runs `name`
+///
+/// Before the child execs, its status still describes jcz itself.
+fn high_water_mark(pid: &str, name: Option<&str>) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let field = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .map(str::trim)
+    };
+
+    // Process names are cut to 15 bytes
+    if let Some(name) = name {
+        if field("Name:")? != &name[..name.len().min(15)] {
+            return None;
+        }
+    }
+    field("VmHWM:")?.trim_end_matches("kB").trim().parse().ok()
+}
+
+/// Run `cmd` with `input` on stdin and return its peak resident memory in KiB
+///
+/// Linux carries the parent's peak into a child across exec, so the usage
+/// `wait4` reports is only the tool's own when it exceeds jcz's peak.
+/// Otherwise the tool's peak is read from `/proc` while it runs; the input
+/// is held back until the tool has started, so short runs are seen too.
+fn peak_memory(mut cmd: Command, input: &Path) -> JcResult<u64> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    let own_peak = high_water_mark("self", None).unwrap_or(0);
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| JcError::Other(format!("Failed to execute {}: {}", tool, e)))?;
+    let pid = child.id() as libc::pid_t;
+    let spawned = Instant::now();
+
+    // Poll until the child exits, leaving it unreaped for wait4
+    let mut stdin = child.stdin.take();
+    let mut feeder = None;
+    let mut polled = 0;
+    loop {
+        let peak = high_water_mark(&pid.to_string(), Some(&tool));
+        polled = polled.max(peak.unwrap_or(0));
+        if peak.is_some() || spawned.elapsed() > EXEC_WAIT {
+            if let Some(mut stdin) = stdin.take() {
+                let mut file = File::open(input)?;
+                feeder = Some(thread::spawn(move || io::copy(&mut file, &mut stdin)));
+            }
+        }
+        let mut info = MaybeUninit::<libc::siginfo_t>::zeroed();
+        // SAFETY: the pid belongs to our unreaped child and info is valid for the call
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                info.as_mut_ptr(),
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: waitid succeeded, and a zeroed pid means the child still runs
+        if unsafe { info.assume_init().si_pid() } != 0 {
+            break;
+        }
+        thread::sleep(MEMORY_POLL);
+    }
+    // A tool that fails early closes the pipe, which shows in its exit status
+    if let Some(feeder) = feeder {
+        let _ = feeder.join();
+    }
+
+    let mut status = 0;
+    let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: the child has exited but not been reaped, and both pointers are valid
+    let ret = unsafe { libc::wait4(pid, &mut status, 0, usage.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    if !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+        return Err(JcError::Other(format!(
+            "{} failed during the benchmark",
+            tool
+        )));
+    }
+
+    // SAFETY: wait4 succeeded, so it filled in the usage
+    let reported = unsafe { usage.assume_init() }.ru_maxrss as u64;
+    Ok(if reported > own_peak {
+        reported
+    } else {
+        polled
+    })
+}
+
+/// Compress and decompress `input` with one format and level
+///
+/// The archive is written by the same compressor `jcz -c FORMAT -l LEVEL`
+/// uses, in a temporary directory. Memory is measured on a separate run of
+/// the compressor's stream commands.
+pub fn bench_file(input: &Path, format: CompressionFormat, level: u8) -> JcResult<BenchResult> {
+    let compressor = create_compressor(format);
+    let size = fs::metadata(input)?.len();
+    let temp_dir = create_decompress_temp_dir()?;
+    let config = CompressionConfig::new()
+        .with_level(level)
+        .with_move_to(temp_dir.path().to_path_buf());
+
+    let start = Instant::now();
+    let output = compressor.compress(input, &config)?;
+    let compress_time = start.elapsed();
+    let compressed_size = fs::metadata(&output)?.len();
+
+    let start = Instant::now();
+    let restored = compressor.decompress(&output, &CompressionConfig::new())?;
+    let decompress_time = start.elapsed();
+    if fs::metadata(&restored)?.len() != size {
+        return Err(JcError::Other(format!(
+            "{} did not restore {} to its original size",
+            format.name(),
+            input.display()
+        )));
+    }
+
+    let compress_command = compressor
+        .compress_command(level)
+        .ok_or_else(|| JcError::Other(format!("{} cannot compress streams", format.name())))?;
+    let decompress_command = compressor
+        .decompress_command()
+        .ok_or_else(|| JcError::Other(format!("{} cannot decompress streams", format.name())))?;
+    let compress_memory = peak_memory(compress_command, input)?;
+    let decompress_memory = peak_memory(decompress_command, &output)?;
+
+    debug!(
+        "Benchmarked {} with {} -{}: {} -> {} bytes in {:?}",
+        input.display(),
+        format.name(),
+        level,
+        size,
+        compressed_size,
+        compress_time
+    );
+
+    Ok(BenchResult {
+        file: input.to_path_buf(),
+        format: format.name(),
+        level,
+        size,
+        compressed_size,
+        ratio: if size == 0 {
+            100.0
+        } else {
+            compressed_size as f64 * 100.0 / size as f64
+        },
+        compress_speed: speed(size, compress_time),
+        decompress_speed: speed(size, decompress_time),
+        compress_memory,
+        decompress_memory,
+    })
+}
+
+/// Benchmark every file with every format at every level it accepts
+///
+/// Runs one at a time so measurements do not compete for the CPU.
+pub fn bench_files(
+    inputs: &[PathBuf],
+    formats: &[CompressionFormat],
+    levels: &[u8],
+) -> JcResult<Vec<BenchResult>> {
+    let mut results = Vec::new();
+    for input in inputs {
+        if !input.is_file() {
+            return Err(JcError::NotAFile(input.to_path_buf()));
+        }
+        for &format in formats {
+            let compressor = create_compressor(format);
+            for &level in levels {
+                if !compressor.validate_level(level) {
+                    debug!("Skipping {} level {}", format.name(), level);
+                    continue;
+                }
+                info!(
+                    "Benchmarking {} with {} -{}",
+                    input.display(),
+                    format.name(),
+                    level
+                );
+                results.push(bench_file(input, format, level)?);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Lay results out as an aligned table
+pub fn render_table(results: &[BenchResult]) -> String {
+    let header = [
+        "FILE",
+        "FORMAT",
+        "LEVEL",
+        "SIZE",
+        "RATIO",
+        "COMPRESS",
+        "DECOMPRESS",
+        "MEM C",
+        "MEM D",
+    ];
+    let rows: Vec<[String; 9]> = results
+        .iter()
+        .map(|r| {
+            [
+                r.file.display().to_string(),
+                r.format.to_string(),
+                r.level.to_string(),
+                r.compressed_size.to_string(),
+                format!("{:.1}%", r.ratio),
+                format!("{:.1} MB/s", r.compress_speed),
+                format!("{:.1} MB/s", r.decompress_speed),
+                format!("{} KiB", r.compress_memory),
+                format!("{} KiB", r.decompress_memory),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    // Names and formats read left-aligned, numbers right-aligned
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                if i < 2 {
+                    format!("{:<width$}", cell)
+                } else {
+                    format!("{:>width$}", cell)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![line(header.to_vec())];
+    for row in &rows {
+        lines.push(line(row.iter().map(String::as_str).collect()));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::core::error::{JcError, JcResult};
+use crate::utils::atomic::owner_pid;
+use crate::utils::{debug, info};
+
+/// Check whether a process with the given PID is still running
+fn process_alive(pid: u32) -> bool {
+    if Path::new("/proc/self").exists() {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(true)
+}
+
+/// Check whether a directory entry is a jcz leftover whose owner is gone
+///
+/// Names that do not carry an owner PID may belong to the user, or to an
+/// older jcz that is still running, and are never removed.
+fn is_stale(name: &str) -> bool {
+    match owner_pid(name) {
+        Some(pid) => pid != std::process::id() && !process_alive(pid),
+        None => false,
+    }
+}
+
+/// Remove stale staging directories and partial outputs left by interrupted runs
+///
+/// Returns the paths that were removed.
+pub fn cleanup_stale(dirs: &[PathBuf]) -> JcResult<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(JcError::NotADirectory(dir.clone()));
+        }
+
+        debug!("Scanning {} for stale jcz files", dir.display());
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if !is_stale(&name) {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+
+            info!("Removed stale {}", path.display());
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
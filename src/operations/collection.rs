@@ -1,12 +1,20 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::compressors::{create_compressor, tar::TarCompressor};
 use crate::core::compressor::{Compressor, MultiFileCompressor};
-use crate::core::config::{CollectionConfig, CollectionMode, CompressionConfig, TimestampOption};
+use crate::core::config::{
+    CollectionConfig, CollectionMode, CompressionConfig, OutputTemplate, TimestampOption,
+};
 use crate::core::error::{JcError, JcResult};
 use crate::core::types::CompoundFormat;
-use crate::utils::{copy_recursive, create_temp_dir, debug, info, move_file, remove_file_silent};
+use crate::utils::atomic::STAGING_DIR_PREFIX;
+use crate::utils::exclude::is_excluded;
+use crate::utils::manifest::{write_manifest, MANIFEST_NAME};
+use crate::utils::template::render_stem;
+use crate::utils::{
+    copy_excluding, create_temp_dir, debug, info, move_file, remove_input, CleanupGuard,
+};
 
 /// Collect multiple files into a compressed archive
 pub fn collect_and_compress(
@@ -19,6 +27,23 @@ pub fn collect_and_compress(
         return Err(JcError::NoInputFiles);
     }
 
+    // Excluded inputs are left out like the entries below directories
+    let inputs: Vec<PathBuf> = inputs
+        .into_iter()
+        .filter(|input| {
+            let excluded = input
+                .file_name()
+                .is_some_and(|name| is_excluded(Path::new(name), &collection_config.base.exclude));
+            if excluded {
+                info!("Excluding {}", input.display());
+            }
+            !excluded
+        })
+        .collect();
+    if inputs.is_empty() {
+        return Err(JcError::NoInputFiles);
+    }
+
     // Check for duplicate basenames
     let basenames: Vec<String> = inputs
         .iter()
@@ -53,10 +78,10 @@ pub fn collect_and_compress(
     );
 
     // Create temporary staging directory
-    let temp_dir = create_temp_dir("jczpkg_")?;
+    let temp_dir = create_temp_dir(STAGING_DIR_PREFIX)?;
     debug!("Created temporary directory: {}", temp_dir.display());
 
-    // Ensure cleanup on exit
+    // Ensure cleanup on exit or interruption
     let _cleanup = CleanupGuard::new(temp_dir.clone());
 
     let staging_dir = match collection_config.mode {
@@ -72,7 +97,7 @@ pub fn collect_and_compress(
         }
     };
 
-    // Copy files to staging directory
+    // Copy files to staging directory, leaving out excluded entries
     for input in &inputs {
         let basename = input
             .file_name()
@@ -80,29 +105,65 @@ pub fn collect_and_compress(
         let dest = staging_dir.join(basename);
 
         debug!("Copying {} to {}", input.display(), dest.display());
-        copy_recursive(input, &dest)?;
+        copy_excluding(
+            input,
+            &dest,
+            Path::new(basename),
+            &collection_config.base.exclude,
+        )?;
     }
 
+    // Describe the staged files before they are archived
+    if collection_config.manifest {
+        if staging_dir.join(MANIFEST_NAME).exists() {
+            return Err(JcError::Other(format!(
+                "Cannot add a manifest, an input is already named {}",
+                MANIFEST_NAME
+            )));
+        }
+        write_manifest(&staging_dir, &inputs, collection_config.base.reproducible)?;
+    }
+
+    // A template names the archive after the package and its inputs
+    let base = &collection_config.base;
+    let archive_stem = match base.output_template {
+        Some(ref template) => {
+            let extension = base.output_extension(format.extension());
+            let name = PathBuf::from(&collection_config.package_name);
+            Some(render_stem(template, &name, &inputs, &extension, base)?)
+        }
+        None => None,
+    };
+
     // Create TAR archive
     let tar_compressor = TarCompressor::new();
 
     let tar_config = CompressionConfig {
         level: 0,
-        timestamp: collection_config.base.timestamp,
         move_to: None,
-        show_output_size: false,
-        force: collection_config.base.force,
+        fsync: false,
+        split_size: None,
+        encrypt: false,
+        checksum: None,
+        output: None,
+        output_template: archive_stem.as_deref().map(OutputTemplate::literal),
+        exclude: Vec::new(),
+        ..collection_config.base.clone()
     };
 
     // Generate TAR filename
     let tar_filename = if collection_config.mode == CollectionMode::Flat {
         // For flat mode, create TAR from staging dir contents
-        let file_list: Vec<PathBuf> = inputs
+        let mut file_list: Vec<PathBuf> = inputs
             .iter()
             .map(|p| staging_dir.join(p.file_name().unwrap()))
             .collect();
+        if collection_config.manifest {
+            file_list.push(staging_dir.join(MANIFEST_NAME));
+        }
 
-        tar_compressor.compress_multi(&file_list, &collection_config.package_name, &tar_config)?
+        let archive_name = archive_stem.unwrap_or(collection_config.package_name.clone());
+        tar_compressor.compress_multi(&file_list, &format!("{}.tar", archive_name), &tar_config)?
     } else {
         // Archive the package directory
         let archive_input = temp_dir.join(&collection_config.package_name);
@@ -111,58 +172,37 @@ pub fn collect_and_compress(
 
     debug!("Created TAR archive: {}", tar_filename.display());
 
-    // Apply secondary compression
-    let final_output = if format.secondary() != format.primary() {
-        let secondary_compressor = create_compressor(format.secondary());
-
-        // Remove timestamp to avoid duplication
-        let new_config = collection_config
-            .base
-            .clone()
-            .with_timestamp(TimestampOption::None);
-        let compressed = secondary_compressor.compress(&tar_filename, &new_config)?;
-
-        // Remove intermediate TAR
-        let _ = remove_file_silent(&tar_filename);
-
-        compressed
-    } else {
-        tar_filename
-    };
-
-    // Move to destination or current directory
+    // Destination is the directory of -o, the move-to directory or the current directory
     let destination = collection_config
         .base
-        .move_to
+        .output_dir()
         .unwrap_or_else(|| PathBuf::from("."));
 
-    let final_path = move_file(&final_output, &destination)?;
-
-    info!("Created collection archive: {}", final_path.display());
-    Ok(final_path)
-}
+    // Apply secondary compression, writing straight into the destination
+    let final_path = if format.secondary() != format.primary() {
+        let secondary_compressor = create_compressor(format.secondary());
 
-/// RAII guard for cleaning up temporary directory
-struct CleanupGuard {
-    path: PathBuf,
-}
+        // Remove timestamp and template to avoid duplication
+        let new_config = CompressionConfig {
+            output_template: None,
+            ..collection_config.base.clone()
+        }
+        .with_timestamp(TimestampOption::None)
+        .with_move_to(destination);
+        secondary_compressor.compress(&tar_filename, &new_config)?
+    } else {
+        move_file(&tar_filename, &destination)?
+    };
 
-impl CleanupGuard {
-    fn new(path: PathBuf) -> Self {
-        Self { path }
-    }
-}
+    info!("Created collection archive: {}", final_path.display());
 
-impl Drop for CleanupGuard {
-    fn drop(&mut self) {
-        if let Err(e) = fs::remove_dir_all(&self.path) {
-            debug!(
-                "Failed to cleanup temp directory {}: {}",
-                self.path.display(),
-                e
-            );
-        } else {
-            debug!("Cleaned up temp directory: {}", self.path.display());
+    // Remove the collected inputs once the archive is known to be intact
+    if collection_config.base.remove_input {
+        create_compressor(format.secondary()).verify(&final_path, &collection_config.base)?;
+        for input in &inputs {
+            remove_input(input)?;
         }
     }
+
+    Ok(final_path)
 }
@@ -1,16 +1,19 @@
 use rayon::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::compressors::create_compressor;
 use crate::core::config::CompressionConfig;
-use crate::core::config::TimestampOption;
+use crate::core::config::{OutputTemplate, TimestampOption};
 use crate::core::error::JcResult;
 use crate::core::types::CompoundFormat;
-use crate::utils::{debug, info, remove_file_silent};
+use crate::utils::atomic::STAGING_DIR_PREFIX;
+use crate::utils::probe::check_compressible;
+use crate::utils::template::render_stem;
+use crate::utils::{create_temp_dir_in, debug, info, remove_input, CleanupGuard};
 
 /// Compress file(s) with compound format (TAR + secondary compression)
 pub fn compress_compound(
-    input: &PathBuf,
+    input: &Path,
     format: CompoundFormat,
     config: &CompressionConfig,
 ) -> JcResult<PathBuf> {
@@ -20,29 +23,65 @@ pub fn compress_compound(
         format.extension()
     );
 
+    // The intermediate TAR lives in a staging directory next to the input, so
+    // it is removed on failure or interruption instead of being left behind
+    let input_dir = input
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let staging = CleanupGuard::new(create_temp_dir_in(input_dir, STAGING_DIR_PREFIX)?);
+    debug!("Created staging directory: {}", staging.path().display());
+
+    // A template names the final archive, so the TAR gets its rendered stem
+    let output_template = match config.output_template {
+        Some(ref template) => {
+            let extension = config.output_extension(format.extension());
+            let stem = render_stem(template, input, &[input], &extension, config)?;
+            Some(OutputTemplate::literal(&stem))
+        }
+        None => None,
+    };
+
     // Step 1: Create TAR archive
     let tar_compressor = create_compressor(format.primary());
     let tar_config = CompressionConfig {
-        level: 0, // TAR doesn't use compression level
-        timestamp: config.timestamp,
-        move_to: None, // Don't move intermediate file
-        show_output_size: false,
-        force: config.force,
+        level: 0,                                    // TAR doesn't use compression level
+        move_to: Some(staging.path().to_path_buf()), // Keep intermediate file in staging
+        fsync: false,
+        split_size: None, // Only the final archive is split, encrypted or checksummed
+        encrypt: false,
+        checksum: None,
+        output: None,
+        output_template,
+        ..config.clone()
     };
 
-    // Remove timestamp to avoid duplication
-    let new_config = config.clone().with_timestamp(TimestampOption::None);
-
     let tar_output = tar_compressor.compress(input, &tar_config)?;
     debug!("Created intermediate TAR: {}", tar_output.display());
 
-    // Step 2: Compress TAR with secondary compressor
+    // Step 2: Compress TAR with secondary compressor into the destination
+    // Remove timestamp and template to avoid duplication
+    let new_config = CompressionConfig {
+        output_template: None,
+        ..config.clone()
+    }
+    .with_timestamp(TimestampOption::None)
+    .with_move_to(
+        config
+            .output_dir()
+            .unwrap_or_else(|| input_dir.to_path_buf()),
+    );
+
     let secondary_compressor = create_compressor(format.secondary());
     let secondary_output = secondary_compressor.compress(&tar_output, &new_config)?;
 
-    // Step 3: Remove intermediate TAR file
-    if let Err(e) = remove_file_silent(&tar_output) {
-        debug!("Failed to remove intermediate TAR: {}", e);
+    // Step 3: The staging directory and intermediate TAR are removed on drop
+    drop(staging);
+
+    // Step 4: Remove the source once the archive is known to be intact
+    if config.remove_input {
+        secondary_compressor.verify(&secondary_output, &new_config)?;
+        remove_input(input)?;
     }
 
     info!("Created compound archive: {}", secondary_output.display());
@@ -50,6 +89,8 @@ pub fn compress_compound(
 }
 
 /// Compress multiple files with compound format
+///
+/// Files the skip policy rejects fail with `JcError::Incompressible`.
 pub fn compress_compound_batch(
     inputs: Vec<PathBuf>,
     format: CompoundFormat,
@@ -57,6 +98,10 @@ pub fn compress_compound_batch(
 ) -> Vec<JcResult<PathBuf>> {
     inputs
         .par_iter()
-        .map(|input| compress_compound(input, format, &config))
+        .enumerate()
+        .map(|(i, input)| {
+            check_compressible(input, config.skip)?;
+            compress_compound(input, format, &config.clone().with_counter(i + 1))
+        })
         .collect()
 }
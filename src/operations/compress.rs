@@ -1,15 +1,16 @@
 use rayon::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::compressors::create_compressor;
 use crate::core::config::CompressionConfig;
 use crate::core::error::{JcError, JcResult};
 use crate::core::types::CompressionFormat;
-use crate::utils::{error, info};
+use crate::utils::probe::check_compressible;
+use crate::utils::{error, info, remove_input};
 
 /// Compress a single file
 pub fn compress_file(
-    input: &PathBuf,
+    input: &Path,
     format: CompressionFormat,
     config: &CompressionConfig,
 ) -> JcResult<PathBuf> {
@@ -23,10 +24,21 @@ pub fn compress_file(
         });
     }
 
-    compressor.compress(input, config)
+    let output = compressor.compress(input, config)?;
+
+    // Only remove the source once the output is known to be intact
+    if config.remove_input {
+        compressor.verify(&output, config)?;
+        remove_input(input)?;
+    }
+
+    Ok(output)
 }
 
 /// Compress multiple files concurrently
+///
+/// Inputs the skip policy rejects fail with `JcError::Incompressible`; TAR
+/// archives only bundle their input, so every input is packed.
 pub fn compress_files(
     inputs: Vec<PathBuf>,
     format: CompressionFormat,
@@ -37,11 +49,18 @@ pub fn compress_files(
     // Use rayon for parallel processing
     inputs
         .par_iter()
-        .map(|input| match compress_file(input, format, &config) {
-            Ok(output) => Ok(output),
-            Err(e) => {
-                error!("Failed to compress {}: {}", input.display(), e);
-                Err(e)
+        .enumerate()
+        .map(|(i, input)| {
+            if format != CompressionFormat::Tar {
+                check_compressible(input, config.skip)?;
+            }
+            match compress_file(input, format, &config.clone().with_counter(i + 1)) {
+                Ok(output) => Ok(output),
+                Err(JcError::Skipped(path)) => Err(JcError::Skipped(path)),
+                Err(e) => {
+                    error!("Failed to compress {}: {}", input.display(), e);
+                    Err(e)
+                }
             }
         })
         .collect()
@@ -0,0 +1,388 @@
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use crate::compressors::{create_compressor, detect_format};
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{CompoundFormat, CompressionFormat};
+use crate::utils::checksum::remove_sidecars;
+use crate::utils::crypto::is_encrypted;
+use crate::utils::split::{collect_volumes, first_volume_base, input_files, spawn_concat};
+use crate::utils::{
+    debug, error, generate_output_filename_from, info, remove_input, resolve_conflict,
+    resolve_output_path, AtomicOutput,
+};
+
+/// Target format of a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertTarget {
+    /// Recompress the payload with a single-file format (gzip, bzip2, xz)
+    Simple(CompressionFormat),
+
+    /// Recompress a TAR archive into a compound format (tgz, tbz2, txz)
+    Compound(CompoundFormat),
+}
+
+impl ConvertTarget {
+    /// Parse a target from a command name
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(compound) = CompoundFormat::from_str(name) {
+            return Some(ConvertTarget::Compound(compound));
+        }
+
+        match CompressionFormat::from_name(name) {
+            Some(CompressionFormat::Tar) | None => None,
+            Some(format) => Some(ConvertTarget::Simple(format)),
+        }
+    }
+
+    /// The compression format applied by this target
+    fn format(&self) -> CompressionFormat {
+        match self {
+            ConvertTarget::Simple(format) => *format,
+            ConvertTarget::Compound(compound) => compound.secondary(),
+        }
+    }
+}
+
+/// A running tool in a conversion pipeline
+struct Stage {
+    tool: &'static str,
+    child: Child,
+
+    /// Collects the tool's standard error while the pipeline runs
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+impl Stage {
+    /// Track a spawned tool, draining its standard error on its own thread
+    ///
+    /// Every stage writes warnings concurrently with the others, so reading
+    /// stderr only after waiting could leave a tool blocked on a full pipe.
+    fn new(tool: &'static str, mut child: Child) -> Self {
+        let stderr = child.stderr.take();
+        let stderr = thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut stderr) = stderr {
+                // Whatever was read is still worth reporting
+                let _ = stderr.read_to_end(&mut buf);
+            }
+            buf
+        });
+
+        Stage {
+            tool,
+            child,
+            stderr,
+        }
+    }
+}
+
+/// Readable end of a decompression chain
+enum ChainOutput {
+    File(File),
+    Pipe(ChildStdout),
+}
+
+impl ChainOutput {
+    fn into_stdio(self) -> Stdio {
+        match self {
+            ChainOutput::File(file) => Stdio::from(file),
+            ChainOutput::Pipe(stdout) => Stdio::from(stdout),
+        }
+    }
+}
+
+impl Read for ChainOutput {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ChainOutput::File(file) => file.read(buf),
+            ChainOutput::Pipe(stdout) => stdout.read(buf),
+        }
+    }
+}
+
+/// Split a path into its compression layers (outermost first) and the payload
+///
+/// Peeling stops at a TAR archive, which is carried over unchanged. A volume
+/// number of a split set is dropped first.
+fn compression_layers(path: &Path) -> (Vec<CompressionFormat>, PathBuf) {
+    let mut layers = Vec::new();
+    let mut payload = first_volume_base(path).unwrap_or_else(|| path.to_path_buf());
+
+    while let Some(format) = detect_format(&payload) {
+        if format == CompressionFormat::Tar {
+            break;
+        }
+        layers.push(format);
+        payload = payload.with_extension("");
+    }
+
+    (layers, payload)
+}
+
+/// Spawn a tool reading from `stdin` with its output piped
+fn spawn_stage(mut cmd: Command, tool: &'static str, stdin: Stdio) -> JcResult<Stage> {
+    cmd.stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("Executing: {:?}", cmd);
+
+    let child = cmd
+        .spawn()
+        .map_err(|e| JcError::Other(format!("Failed to spawn {}: {}", tool, e)))?;
+
+    Ok(Stage::new(tool, child))
+}
+
+/// Start decompressing `input` through every layer, outermost first
+///
+/// The volumes of a split set are concatenated before the first layer.
+fn spawn_decompress_chain(
+    input: &Path,
+    layers: &[CompressionFormat],
+) -> JcResult<(Vec<Stage>, ChainOutput)> {
+    let mut stages = Vec::new();
+    let mut output = if first_volume_base(input).is_some() {
+        let mut child = spawn_concat(&collect_volumes(input)?)?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| JcError::Other("Failed to capture cat output".to_string()))?;
+        stages.push(Stage::new("cat", child));
+        ChainOutput::Pipe(stdout)
+    } else {
+        ChainOutput::File(File::open(input)?)
+    };
+
+    for format in layers {
+        let compressor = create_compressor(*format);
+        let cmd = compressor.decompress_command().ok_or_else(|| {
+            JcError::Other(format!("{} cannot decompress streams", compressor.name()))
+        })?;
+
+        let mut stage = spawn_stage(cmd, compressor.name(), output.into_stdio())?;
+        output = ChainOutput::Pipe(stage.child.stdout.take().ok_or_else(|| {
+            JcError::Other(format!("Failed to capture {} output", compressor.name()))
+        })?);
+        stages.push(stage);
+    }
+
+    Ok((stages, output))
+}
+
+/// Wait for pipeline stages, reporting the first one that failed
+fn wait_stages(stages: Vec<Stage>, decompressing: bool) -> JcResult<()> {
+    let mut first_error = None;
+
+    for mut stage in stages {
+        let status = stage.child.wait()?;
+        let stderr = stage.stderr.join().unwrap_or_default();
+        if !status.success() && first_error.is_none() {
+            let tool = stage.tool.to_string();
+            let stderr = String::from_utf8_lossy(&stderr).to_string();
+            first_error = Some(if decompressing {
+                JcError::DecompressionFailed { tool, stderr }
+            } else {
+                JcError::CompressionFailed { tool, stderr }
+            });
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Compare two streams byte by byte
+fn streams_equal(a: &mut impl Read, b: &mut impl Read) -> std::io::Result<bool> {
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            // Both streams must end together
+            return Ok(b.read(&mut buf_b[..1])? == 0);
+        }
+        if b.read_exact(&mut buf_b[..n]).is_err() || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Check that `converted` decompresses to exactly the payload of `original`
+fn verify_conversion(
+    original: &Path,
+    layers: &[CompressionFormat],
+    converted: &Path,
+    format: CompressionFormat,
+) -> JcResult<()> {
+    let (original_stages, mut original_stream) = spawn_decompress_chain(original, layers)?;
+    let (converted_stages, mut converted_stream) = spawn_decompress_chain(converted, &[format])?;
+
+    let equal = streams_equal(&mut original_stream, &mut converted_stream)?;
+
+    // Stop the tools before checking their status, one side may not be drained
+    drop(original_stream);
+    drop(converted_stream);
+    let original_status = wait_stages(original_stages, true);
+    let converted_status = wait_stages(converted_stages, true);
+
+    if !equal {
+        return Err(JcError::VerificationFailed {
+            path: converted.to_path_buf(),
+            reason: "content differs from the original".to_string(),
+        });
+    }
+    original_status?;
+    converted_status.map_err(|e| JcError::VerificationFailed {
+        path: converted.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+/// Name of the converted output of `input`, before it is placed in the move-to directory
+pub fn convert_output(
+    input: &Path,
+    target: ConvertTarget,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    let (_, payload) = compression_layers(input);
+
+    match target {
+        ConvertTarget::Simple(format) => {
+            generate_output_filename_from(&payload, input, format.extension(), config)
+        }
+        ConvertTarget::Compound(compound) => {
+            if detect_format(&payload) != Some(CompressionFormat::Tar) {
+                return Err(JcError::InvalidExtension(
+                    input.to_path_buf(),
+                    "tar, tar.gz, tar.bz2 or tar.xz".to_string(),
+                ));
+            }
+            generate_output_filename_from(
+                &payload.with_extension(""),
+                input,
+                compound.extension(),
+                config,
+            )
+        }
+    }
+}
+
+/// Convert a single compressed file to another format
+///
+/// The decompressed payload is streamed straight into the target compressor,
+/// so an inner TAR archive is carried over byte-for-byte.
+pub fn convert_file(
+    input: &Path,
+    target: ConvertTarget,
+    verify: bool,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    let format = target.format();
+    let compressor = create_compressor(format);
+
+    if !compressor.validate_level(config.level) {
+        return Err(JcError::InvalidCompressionLevel {
+            algorithm: compressor.name().to_string(),
+            level: config.level,
+        });
+    }
+
+    if is_encrypted(input) {
+        return Err(JcError::Other(format!(
+            "{} is encrypted, decompress it first",
+            input.display()
+        )));
+    }
+
+    let (layers, _) = compression_layers(input);
+    let output_path = convert_output(input, target, config)?;
+    let output_path = resolve_output_path(&output_path, &config.move_to)?;
+
+    if output_path == input {
+        return Err(JcError::Other(format!(
+            "{} is already in {} format",
+            input.display(),
+            compressor.name()
+        )));
+    }
+
+    let (output_path, reservation) = resolve_conflict(&output_path, config.on_conflict)?;
+    info!(
+        "Converting {} to {} with {}",
+        input.display(),
+        output_path.display(),
+        compressor.name()
+    );
+
+    let atomic_output = AtomicOutput::new(&output_path);
+    let output_file = File::create(atomic_output.temp_path())?;
+    let mut writer = BufWriter::new(output_file);
+
+    let (decompress_stages, payload_stream) = spawn_decompress_chain(input, &layers)?;
+
+    let cmd = compressor
+        .compress_command(config.level)
+        .ok_or_else(|| JcError::Other(format!("{} cannot compress streams", compressor.name())))?;
+    let mut compress_stage = spawn_stage(cmd, compressor.name(), payload_stream.into_stdio())?;
+
+    if let Some(mut stdout) = compress_stage.child.stdout.take() {
+        std::io::copy(&mut stdout, &mut writer)?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+
+    // A truncated payload still compresses cleanly, so check the readers first
+    wait_stages(decompress_stages, true)?;
+    wait_stages(vec![compress_stage], false)?;
+
+    if verify {
+        verify_conversion(input, &layers, atomic_output.temp_path(), format)?;
+        debug!("Verified {}", output_path.display());
+    }
+
+    let final_path = atomic_output.commit(config.fsync)?;
+    reservation.keep();
+    info!("Converted file: {}", final_path.display());
+
+    // Only remove the original once the new file is known to be intact
+    if config.remove_input {
+        if !verify {
+            compressor.verify(&final_path, config)?;
+        }
+        for file in input_files(input)? {
+            remove_input(&file)?;
+        }
+        remove_sidecars(&first_volume_base(input).unwrap_or_else(|| input.to_path_buf()))?;
+    }
+
+    Ok(final_path)
+}
+
+/// Convert multiple files concurrently
+pub fn convert_files(
+    inputs: Vec<PathBuf>,
+    target: ConvertTarget,
+    verify: bool,
+    config: CompressionConfig,
+) -> Vec<JcResult<PathBuf>> {
+    info!("Converting {} files", inputs.len());
+
+    inputs
+        .par_iter()
+        .map(|input| match convert_file(input, target, verify, &config) {
+            Ok(output) => Ok(output),
+            Err(JcError::Skipped(path)) => Err(JcError::Skipped(path)),
+            Err(e) => {
+                error!("Failed to convert {}: {}", input.display(), e);
+                Err(e)
+            }
+        })
+        .collect()
+}
@@ -1,23 +1,31 @@
 use rayon::prelude::*;
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use crate::compressors::{
-    detect_format, Bzip2Compressor, GzipCompressor, TarCompressor, XzCompressor,
+    create_compressor, detect_format, Bzip2Compressor, GzipCompressor, TarCompressor, XzCompressor,
 };
 use crate::core::config::CompressionConfig;
 use crate::core::error::{JcError, JcResult};
-use crate::utils::{create_decompress_temp_dir, debug, error, info, prompt_overwrite};
+use crate::core::types::CompressionFormat;
+use crate::utils::checksum::{remove_sidecars, verify_checksum};
+use crate::utils::crypto::{decrypt_in_dir, is_encrypted};
+use crate::utils::manifest::verify_manifest;
+use crate::utils::split::{first_volume_base, input_files, join_volumes};
+use crate::utils::stream::run_with_input_into_writer;
+use crate::utils::{
+    create_decompress_temp_dir, debug, error, info, place_output, remove_input, resolve_conflict,
+    resolve_dir_conflict,
+};
 
 /// Helper function to decompress in a working directory based on format
 fn decompress_in_working_dir(
-    format: crate::core::types::CompressionFormat,
-    input: &PathBuf,
-    working_dir: &PathBuf,
+    format: CompressionFormat,
+    input: &Path,
+    working_dir: &Path,
     config: &CompressionConfig,
 ) -> JcResult<PathBuf> {
-    use crate::core::types::CompressionFormat;
-
     match format {
         CompressionFormat::Gzip => {
             let compressor = GzipCompressor::new();
@@ -38,15 +46,102 @@ fn decompress_in_working_dir(
     }
 }
 
-/// Decompress a single file, handling compound formats
-pub fn decompress_file(input: &PathBuf, config: &CompressionConfig) -> JcResult<PathBuf> {
-    // Create a temporary directory for decompression work
-    let temp_dir = create_decompress_temp_dir()?;
-    let temp_dir_path = temp_dir.path().to_path_buf();
+/// Called with each compressed layer of an archive before it is removed
+pub type LayerVisitor<'a> = &'a mut dyn FnMut(CompressionFormat, &Path) -> JcResult<()>;
 
-    debug!("Created temp directory: {}", temp_dir_path.display());
+/// Check whether a path still carries a compression or encryption extension
+fn has_layer(path: &Path) -> bool {
+    detect_format(path).is_some() || is_encrypted(path)
+}
+
+/// Get the path the layer under the split and encryption layers of `input`
+/// would have in `working_dir`
+fn outer_layer_path(input: &Path, working_dir: &Path) -> JcResult<PathBuf> {
+    let mut base = first_volume_base(input).unwrap_or_else(|| input.to_path_buf());
+    if is_encrypted(&base) {
+        base = base.with_extension("");
+    }
+    let name = base
+        .file_name()
+        .ok_or_else(|| JcError::Other("Invalid archive name".to_string()))?;
+    Ok(working_dir.join(name))
+}
+
+/// Remove the split and encryption layers of `input` on the way into the
+/// tool for the `format` layer below them
+///
+/// Volumes are read in turn and decrypted as they are fed to the tool, so
+/// neither a joined nor a decrypted copy is written. `layer` is the path
+/// that layer would have in `working_dir`. Returns what it decompressed to.
+fn stream_outer_layers(
+    input: &Path,
+    format: CompressionFormat,
+    layer: &Path,
+    working_dir: &Path,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    info!("Streaming {} into {}", input.display(), format.name());
+
+    let compressor = create_compressor(format);
+    let Some(cmd) = compressor.decompress_command() else {
+        return TarCompressor::new().extract_stream_in_dir(input, layer, working_dir, config);
+    };
+
+    let output_path = layer.with_extension("");
+    let mut writer = BufWriter::new(File::create(&output_path)?);
+    let output = run_with_input_into_writer(cmd, input, &config.keys, &mut writer)?;
+    writer.flush()?;
+
+    if !output.status.success() {
+        return Err(JcError::DecompressionFailed {
+            tool: format.name().to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+
+    Ok(output_path)
+}
+
+/// Peel the layers off `input` inside `working_dir`
+///
+/// Removes split volumes, encryption and compression until no known layer is
+/// left, or until a TAR archive is reached when `keep_tar` is set. `visit`,
+/// if given, is called with each compressed layer before it is removed;
+/// without it, split and encrypted inputs are streamed into the first
+/// decompressor instead of being joined and decrypted on disk. Returns the
+/// innermost file or directory, which is still inside `working_dir`.
+pub fn peel_layers(
+    input: &Path,
+    working_dir: &Path,
+    config: &CompressionConfig,
+    keep_tar: bool,
+    mut visit: Option<LayerVisitor<'_>>,
+) -> JcResult<PathBuf> {
+    let mut current_file = input.to_path_buf();
+
+    // Split volumes and encryption are the outermost layers
+    if first_volume_base(input).is_some() || is_encrypted(input) {
+        let layer = outer_layer_path(input, working_dir)?;
+        let format = detect_format(&layer).ok_or_else(|| {
+            JcError::InvalidExtension(layer.clone(), "supported compression format".to_string())
+        })?;
 
-    let mut current_file = input.clone();
+        if visit.is_none() && !(keep_tar && format == CompressionFormat::Tar) {
+            current_file = stream_outer_layers(input, format, &layer, working_dir, config)?;
+            if detect_format(&current_file).is_none() {
+                info!("No more compression layers detected");
+                return Ok(current_file);
+            }
+        } else {
+            // The layer has to be seen as a file, so it is written out
+            if first_volume_base(input).is_some() {
+                current_file = join_volumes(input, working_dir)?;
+            }
+            if is_encrypted(&current_file) {
+                current_file = decrypt_in_dir(&current_file, working_dir, &config.keys)?;
+            }
+        }
+    }
 
     // Iteratively decompress until no more compression detected
     loop {
@@ -56,6 +151,12 @@ pub fn decompress_file(input: &PathBuf, config: &CompressionConfig) -> JcResult<
                 "supported compression format".to_string(),
             )
         })?;
+        if keep_tar && format == CompressionFormat::Tar {
+            break;
+        }
+        if let Some(visit) = visit.as_mut() {
+            visit(format, &current_file)?;
+        }
 
         info!(
             "Decompression iteration: format={:?}, current_file={}",
@@ -64,7 +165,7 @@ pub fn decompress_file(input: &PathBuf, config: &CompressionConfig) -> JcResult<
         );
 
         // Decompress in temp directory
-        let output = decompress_in_working_dir(format, &current_file, &temp_dir_path, config)?;
+        let output = decompress_in_working_dir(format, &current_file, working_dir, config)?;
 
         info!("Decompressed to: {}", output.display());
 
@@ -77,98 +178,157 @@ pub fn decompress_file(input: &PathBuf, config: &CompressionConfig) -> JcResult<
         }
     }
 
-    // Determine final destination
-    let final_dest = if let Some(ref move_to) = config.move_to {
-        // When using -C with multiple extracted files, use move_to directly
-        // Otherwise, create a subdirectory based on the archive name
-        if current_file.is_dir() && current_file == temp_dir_path {
-            // Multiple files extracted - put them directly in move_to
-            move_to.clone()
-        } else {
-            // Single file or directory - create subdirectory
-            let mut dest = input.clone();
-            while detect_format(&dest).is_some() {
-                dest = dest.with_extension("");
-            }
+    Ok(current_file)
+}
+
+/// Peel every layer off `input` inside `working_dir`
+///
+/// Checks the archive's checksum if asked to, then checks collection
+/// manifests once every layer is removed. Returns the innermost file or
+/// directory, which is still inside `working_dir`.
+pub fn extract_in_dir(
+    input: &Path,
+    working_dir: &Path,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    // Refuse to extract anything from an archive that does not match its checksum
+    if config.verify_checksum {
+        verify_checksum(input)?;
+    }
+
+    let current_file = peel_layers(input, working_dir, config, false, None)?;
+
+    // Collections carry a manifest of their members; check them before placing
+    if current_file.is_dir() {
+        verify_manifest(&current_file)?;
+    }
+
+    Ok(current_file)
+}
+
+/// Where the extracted file or directory of `input` goes
+///
+/// `-o` names it; otherwise it is the archive name without its compression
+/// and encryption extensions, next to the archive or in the move-to directory.
+/// Archives of several loose files are extracted straight into the move-to
+/// directory instead.
+pub fn decompress_destination(input: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
+    if let Some(ref output) = config.output {
+        return Ok(output.clone());
+    }
+
+    // Remove all compression extensions
+    let mut dest = input.to_path_buf();
+    while has_layer(&dest) {
+        dest = dest.with_extension("");
+    }
+
+    match config.move_to {
+        Some(ref move_to) => {
             let filename = dest
                 .file_name()
                 .ok_or_else(|| JcError::Other("Invalid output filename".to_string()))?;
-            move_to.join(filename)
+            Ok(move_to.join(filename))
         }
-    } else {
-        // Determine output based on input filename
-        let mut dest = input.clone();
-        // Remove all compression extensions
-        while detect_format(&dest).is_some() {
-            dest = dest.with_extension("");
+        None => Ok(dest),
+    }
+}
+
+/// Decompress a single file, handling compound formats
+pub fn decompress_file(input: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
+    // Create a temporary directory for decompression work
+    let temp_dir = create_decompress_temp_dir()?;
+    let temp_dir_path = temp_dir.path().to_path_buf();
+
+    debug!("Created temp directory: {}", temp_dir_path.display());
+
+    let current_file = extract_in_dir(input, &temp_dir_path, config)?;
+
+    // Determine final destination
+    let final_dest = match config.move_to {
+        // When using -C with multiple extracted files, put them directly in move_to
+        Some(ref move_to)
+            if config.output.is_none()
+                && current_file.is_dir()
+                && current_file == temp_dir_path =>
+        {
+            move_to.clone()
         }
-        dest
+        _ => decompress_destination(input, config)?,
     };
 
     debug!("Final destination: {}", final_dest.display());
 
     // Move from temp directory to final destination
     // All decompressed files are in temp directory, so we always need to copy/move them
-    if current_file.is_dir() {
+    let mut skipped_entries = false;
+    let final_dest = if current_file.is_dir() {
         // This is a directory containing multiple extracted files
         // We need to copy the contents, not create a subdirectory
         if current_file == temp_dir_path {
             // This is the working directory itself (multiple loose files from TAR)
             // Copy contents to final destination
-            fs::create_dir_all(&final_dest).map_err(|e| JcError::Io(e))?;
-            for entry in fs::read_dir(&current_file).map_err(|e| JcError::Io(e))? {
-                let entry = entry.map_err(|e| JcError::Io(e))?;
+            fs::create_dir_all(&final_dest)?;
+            for entry in fs::read_dir(&current_file)? {
+                let entry = entry?;
                 let src_path = entry.path();
                 let dst_path = final_dest.join(entry.file_name());
 
-                // Check if individual file exists and prompt for overwrite
-                if dst_path.exists() && !config.force {
-                    if !prompt_overwrite(&dst_path)? {
-                        info!("Skipping {}", dst_path.display());
+                // Apply the conflict policy to each extracted entry
+                let resolved = if src_path.is_dir() {
+                    resolve_dir_conflict(&dst_path, config.on_conflict)
+                } else {
+                    resolve_conflict(&dst_path, config.on_conflict)
+                };
+                let (dst_path, reservation) = match resolved {
+                    Ok(resolved) => resolved,
+                    Err(JcError::Skipped(path)) => {
+                        info!("Skipping {}", path.display());
+                        skipped_entries = true;
                         continue;
                     }
-                }
+                    Err(e) => return Err(e),
+                };
 
-                use crate::utils::copy_recursive;
-                if src_path.is_dir() {
-                    copy_recursive(&src_path, &dst_path).map_err(|e| JcError::Io(e))?;
-                } else {
-                    fs::copy(&src_path, &dst_path).map_err(|e| JcError::Io(e))?;
-                }
+                place_output(&src_path, &dst_path, config.fsync)?;
+                reservation.keep();
             }
             info!(
                 "Decompressed {} files to: {}",
                 fs::read_dir(&final_dest).map(|d| d.count()).unwrap_or(0),
                 final_dest.display()
             );
+            final_dest
         } else {
             // This is a subdirectory that was extracted from TAR
-            // Check if destination exists
-            if final_dest.exists() && !config.force {
-                if !prompt_overwrite(&final_dest)? {
-                    return Err(JcError::Other(format!(
-                        "Decompression aborted: directory already exists: {}",
-                        final_dest.display()
-                    )));
-                }
-            }
-            use crate::utils::copy_recursive;
-            copy_recursive(&current_file, &final_dest).map_err(|e| JcError::Io(e))?;
+            let (final_dest, reservation) = resolve_dir_conflict(&final_dest, config.on_conflict)?;
+            place_output(&current_file, &final_dest, config.fsync)?;
+            reservation.keep();
             info!("Decompressed directory: {}", final_dest.display());
+            final_dest
         }
     } else {
         // Copy single file
-        // Check if destination exists
-        if final_dest.exists() && !config.force {
-            if !prompt_overwrite(&final_dest)? {
-                return Err(JcError::Other(format!(
-                    "Decompression aborted: file already exists: {}",
-                    final_dest.display()
-                )));
+        let (final_dest, reservation) = resolve_conflict(&final_dest, config.on_conflict)?;
+        place_output(&current_file, &final_dest, config.fsync)?;
+        reservation.keep();
+        info!("Decompressed file: {}", final_dest.display());
+        final_dest
+    };
+
+    // Remove the archive once everything in it has been extracted
+    if config.remove_input {
+        if skipped_entries {
+            info!(
+                "Keeping {} because some entries were skipped",
+                input.display()
+            );
+        } else {
+            for file in input_files(input)? {
+                remove_input(&file)?;
             }
+            remove_sidecars(&first_volume_base(input).unwrap_or_else(|| input.to_path_buf()))?;
         }
-        fs::copy(&current_file, &final_dest).map_err(|e| JcError::Io(e))?;
-        info!("Decompressed file: {}", final_dest.display());
     }
 
     // temp_dir will be automatically cleaned up when it goes out of scope
@@ -183,6 +343,7 @@ pub fn decompress_files(inputs: Vec<PathBuf>, config: CompressionConfig) -> Vec<
         .par_iter()
         .map(|input| match decompress_file(input, &config) {
             Ok(output) => Ok(output),
+            Err(JcError::Skipped(path)) => Err(JcError::Skipped(path)),
             Err(e) => {
                 error!("Failed to decompress {}: {}", input.display(), e);
                 Err(e)
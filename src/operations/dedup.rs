@@ -0,0 +1,621 @@
+use chrono::Local;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Stdio};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::compressors::{create_compressor, detect_format};
+use crate::core::config::{CollectionMode, CompressionConfig};
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{ChecksumAlgorithm, CompressionFormat};
+use crate::operations::compress::compress_file;
+use crate::utils::atomic::STAGING_DIR_PREFIX;
+use crate::utils::checksum::Hasher;
+use crate::utils::dedup::{
+    chunk_hash, for_each_chunk, ChunkLocation, DedupEntry, DedupSnapshot, PackIndex, Repository,
+};
+use crate::utils::manifest::host_name;
+use crate::utils::snapshot::{is_safe_relative, EntryKind};
+use crate::utils::{
+    create_temp_dir_in, debug, error, generate_output_filename_from, info, resolve_conflict,
+    AtomicOutput, CleanupGuard,
+};
+
+/// Number of hex digits in a pack id
+const PACK_ID_LEN: usize = 16;
+
+/// Describe `source` and everything below it, stored under `path`
+fn scan_input(
+    source: &Path,
+    path: String,
+    entries: &mut Vec<(PathBuf, DedupEntry)>,
+) -> JcResult<()> {
+    let metadata = fs::symlink_metadata(source)?;
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        EntryKind::Dir
+    } else if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        debug!("Skipping special file {}", source.display());
+        return Ok(());
+    };
+
+    let target = match kind {
+        EntryKind::Symlink => fs::read_link(source)?.to_string_lossy().to_string(),
+        _ => String::new(),
+    };
+    entries.push((
+        source.to_path_buf(),
+        DedupEntry {
+            path: path.clone(),
+            kind,
+            mode: format!("{:04o}", metadata.permissions().mode() & 0o7777),
+            mtime: metadata.mtime(),
+            size: if kind == EntryKind::File {
+                metadata.len()
+            } else {
+                0
+            },
+            sha256: String::new(),
+            target,
+            chunks: Vec::new(),
+        },
+    ));
+
+    if kind == EntryKind::Dir {
+        let mut children: Vec<PathBuf> = fs::read_dir(source)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        children.sort();
+        for child in children {
+            let name = child.file_name().unwrap_or_default().to_string_lossy();
+            let child_path = format!("{}/{}", path, name);
+            scan_input(&child, child_path, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pack of the chunks first seen during one store
+struct PackWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    hasher: Hasher,
+    chunks: Vec<ChunkLocation>,
+    offset: u64,
+}
+
+impl PackWriter {
+    fn new(path: PathBuf) -> JcResult<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            hasher: Hasher::new(ChecksumAlgorithm::Sha256),
+            chunks: Vec::new(),
+            offset: 0,
+        })
+    }
+
+    fn add(&mut self, hash: String, data: &[u8]) -> JcResult<()> {
+        self.writer.write_all(data)?;
+        self.hasher.update(data);
+        self.chunks.push(ChunkLocation {
+            hash,
+            offset: self.offset,
+            length: data.len() as u64,
+        });
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    /// Compress the pack into the repository and record its index
+    ///
+    /// Returns the number of new bytes, or `None` if no chunk was new.
+    fn finish(
+        self,
+        repo: &Repository,
+        format: CompressionFormat,
+        config: &CompressionConfig,
+    ) -> JcResult<Option<u64>> {
+        let Self {
+            path,
+            writer,
+            hasher,
+            chunks,
+            offset,
+        } = self;
+        writer.into_inner().map_err(|e| e.into_error())?;
+        if chunks.is_empty() {
+            return Ok(None);
+        }
+
+        let mut id = hasher.finalize();
+        id.truncate(PACK_ID_LEN);
+        let named = path.with_file_name(format!("{}.pack", id));
+        fs::rename(&path, &named)?;
+
+        let pack_config = CompressionConfig::new()
+            .with_level(config.level)
+            .with_fsync(config.fsync)
+            .with_force(true)
+            .with_move_to(repo.packs_dir());
+        let pack = compress_file(&named, format, &pack_config)?;
+        let pack = pack
+            .file_name()
+            .ok_or_else(|| JcError::Other("Invalid pack filename".to_string()))?
+            .to_string_lossy()
+            .to_string();
+
+        repo.write_index(&id, &PackIndex { pack, chunks }, config.fsync)?;
+        Ok(Some(offset))
+    }
+}
+
+/// Name of the snapshot stored for `name`, the way its archive would be named
+///
+/// Timestamps take the modification time of `first` where they use one.
+pub fn snapshot_name(name: &str, first: &Path, config: &CompressionConfig) -> JcResult<String> {
+    let file = generate_output_filename_from(Path::new(name), first, "json", config)?;
+    let file = file
+        .file_name()
+        .ok_or_else(|| JcError::Other(format!("Invalid snapshot name: {}", name)))?
+        .to_string_lossy()
+        .to_string();
+    Ok(file.trim_end_matches(".json").to_string())
+}
+
+/// Store inputs in a deduplicating repository as one snapshot
+///
+/// Files are cut into content-defined chunks and only chunks the repository
+/// does not hold yet are written, together in one pack compressed with
+/// `format`. The snapshot is named like an archive of `name` would be, so
+/// timestamps and templates apply; with `CollectionMode::WithParent` its
+/// entries are placed under `name/`.
+pub fn store_snapshot(
+    inputs: &[PathBuf],
+    repo: &Path,
+    name: &str,
+    mode: CollectionMode,
+    format: CompressionFormat,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    let first = inputs.first().ok_or(JcError::NoInputFiles)?;
+    let repo = Repository::open_or_init(repo, config.fsync)?;
+
+    // The snapshot is named like the archive of the same inputs
+    let named = repo.snapshot_path(&snapshot_name(name, first, config)?);
+    let (snapshot_path, reservation) = resolve_conflict(&named, config.on_conflict)?;
+    let snapshot_name = snapshot_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut entries = Vec::new();
+    let mut top_names = HashSet::new();
+    let mut duplicates = Vec::new();
+    for input in inputs {
+        let base = input
+            .file_name()
+            .ok_or_else(|| JcError::Other(format!("Invalid input: {}", input.display())))?
+            .to_string_lossy()
+            .to_string();
+        if !top_names.insert(base.clone()) {
+            duplicates.push(base.clone());
+        }
+        let path = match mode {
+            CollectionMode::WithParent => format!("{}/{}", name, base),
+            CollectionMode::Flat => base,
+        };
+        scan_input(input, path, &mut entries)?;
+    }
+    if !duplicates.is_empty() {
+        return Err(JcError::DuplicateBasenames(duplicates));
+    }
+    if mode == CollectionMode::WithParent {
+        entries.insert(
+            0,
+            (
+                PathBuf::new(),
+                DedupEntry {
+                    path: name.to_string(),
+                    kind: EntryKind::Dir,
+                    mode: "0755".to_string(),
+                    mtime: Local::now().timestamp(),
+                    size: 0,
+                    sha256: String::new(),
+                    target: String::new(),
+                    chunks: Vec::new(),
+                },
+            ),
+        );
+    }
+
+    info!(
+        "Storing {} entries as snapshot {} in {}",
+        entries.len(),
+        snapshot_name,
+        repo.root.display()
+    );
+
+    // New chunks are gathered in a pack next to the repository's own files
+    let staging = CleanupGuard::new(create_temp_dir_in(&repo.root, STAGING_DIR_PREFIX)?);
+    let mut pack = PackWriter::new(staging.path().join("pack"))?;
+    let known = repo.load_index()?;
+    let mut added = HashSet::new();
+    let mut total = 0u64;
+
+    for (source, entry) in entries.iter_mut() {
+        if entry.kind != EntryKind::File {
+            continue;
+        }
+
+        let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+        for_each_chunk(File::open(&*source)?, &repo.config, |data| {
+            let hash = chunk_hash(data);
+            hasher.update(data);
+            total += data.len() as u64;
+            if !known.contains_key(&hash) && added.insert(hash.clone()) {
+                pack.add(hash.clone(), data)?;
+            }
+            entry.chunks.push(hash);
+            Ok(())
+        })?;
+        entry.sha256 = hasher.finalize();
+    }
+
+    let new_bytes = pack.finish(&repo, format, config)?.unwrap_or(0);
+
+    // The snapshot goes last, once every chunk it refers to is stored
+    let snapshot = DedupSnapshot {
+        jcz_version: env!("CARGO_PKG_VERSION").to_string(),
+        name: snapshot_name.clone(),
+        created: Local::now().to_rfc3339(),
+        host: host_name(),
+        inputs: inputs.iter().map(|p| p.display().to_string()).collect(),
+        entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+    };
+    let output = repo.write_snapshot(&snapshot_path, &snapshot, config.fsync)?;
+    reservation.keep();
+
+    info!(
+        "Stored snapshot {}: {} bytes, {} new bytes in {} new chunks",
+        snapshot_name,
+        total,
+        new_bytes,
+        added.len()
+    );
+    Ok(output)
+}
+
+/// Most packs a [`ChunkReader`] keeps decompressing at once
+const MAX_OPEN_PACKS: usize = 8;
+
+/// A pack being decompressed, positioned at `offset` of its content
+struct PackStream {
+    pack: String,
+    child: Child,
+    stdout: ChildStdout,
+    offset: u64,
+}
+
+impl PackStream {
+    /// Start decompressing a pack from the beginning
+    fn open(repo: &Repository, pack: &str) -> JcResult<Self> {
+        let path = repo.packs_dir().join(pack);
+        let compressor = detect_format(&path)
+            .map(create_compressor)
+            .ok_or_else(|| JcError::Other(format!("Unknown pack format: {}", path.display())))?;
+        let mut cmd = compressor.decompress_command().ok_or_else(|| {
+            JcError::Other(format!("{} cannot decompress streams", compressor.name()))
+        })?;
+        cmd.stdin(Stdio::from(File::open(&path)?))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        debug!("Executing: {:?} < {}", cmd, path.display());
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| JcError::Other(format!("Failed to spawn {}: {}", compressor.name(), e)))?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            JcError::Other(format!("Failed to capture {} output", compressor.name()))
+        })?;
+
+        Ok(Self {
+            pack: pack.to_string(),
+            child,
+            stdout,
+            offset: 0,
+        })
+    }
+
+    /// Skip to `location` and read the chunk there
+    fn read(&mut self, location: &ChunkLocation) -> io::Result<Vec<u8>> {
+        let skip = location.offset - self.offset;
+        let skipped = io::copy(&mut (&mut self.stdout).take(skip), &mut io::sink())?;
+        if skipped != skip {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut data = vec![0; location.length as usize];
+        self.stdout.read_exact(&mut data)?;
+        self.offset = location.offset + location.length;
+        Ok(data)
+    }
+}
+
+impl Drop for PackStream {
+    fn drop(&mut self) {
+        // The rest of the pack is not needed
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reads chunks back out of a repository's packs
+///
+/// Packs are decompressed as streams, never onto disk or into memory as a
+/// whole. Reading a chunk skips forward to its offset; a chunk behind the
+/// current position starts its pack over. Chunks are mostly read in the order
+/// they were stored, so each pack is usually decompressed once.
+struct ChunkReader<'a> {
+    repo: &'a Repository,
+    index: HashMap<String, (String, ChunkLocation)>,
+
+    /// Open packs, least recently used first
+    streams: Vec<PackStream>,
+}
+
+impl<'a> ChunkReader<'a> {
+    fn new(repo: &'a Repository) -> JcResult<Self> {
+        Ok(Self {
+            repo,
+            index: repo.load_index()?,
+            streams: Vec::new(),
+        })
+    }
+
+    /// Read a chunk, checking it against its hash
+    fn read(&mut self, hash: &str) -> JcResult<Vec<u8>> {
+        let (pack, location) = self.index.get(hash).ok_or_else(|| {
+            JcError::Other(format!("Chunk {} is missing from the repository", hash))
+        })?;
+
+        let open = self.streams.iter().position(|s| s.pack == *pack);
+        let mut stream = match open.map(|i| self.streams.remove(i)) {
+            Some(stream) if stream.offset <= location.offset => stream,
+            _ => PackStream::open(self.repo, pack)?,
+        };
+
+        let path = self.repo.packs_dir().join(pack);
+        let data = stream.read(location).map_err(|e| {
+            JcError::Other(format!(
+                "Failed to read chunk {} from {}: {}",
+                hash,
+                path.display(),
+                e
+            ))
+        })?;
+
+        self.streams.push(stream);
+        if self.streams.len() > MAX_OPEN_PACKS {
+            self.streams.remove(0);
+        }
+
+        let actual = chunk_hash(&data);
+        if actual != hash {
+            return Err(JcError::ChecksumMismatch {
+                path,
+                expected: hash.to_string(),
+                actual,
+            });
+        }
+
+        Ok(data)
+    }
+}
+
+/// Write the entries of a snapshot below `target`
+///
+/// Existing entries are never replaced. Each file is checked against its
+/// recorded hash before it is moved into place.
+pub fn extract_snapshot(
+    repo: &Path,
+    name: &str,
+    target: &Path,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    let repo = Repository::open(repo)?;
+    let snapshot = repo.read_snapshot(name)?;
+
+    if let Some(entry) = snapshot.entries.iter().find(|e| !is_safe_relative(&e.path)) {
+        return Err(JcError::Other(format!(
+            "Refusing to extract {} outside the target directory",
+            entry.path
+        )));
+    }
+    if let Some(entry) = snapshot
+        .entries
+        .iter()
+        .find(|e| fs::symlink_metadata(target.join(&e.path)).is_ok() && !e.path.contains('/'))
+    {
+        return Err(JcError::OutputExists(target.join(&entry.path)));
+    }
+
+    fs::create_dir_all(target)?;
+    let mut reader = ChunkReader::new(&repo)?;
+    for entry in &snapshot.entries {
+        let dest = target.join(&entry.path);
+        match entry.kind {
+            EntryKind::Dir => fs::create_dir_all(&dest)?,
+            EntryKind::Symlink => symlink(&entry.target, &dest)?,
+            EntryKind::File => {
+                let output = AtomicOutput::new(&dest);
+                let mut file = File::create(output.temp_path())?;
+                let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+                for hash in &entry.chunks {
+                    let data = reader.read(hash)?;
+                    hasher.update(&data);
+                    file.write_all(&data)?;
+                }
+
+                let actual = hasher.finalize();
+                if actual != entry.sha256 {
+                    return Err(JcError::ChecksumMismatch {
+                        path: dest,
+                        expected: entry.sha256.clone(),
+                        actual,
+                    });
+                }
+
+                set_attributes(&file, entry)?;
+                drop(file);
+                output.commit(config.fsync)?;
+            }
+        }
+    }
+
+    // Directory permissions last, so read-only directories can be filled first
+    for entry in snapshot.entries.iter().rev() {
+        if entry.kind == EntryKind::Dir {
+            let mode = u32::from_str_radix(&entry.mode, 8).unwrap_or(0o755);
+            fs::set_permissions(target.join(&entry.path), fs::Permissions::from_mode(mode))?;
+        }
+    }
+
+    info!(
+        "Extracted snapshot {} ({} entries) to {}",
+        snapshot.name,
+        snapshot.entries.len(),
+        target.display()
+    );
+    Ok(target.to_path_buf())
+}
+
+/// Give an extracted file its recorded permissions and modification time
+fn set_attributes(file: &File, entry: &DedupEntry) -> JcResult<()> {
+    let mode = u32::from_str_radix(&entry.mode, 8).unwrap_or(0o644);
+    file.set_permissions(fs::Permissions::from_mode(mode))?;
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(entry.mtime.max(0) as u64))?;
+    Ok(())
+}
+
+/// Print the snapshots of a repository, or the entries of one snapshot
+pub fn list_repository(repo: &Path, name: Option<&str>) -> JcResult<()> {
+    let repo = Repository::open(repo)?;
+
+    if let Some(name) = name {
+        let snapshot = repo.read_snapshot(name)?;
+        for entry in &snapshot.entries {
+            match entry.kind {
+                EntryKind::Dir => println!("{} {:>12} {}/", entry.mode, "", entry.path),
+                EntryKind::File => println!("{} {:>12} {}", entry.mode, entry.size, entry.path),
+                EntryKind::Symlink => {
+                    println!(
+                        "{} {:>12} {} -> {}",
+                        entry.mode, "", entry.path, entry.target
+                    )
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let names = repo.snapshot_names()?;
+    let mut total = 0;
+    for name in &names {
+        let snapshot = repo.read_snapshot(name)?;
+        let files = snapshot
+            .entries
+            .iter()
+            .filter(|e| e.kind == EntryKind::File)
+            .count();
+        total += snapshot.size();
+        println!(
+            "{}  {}  {} files  {} bytes",
+            name,
+            snapshot.created,
+            files,
+            snapshot.size()
+        );
+    }
+
+    let mut stored = 0;
+    for entry in fs::read_dir(repo.packs_dir())? {
+        stored += entry?.metadata()?.len();
+    }
+    info!(
+        "{} snapshot(s) with {} bytes of files, stored in {} bytes of packs",
+        names.len(),
+        total,
+        stored
+    );
+    Ok(())
+}
+
+/// Check that snapshots can be restored: every chunk is present and intact
+/// and every file rebuilds to its recorded hash
+///
+/// All snapshots are checked when `names` is empty.
+pub fn verify_repository(repo: &Path, names: &[String]) -> JcResult<()> {
+    let repo = Repository::open(repo)?;
+    let names = if names.is_empty() {
+        repo.snapshot_names()?
+    } else {
+        names.to_vec()
+    };
+
+    let mut reader = ChunkReader::new(&repo)?;
+    let mut problems = 0;
+    for name in &names {
+        let snapshot = repo.read_snapshot(name)?;
+
+        'entries: for entry in snapshot
+            .entries
+            .iter()
+            .filter(|e| e.kind == EntryKind::File)
+        {
+            let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+            for hash in &entry.chunks {
+                match reader.read(hash) {
+                    Ok(data) => hasher.update(&data),
+                    Err(e) => {
+                        error!("{}: {}: {}", name, entry.path, e);
+                        problems += 1;
+                        continue 'entries;
+                    }
+                }
+            }
+
+            if hasher.finalize() != entry.sha256 {
+                error!("{}: {}: content does not match its hash", name, entry.path);
+                problems += 1;
+            }
+        }
+        debug!("Checked snapshot {}", name);
+    }
+
+    if problems > 0 {
+        return Err(JcError::Other(format!(
+            "{} problem(s) found in {}",
+            problems,
+            repo.root.display()
+        )));
+    }
+
+    info!(
+        "Verified {} snapshot(s) in {}",
+        names.len(),
+        repo.root.display()
+    );
+    Ok(())
+}
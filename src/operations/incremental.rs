@@ -0,0 +1,314 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{CompoundFormat, CompressionFormat};
+use crate::operations::compound::compress_compound;
+use crate::operations::compress::compress_file;
+use crate::operations::decompress::extract_in_dir;
+use crate::utils::atomic::{DECOMPRESS_DIR_PREFIX, STAGING_DIR_PREFIX};
+use crate::utils::snapshot::{
+    diff_snapshots, is_safe_relative, read_marker, read_state, scan_tree, write_marker,
+    write_state, BackupKind, BackupMarker, EntryKind, SNAPSHOT_MARKER,
+};
+use crate::utils::{create_temp_dir_in, debug, info, CleanupGuard};
+
+/// Place one entry of the backed-up directory in the staging tree
+///
+/// Files are hard-linked where possible so that large trees are not copied;
+/// the staging tree is only read from and then unlinked.
+fn stage_entry(source: &Path, dest: &Path, kind: EntryKind) -> JcResult<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match kind {
+        EntryKind::Dir => {
+            fs::create_dir_all(dest)?;
+            fs::set_permissions(dest, fs::metadata(source)?.permissions())?;
+        }
+        EntryKind::Symlink => symlink(fs::read_link(source)?, dest)?,
+        EntryKind::File => {
+            if fs::hard_link(source, dest).is_err() {
+                fs::copy(source, dest)?;
+                let modified = fs::metadata(source)?.modified()?;
+                fs::File::options()
+                    .write(true)
+                    .open(dest)?
+                    .set_modified(modified)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Back up a directory as a full archive, or as the changes since a snapshot
+///
+/// Without a state file this writes a full archive and records its snapshot.
+/// Otherwise only entries added or changed since the recorded snapshot are
+/// archived, together with a marker listing deleted paths. Incremental
+/// backups then record the new snapshot; differential ones leave the state
+/// of the full backup in place.
+pub fn backup_incremental(
+    input: &Path,
+    command: &str,
+    state_path: &Path,
+    differential: bool,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    if !input.is_dir() {
+        return Err(JcError::NotADirectory(input.to_path_buf()));
+    }
+
+    let previous = read_state(state_path)?;
+    let current = scan_tree(input, previous.as_ref())?;
+    if let Some(ref previous) = previous {
+        if previous.root != current.root {
+            return Err(JcError::Other(format!(
+                "{} records backups of {}, not {}",
+                state_path.display(),
+                previous.root,
+                current.root
+            )));
+        }
+    }
+
+    let (kind, changed, deleted): (_, Vec<String>, _) = match previous {
+        None => (
+            BackupKind::Full,
+            current.entries.keys().cloned().collect(),
+            Vec::new(),
+        ),
+        Some(ref previous) => {
+            let (changed, deleted) = diff_snapshots(previous, &current);
+            let kind = if differential {
+                BackupKind::Differential
+            } else {
+                BackupKind::Incremental
+            };
+            (kind, changed, deleted)
+        }
+    };
+
+    info!(
+        "{} backup of {}: {} changed and {} deleted entries",
+        kind.name(),
+        input.display(),
+        changed.len(),
+        deleted.len()
+    );
+
+    // Stage the archive contents next to the input, under the input's name
+    let input_dir = input
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let staging = CleanupGuard::new(create_temp_dir_in(input_dir, STAGING_DIR_PREFIX)?);
+    let name = input
+        .file_name()
+        .ok_or_else(|| JcError::Other("Invalid input directory name".to_string()))?;
+    let staged = staging.path().join(name);
+    fs::create_dir(&staged)?;
+    fs::set_permissions(&staged, fs::metadata(input)?.permissions())?;
+
+    // Paths are sorted, so directories are staged before their contents
+    for path in &changed {
+        stage_entry(
+            &input.join(path),
+            &staged.join(path),
+            current.entries[path].kind,
+        )?;
+    }
+    write_marker(
+        &staged,
+        &BackupMarker {
+            jcz_version: env!("CARGO_PKG_VERSION").to_string(),
+            kind,
+            id: current.id.clone(),
+            base: previous.as_ref().map(|p| p.id.clone()),
+            created: current.created.clone(),
+            deleted,
+        },
+    )?;
+    debug!("Staged {} for archiving", staged.display());
+
+    // The archive goes where the input's own archive would
+    let archive_config = config.clone().with_move_to(
+        config
+            .output_dir()
+            .unwrap_or_else(|| input_dir.to_path_buf()),
+    );
+    let output = match CompoundFormat::from_str(command) {
+        Some(compound) => compress_compound(&staged, compound, &archive_config)?,
+        None => compress_file(&staged, CompressionFormat::Tar, &archive_config)?,
+    };
+
+    // Record the snapshot only once its archive is in place
+    if kind != BackupKind::Differential {
+        write_state(state_path, &current, config.fsync)?;
+    }
+
+    info!("Created {} backup: {}", kind.name(), output.display());
+    Ok(output)
+}
+
+/// One archive of a restore chain, extracted and checked
+struct Layer {
+    archive: PathBuf,
+    marker: BackupMarker,
+
+    /// Extracted directory the marker is in
+    root: PathBuf,
+
+    /// Removes the extracted files once the layer is applied
+    _working: CleanupGuard,
+}
+
+/// Find the directory holding the backup marker in extracted archive contents
+fn find_backup_root(extracted: &Path) -> Option<PathBuf> {
+    if extracted.join(SNAPSHOT_MARKER).is_file() {
+        return Some(extracted.to_path_buf());
+    }
+
+    fs::read_dir(extracted)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| p.join(SNAPSHOT_MARKER).is_file())
+}
+
+/// Remove a file, link or directory, ignoring paths that are already gone
+fn remove_entry(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Move the extracted entries of `source` into `dest`, replacing what is there
+fn merge_into(source: &Path, dest: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+
+        let both_dirs =
+            entry.file_type()?.is_dir() && fs::symlink_metadata(&target).is_ok_and(|m| m.is_dir());
+        if both_dirs {
+            merge_into(&entry.path(), &target)?;
+        } else {
+            remove_entry(&target)?;
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore a directory from a full backup and the archives that follow it
+///
+/// `archives` are applied in order: the first must be a full backup and each
+/// later one must have been made against the snapshot of the one before it.
+/// Every archive is extracted next to `target`, its deletions are applied and
+/// its entries moved into place.
+pub fn restore_chain(
+    archives: &[PathBuf],
+    target: &Path,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    if target.exists() && (!target.is_dir() || fs::read_dir(target)?.next().is_some()) {
+        return Err(JcError::Other(format!(
+            "Restore target must be a new or empty directory: {}",
+            target.display()
+        )));
+    }
+    let parent = target
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    // Check the whole chain before changing anything in the target
+    let mut layers = Vec::new();
+    for (i, archive) in archives.iter().enumerate() {
+        let working = CleanupGuard::new(create_temp_dir_in(parent, DECOMPRESS_DIR_PREFIX)?);
+        let extracted = extract_in_dir(archive, working.path(), config)?;
+        let root = find_backup_root(&extracted).ok_or_else(|| {
+            JcError::Other(format!(
+                "{} is not an incremental or full backup archive",
+                archive.display()
+            ))
+        })?;
+        let marker = read_marker(&root)?.ok_or_else(|| {
+            JcError::Other(format!("Missing backup marker in {}", archive.display()))
+        })?;
+
+        if let Some(path) = marker.deleted.iter().find(|p| !is_safe_relative(p)) {
+            return Err(JcError::Other(format!(
+                "Refusing to delete {} outside the restored directory",
+                path
+            )));
+        }
+
+        match (i, marker.kind) {
+            (0, BackupKind::Full) => {}
+            (0, kind) => {
+                return Err(JcError::Other(format!(
+                    "{} is an {} backup; a restore starts from a full backup",
+                    archive.display(),
+                    kind.name()
+                )))
+            }
+            (_, BackupKind::Full) => {
+                return Err(JcError::Other(format!(
+                    "{} is a full backup; only the first archive can be",
+                    archive.display()
+                )))
+            }
+            (_, _) => {
+                let previous: &Layer = &layers[i - 1];
+                if marker.base.as_ref() != Some(&previous.marker.id) {
+                    return Err(JcError::Other(format!(
+                        "{} was not made against the snapshot of {}",
+                        archive.display(),
+                        previous.archive.display()
+                    )));
+                }
+            }
+        }
+
+        layers.push(Layer {
+            archive: archive.clone(),
+            marker,
+            root,
+            _working: working,
+        });
+    }
+
+    fs::create_dir_all(target)?;
+    for layer in layers {
+        fs::remove_file(layer.root.join(SNAPSHOT_MARKER))?;
+        for path in &layer.marker.deleted {
+            remove_entry(&target.join(path))?;
+        }
+        merge_into(&layer.root, target)?;
+
+        info!(
+            "Applied {} backup {} ({} deleted entries)",
+            layer.marker.kind.name(),
+            layer.archive.display(),
+            layer.marker.deleted.len()
+        );
+    }
+
+    info!("Restored {}", target.display());
+    Ok(target.to_path_buf())
+}
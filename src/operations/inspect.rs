@@ -0,0 +1,306 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compressors::{detect_format, TarCompressor};
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::CompressionFormat;
+use crate::operations::decompress::{peel_layers, LayerVisitor};
+use crate::utils::checksum::{has_checksum, verify_checksum};
+use crate::utils::create_decompress_temp_dir;
+use crate::utils::crypto::is_encrypted;
+use crate::utils::header::{bzip2_block_size, gzip_header, xz_header};
+use crate::utils::split::{first_volume_base, input_files};
+
+/// One layer of an archive, with what its headers record
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LayerInfo {
+    /// `split`, `age`, `gzip`, `bzip2`, `xz` or `tar`
+    pub format: &'static str,
+
+    /// Size of the layer in bytes
+    pub size: u64,
+
+    /// Compression level or preset, where the headers tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+
+    /// Original file name from a gzip header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Original modification time from a gzip header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
+
+    /// Operating system from a gzip header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<&'static str>,
+
+    /// Integrity check of xz blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+
+    /// Number of xz blocks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+
+    /// LZMA2 dictionary size of xz files, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<u32>,
+
+    /// Block size of bzip2 files, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<u32>,
+
+    /// Number of volumes of a split archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<usize>,
+
+    /// Number of members of a TAR archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+}
+
+impl LayerInfo {
+    /// Describe a compressed layer or TAR archive from its file
+    fn read(format: CompressionFormat, path: &Path) -> JcResult<Self> {
+        let mut layer = Self {
+            format: format.name(),
+            size: fs::metadata(path)?.len(),
+            ..Self::default()
+        };
+
+        match format {
+            CompressionFormat::Gzip => {
+                let header = gzip_header(path)?;
+                layer.level = Some(header.level.to_string());
+                layer.name = header.name;
+                layer.mtime = header.mtime;
+                layer.os = Some(header.os);
+            }
+            CompressionFormat::Bzip2 => {
+                let block_size = bzip2_block_size(path)?;
+                layer.level = Some((block_size / 100_000).to_string());
+                layer.block_size = Some(block_size);
+            }
+            CompressionFormat::Xz => {
+                let header = xz_header(path)?;
+                layer.level = header.preset.map(str::to_string);
+                layer.check = Some(header.check);
+                layer.blocks = Some(header.blocks);
+                layer.dictionary = header.dictionary;
+            }
+            CompressionFormat::Tar => {
+                layer.entries = Some(TarCompressor::new().list_members(path)?.len());
+            }
+        }
+
+        Ok(layer)
+    }
+
+    /// Describe the layer on one line: its size, then whatever is known
+    fn render(&self) -> String {
+        let mut details = vec![format!("{} bytes", self.size)];
+        if let Some(volumes) = self.volumes {
+            details.push(format!("{} volumes", volumes));
+        }
+        if let Some(ref level) = self.level {
+            details.push(format!("level {}", level));
+        }
+        if let Some(ref name) = self.name {
+            details.push(format!("name {}", name));
+        }
+        if let Some(ref mtime) = self.mtime {
+            details.push(format!("mtime {}", mtime));
+        }
+        if let Some(os) = self.os {
+            details.push(format!("os {}", os));
+        }
+        if let Some(ref check) = self.check {
+            details.push(format!("check {}", check));
+        }
+        if let Some(blocks) = self.blocks {
+            details.push(format!("blocks {}", blocks));
+        }
+        if let Some(dictionary) = self.dictionary {
+            details.push(format!("dictionary {} KiB", dictionary / 1024));
+        }
+        if let Some(block_size) = self.block_size {
+            details.push(format!("block size {} kB", block_size / 1000));
+        }
+        if let Some(entries) = self.entries {
+            details.push(format!("{} entries", entries));
+        }
+
+        format!(
+            "  {:<13} {}",
+            format!("{}:", self.format),
+            details.join(", ")
+        )
+    }
+}
+
+/// What an archive is made of, outermost layer first
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveInfo {
+    pub path: PathBuf,
+
+    /// Layers from the outside in
+    pub layers: Vec<LayerInfo>,
+
+    /// Size of the archive, every volume included
+    pub compressed_size: u64,
+
+    /// Size once the compression layers are removed, before unpacking TAR archives
+    pub uncompressed_size: u64,
+
+    /// Compressed size as a percentage of the uncompressed size
+    pub ratio: f64,
+
+    /// Members of the innermost TAR archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+}
+
+impl ArchiveInfo {
+    /// Describe the archive as indented text lines
+    pub fn render(&self) -> String {
+        let formats: Vec<&str> = self.layers.iter().map(|l| l.format).collect();
+        let mut lines = vec![
+            self.path.display().to_string(),
+            format!("  layers:       {}", formats.join(" > ")),
+            format!("  compressed:   {} bytes", self.compressed_size),
+            format!(
+                "  uncompressed: {} bytes (ratio {:.1}%)",
+                self.uncompressed_size, self.ratio
+            ),
+        ];
+        if let Some(entries) = self.entries {
+            lines.push(format!("  entries:      {}", entries));
+        }
+        lines.extend(self.layers.iter().map(LayerInfo::render));
+        lines.join("\n")
+    }
+}
+
+/// Remove every compression and encryption layer of `input` inside `working_dir`,
+/// keeping a TAR archive packed
+///
+/// Returns the innermost file and whether it is a TAR archive.
+fn open_archive(
+    input: &Path,
+    working_dir: &Path,
+    config: &CompressionConfig,
+    visit: Option<LayerVisitor<'_>>,
+) -> JcResult<(PathBuf, bool)> {
+    if !input.is_file() {
+        return Err(JcError::FileNotFound(input.to_path_buf()));
+    }
+
+    let inner = peel_layers(input, working_dir, config, true, visit)?;
+    let is_tar = detect_format(&inner) == Some(CompressionFormat::Tar);
+    Ok((inner, is_tar))
+}
+
+/// List the members of an archive, or the single file it compresses
+pub fn list_archive(input: &Path, config: &CompressionConfig) -> JcResult<Vec<String>> {
+    let temp_dir = create_decompress_temp_dir()?;
+    let (inner, is_tar) = open_archive(input, temp_dir.path(), config, None)?;
+
+    if is_tar {
+        TarCompressor::new().list_members(&inner)
+    } else {
+        let name = inner
+            .file_name()
+            .ok_or_else(|| JcError::Other("Invalid archive name".to_string()))?;
+        Ok(vec![name.to_string_lossy().to_string()])
+    }
+}
+
+/// Check that every layer of an archive decompresses and that TAR archives read through
+///
+/// Archives with a recorded checksum are also checked against it. Nothing
+/// is written outside a temporary directory.
+pub fn test_archive(input: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
+    if has_checksum(input)? {
+        verify_checksum(input)?;
+    }
+
+    let temp_dir = create_decompress_temp_dir()?;
+    let (inner, is_tar) = open_archive(input, temp_dir.path(), config, None)?;
+    if is_tar {
+        TarCompressor::new().list_members(&inner)?;
+    }
+
+    Ok(input.to_path_buf())
+}
+
+/// Test several archives concurrently
+pub fn test_archives(inputs: Vec<PathBuf>, config: CompressionConfig) -> Vec<JcResult<PathBuf>> {
+    inputs
+        .par_iter()
+        .map(|input| test_archive(input, &config))
+        .collect()
+}
+
+/// Work out the layers of an archive and what their headers record
+///
+/// Layers are inspected as `decompress_file` would peel them, in a
+/// temporary directory; TAR archives are read but not unpacked.
+pub fn archive_info(input: &Path, config: &CompressionConfig) -> JcResult<ArchiveInfo> {
+    let volumes = input_files(input)?;
+    let mut compressed_size = 0;
+    for file in &volumes {
+        compressed_size += fs::metadata(file)?.len();
+    }
+
+    let mut layers = Vec::new();
+    if first_volume_base(input).is_some() {
+        layers.push(LayerInfo {
+            format: "split",
+            size: compressed_size,
+            volumes: Some(volumes.len()),
+            ..LayerInfo::default()
+        });
+    }
+    if is_encrypted(first_volume_base(input).as_deref().unwrap_or(input)) {
+        layers.push(LayerInfo {
+            format: "age",
+            size: compressed_size,
+            ..LayerInfo::default()
+        });
+    }
+
+    let temp_dir = create_decompress_temp_dir()?;
+    let mut visit = |format, path: &Path| {
+        layers.push(LayerInfo::read(format, path)?);
+        Ok(())
+    };
+    let (inner, is_tar) = open_archive(input, temp_dir.path(), config, Some(&mut visit))?;
+
+    let mut entries = None;
+    if is_tar {
+        let tar = LayerInfo::read(CompressionFormat::Tar, &inner)?;
+        entries = tar.entries;
+        layers.push(tar);
+    }
+
+    let uncompressed_size = fs::metadata(&inner)?.len();
+    let ratio = if uncompressed_size == 0 {
+        100.0
+    } else {
+        compressed_size as f64 * 100.0 / uncompressed_size as f64
+    };
+
+    Ok(ArchiveInfo {
+        path: input.to_path_buf(),
+        layers,
+        compressed_size,
+        uncompressed_size,
+        ratio,
+        entries,
+    })
+}
@@ -1,8 +1,25 @@
+pub mod auto;
+pub mod bench;
+pub mod cleanup;
 pub mod collection;
 pub mod compound;
 pub mod compress;
+pub mod convert;
 pub mod decompress;
+pub mod dedup;
+pub mod incremental;
+pub mod inspect;
+pub mod modify;
+pub mod plan;
+pub mod prune;
+pub mod recursive;
 
+#[allow(unused_imports)]
+pub use auto::{choose_format, choose_formats, compress_auto, AutoChoice, AutoGoal};
+#[allow(unused_imports)]
+pub use bench::{bench_files, render_table, BenchResult};
+#[allow(unused_imports)]
+pub use cleanup::cleanup_stale;
 #[allow(unused_imports)]
 pub use collection::collect_and_compress;
 #[allow(unused_imports)]
@@ -10,4 +27,23 @@ pub use compound::{compress_compound, compress_compound_batch};
 #[allow(unused_imports)]
 pub use compress::{compress_file, compress_files};
 #[allow(unused_imports)]
-pub use decompress::{decompress_file, decompress_files};
+pub use convert::{convert_file, convert_files, ConvertTarget};
+#[allow(unused_imports)]
+pub use decompress::{decompress_file, decompress_files, extract_in_dir};
+#[allow(unused_imports)]
+pub use dedup::{extract_snapshot, list_repository, store_snapshot, verify_repository};
+#[allow(unused_imports)]
+pub use incremental::{backup_incremental, restore_chain};
+#[allow(unused_imports)]
+pub use inspect::{archive_info, list_archive, test_archives, ArchiveInfo};
+#[allow(unused_imports)]
+pub use modify::{modify_archive, TarEdit};
+#[allow(unused_imports)]
+pub use plan::{
+    plan_auto, plan_collection, plan_compress, plan_convert, plan_decompress, plan_dedup,
+    plan_edit, plan_incremental, plan_recursive, plan_restore, Plan,
+};
+#[allow(unused_imports)]
+pub use prune::prune_outputs;
+#[allow(unused_imports)]
+pub use recursive::{compress_recursive, decompress_recursive, recursive_files};
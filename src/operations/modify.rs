@@ -0,0 +1,175 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::compressors::{create_compressor, detect_format, TarCompressor};
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::CompressionFormat;
+use crate::utils::atomic::STAGING_DIR_PREFIX;
+use crate::utils::crypto::is_encrypted;
+use crate::utils::split::first_volume_base;
+use crate::utils::{create_temp_dir_in, debug, info, AtomicOutput, CleanupGuard};
+
+/// Change to apply to an existing TAR archive
+#[derive(Debug, Clone)]
+pub enum TarEdit {
+    /// Append files or directories
+    Append(Vec<PathBuf>),
+
+    /// Add missing files and replace members that are newer on disk
+    Update(Vec<PathBuf>),
+
+    /// Delete the named members
+    Delete(Vec<String>),
+}
+
+impl TarEdit {
+    fn apply(&self, archive: &Path, config: &CompressionConfig) -> JcResult<PathBuf> {
+        let tar = TarCompressor::new();
+        match self {
+            TarEdit::Append(inputs) => tar.append(archive, inputs, config),
+            TarEdit::Update(inputs) => tar.update(archive, inputs, config),
+            TarEdit::Delete(members) => tar.delete(archive, members, config),
+        }
+    }
+}
+
+/// Run a stream tool from one file into another
+fn pipe_file(
+    cmd: Option<std::process::Command>,
+    tool: &str,
+    input: &Path,
+    output: &Path,
+    decompressing: bool,
+) -> JcResult<()> {
+    let mut cmd = cmd.ok_or_else(|| JcError::Other(format!("{} cannot stream", tool)))?;
+    cmd.stdin(Stdio::from(File::open(input)?))
+        .stdout(Stdio::from(File::create(output)?))
+        .stderr(Stdio::piped());
+
+    debug!("Executing: {:?}", cmd);
+
+    let result = cmd
+        .output()
+        .map_err(|e| JcError::Other(format!("Failed to execute {}: {}", tool, e)))?;
+
+    if !result.status.success() {
+        let tool = tool.to_string();
+        let stderr = String::from_utf8_lossy(&result.stderr).to_string();
+        return Err(if decompressing {
+            JcError::DecompressionFailed { tool, stderr }
+        } else {
+            JcError::CompressionFailed { tool, stderr }
+        });
+    }
+
+    Ok(())
+}
+
+/// Modify an existing TAR archive in place
+///
+/// Compressed archives (.tar.gz, .tar.bz2, .tar.xz) are decompressed into a
+/// staging directory, edited and recompressed. The archive is only replaced
+/// once the new version has been written completely.
+pub fn modify_archive(
+    archive: &Path,
+    edit: &TarEdit,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    if !archive.is_file() {
+        return Err(JcError::FileNotFound(archive.to_path_buf()));
+    }
+    if first_volume_base(archive).is_some() {
+        return Err(JcError::Other(format!(
+            "{} is part of a split archive, decompress it first",
+            archive.display()
+        )));
+    }
+    if is_encrypted(archive) {
+        return Err(JcError::Other(format!(
+            "{} is encrypted, decompress it first",
+            archive.display()
+        )));
+    }
+
+    let format = match detect_format(archive) {
+        Some(CompressionFormat::Tar) => return edit.apply(archive, config),
+        Some(format)
+            if detect_format(&archive.with_extension("")) == Some(CompressionFormat::Tar) =>
+        {
+            format
+        }
+        _ => {
+            return Err(JcError::InvalidExtension(
+                archive.to_path_buf(),
+                "tar, tar.gz, tar.bz2 or tar.xz".to_string(),
+            ))
+        }
+    };
+
+    let compressor = create_compressor(format);
+    if !compressor.validate_level(config.level) {
+        return Err(JcError::InvalidCompressionLevel {
+            algorithm: compressor.name().to_string(),
+            level: config.level,
+        });
+    }
+
+    // Stage the inner TAR archive next to the original
+    let archive_dir = archive
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let staging = CleanupGuard::new(create_temp_dir_in(archive_dir, STAGING_DIR_PREFIX)?);
+
+    let tar_name = archive
+        .with_extension("")
+        .file_name()
+        .ok_or_else(|| JcError::Other("Invalid archive name".to_string()))?
+        .to_os_string();
+    let staged_tar = staging.path().join(tar_name);
+
+    info!(
+        "Decompressing {} with {} for modification",
+        archive.display(),
+        compressor.name()
+    );
+    pipe_file(
+        compressor.decompress_command(),
+        compressor.name(),
+        archive,
+        &staged_tar,
+        true,
+    )?;
+
+    let staging_config = CompressionConfig {
+        fsync: false,
+        ..config.clone()
+    };
+    edit.apply(&staged_tar, &staging_config)?;
+
+    info!(
+        "Recompressing {} with {} at level {}",
+        archive.display(),
+        compressor.name(),
+        config.level
+    );
+    let atomic_output = AtomicOutput::new(archive);
+    pipe_file(
+        compressor.compress_command(config.level),
+        compressor.name(),
+        &staged_tar,
+        atomic_output.temp_path(),
+        false,
+    )?;
+
+    // Keep the original permissions on the replacement
+    let permissions = std::fs::metadata(archive)?.permissions();
+    std::fs::set_permissions(atomic_output.temp_path(), permissions)?;
+
+    let final_path = atomic_output.commit(config.fsync)?;
+    info!("Updated archive: {}", final_path.display());
+
+    Ok(final_path)
+}
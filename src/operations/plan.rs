@@ -0,0 +1,447 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::{CollectionMode, CompressionConfig, ConflictPolicy};
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{CompoundFormat, CompressionFormat};
+use crate::operations::auto::AutoChoice;
+use crate::operations::convert::{convert_output, ConvertTarget};
+use crate::operations::decompress::decompress_destination;
+use crate::operations::dedup::snapshot_name;
+use crate::operations::recursive::recursive_files;
+use crate::utils::conflict::planned_conflict;
+use crate::utils::dedup::snapshot_path_in;
+use crate::utils::probe::skip_reason;
+use crate::utils::template::render_stem;
+use crate::utils::{check_move_to, generate_output_filename, generate_output_filename_from};
+
+/// One output a run would write
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedOutput {
+    /// Operation writing the output, e.g. `compress` or `decompress`
+    pub action: &'static str,
+
+    pub inputs: Vec<PathBuf>,
+
+    /// Path that would be written, once the conflict policy is applied
+    pub output: PathBuf,
+
+    /// Conflict policy applied because the output already exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exists: Option<&'static str>,
+}
+
+/// An input a run would leave as it is, because compressing it would gain little
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedInput {
+    pub input: PathBuf,
+
+    pub reason: String,
+}
+
+/// What a run would do, worked out without changing the filesystem
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    /// Directories that would be created
+    pub create_dirs: Vec<PathBuf>,
+
+    pub outputs: Vec<PlannedOutput>,
+
+    /// Inputs that would not be compressed
+    pub skip: Vec<SkippedInput>,
+
+    /// Inputs that would be removed once their outputs are verified
+    pub remove: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Record an output, applying the conflict policy if one is given
+    ///
+    /// Outputs changed in place, such as edited archives, have no policy.
+    fn add(
+        &mut self,
+        action: &'static str,
+        inputs: Vec<PathBuf>,
+        output: &Path,
+        policy: Option<ConflictPolicy>,
+    ) {
+        let (output, exists) = match policy {
+            Some(policy) => planned_conflict(output, policy),
+            None => (output.to_path_buf(), None),
+        };
+
+        self.outputs.push(PlannedOutput {
+            action,
+            inputs,
+            output,
+            exists: exists.map(|p| p.name()),
+        });
+    }
+
+    /// Record a destination directory, checking that it could be created
+    fn add_dir(&mut self, dir: &Path) -> JcResult<()> {
+        if check_move_to(dir)? && !self.create_dirs.iter().any(|d| d == dir) {
+            self.create_dirs.push(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn add_skip(&mut self, input: &Path, reason: String) {
+        self.skip.push(SkippedInput {
+            input: input.to_path_buf(),
+            reason,
+        });
+    }
+
+    fn add_removals(&mut self, inputs: &[PathBuf], config: &CompressionConfig) {
+        if config.remove_input {
+            self.remove.extend(inputs.iter().cloned());
+        }
+    }
+
+    /// Describe the plan as text lines or as a JSON document
+    pub fn render(&self, json: bool) -> JcResult<String> {
+        if json {
+            return serde_json::to_string_pretty(self)
+                .map_err(|e| JcError::Other(format!("Failed to describe the plan: {}", e)));
+        }
+
+        let mut lines = Vec::new();
+        for dir in &self.create_dirs {
+            lines.push(format!("mkdir       {}", dir.display()));
+        }
+        for output in &self.outputs {
+            let inputs: Vec<String> = output
+                .inputs
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            let mut line = format!(
+                "{:<11} {} -> {}",
+                output.action,
+                inputs.join(", "),
+                output.output.display()
+            );
+            if let Some(policy) = output.exists {
+                line.push_str(&format!(" (exists: {})", policy));
+            }
+            lines.push(line);
+        }
+        for skipped in &self.skip {
+            lines.push(format!(
+                "skip        {} ({})",
+                skipped.input.display(),
+                skipped.reason
+            ));
+        }
+        for input in &self.remove {
+            lines.push(format!("remove      {}", input.display()));
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Put an output in the move-to directory unless `-o` names it
+fn place(output: PathBuf, config: &CompressionConfig) -> JcResult<PathBuf> {
+    match config.move_to {
+        Some(ref dir) if config.output.is_none() => {
+            let name = output
+                .file_name()
+                .ok_or_else(|| JcError::Other("Invalid output filename".to_string()))?;
+            Ok(dir.join(name))
+        }
+        _ => Ok(output),
+    }
+}
+
+/// Extension of the outputs of a compression command
+fn command_extension(command: &str, config: &CompressionConfig) -> JcResult<String> {
+    let extension = match CompoundFormat::from_str(command) {
+        Some(compound) => compound.extension(),
+        None => CompressionFormat::from_name(command)
+            .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?
+            .extension(),
+    };
+    Ok(config.output_extension(extension))
+}
+
+/// Plan compressing each input on its own
+pub fn plan_compress(
+    inputs: &[PathBuf],
+    command: &str,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let jobs = inputs
+        .iter()
+        .map(|input| {
+            let reason = if command == CompressionFormat::Tar.name() {
+                None
+            } else {
+                skip_reason(input, config.skip)?
+            };
+            Ok((input, reason.map_or(Ok(command), Err)))
+        })
+        .collect::<JcResult<Vec<_>>>()?;
+    plan_compress_jobs(&jobs, config)
+}
+
+/// Plan compressing each input with the format `-c auto` chose for it
+pub fn plan_auto(choices: &[JcResult<AutoChoice>], config: &CompressionConfig) -> JcResult<Plan> {
+    let jobs = choices
+        .iter()
+        .map(|choice| match choice {
+            Ok(choice) => Ok((&choice.inputs[0], Ok(choice.command))),
+            Err(JcError::Incompressible { path, reason }) => Ok((path, Err(reason.clone()))),
+            Err(e) => Err(JcError::Other(e.to_string())),
+        })
+        .collect::<JcResult<Vec<_>>>()?;
+    plan_compress_jobs(&jobs, config)
+}
+
+/// Plan compressing each input with its command, or skipping it for the reason given
+fn plan_compress_jobs(
+    jobs: &[(&PathBuf, Result<&str, String>)],
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    if let Some(ref dir) = config.move_to {
+        plan.add_dir(dir)?;
+    }
+
+    let mut compressed = Vec::new();
+    for (i, (input, command)) in jobs.iter().enumerate() {
+        let command = match command {
+            Ok(command) => command,
+            Err(reason) => {
+                plan.add_skip(input, reason.clone());
+                continue;
+            }
+        };
+        let extension = command_extension(command, config)?;
+        let config = config.clone().with_counter(i + 1);
+        let output = place(
+            generate_output_filename(input, &extension, &config)?,
+            &config,
+        )?;
+        plan.add(
+            "compress",
+            vec![input.to_path_buf()],
+            &output,
+            Some(config.on_conflict),
+        );
+        compressed.push(input.to_path_buf());
+    }
+    plan.add_removals(&compressed, config);
+
+    Ok(plan)
+}
+
+/// Plan compressing or decompressing every file below the inputs
+pub fn plan_recursive(
+    inputs: &[PathBuf],
+    decompress: bool,
+    command: &str,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let format = if decompress {
+        None
+    } else {
+        Some(
+            CompressionFormat::from_name(command)
+                .ok_or_else(|| JcError::InvalidCommand(command.to_string()))?,
+        )
+    };
+
+    let mut plan = Plan::default();
+    let mut files = recursive_files(inputs, format, config)?;
+    let mut skipped = Vec::new();
+    for (i, (file, dest)) in files.iter().enumerate() {
+        let mut job_config = config.clone().with_counter(i + 1);
+        if let Some(ref dest) = dest {
+            plan.add_dir(dest)?;
+            job_config.move_to = Some(dest.clone());
+        }
+
+        let (action, output) = match format {
+            Some(format) => {
+                if format != CompressionFormat::Tar {
+                    if let Some(reason) = skip_reason(file, config.skip)? {
+                        plan.add_skip(file, reason);
+                        skipped.push(file.clone());
+                        continue;
+                    }
+                }
+                let extension = job_config.output_extension(format.extension());
+                let output = generate_output_filename(file, &extension, &job_config)?;
+                ("compress", place(output, &job_config)?)
+            }
+            None => ("decompress", decompress_destination(file, &job_config)?),
+        };
+        plan.add(
+            action,
+            vec![file.clone()],
+            &output,
+            Some(config.on_conflict),
+        );
+    }
+
+    if config.remove_input {
+        files.retain(|(file, _)| !skipped.contains(file));
+        plan.remove = files.into_iter().map(|(file, _)| file).collect();
+    }
+    Ok(plan)
+}
+
+/// Plan collecting the inputs into one archive named after `package_name`
+pub fn plan_collection(
+    inputs: &[PathBuf],
+    command: &str,
+    package_name: &str,
+    mode: CollectionMode,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    if let Some(ref dir) = config.move_to {
+        plan.add_dir(dir)?;
+    }
+
+    let extension = command_extension(command, config)?;
+    let name = Path::new(package_name);
+    let output = if let Some(ref output) = config.output {
+        output.clone()
+    } else {
+        let file = match (&config.output_template, mode) {
+            (Some(template), _) => format!(
+                "{}.{}",
+                render_stem(template, name, inputs, &extension, config)?,
+                extension
+            ),
+            (None, CollectionMode::WithParent) => {
+                let first = inputs.first().ok_or(JcError::NoInputFiles)?;
+                generate_output_filename_from(name, first, &extension, config)?
+                    .to_string_lossy()
+                    .to_string()
+            }
+            // Flat collections are named after the package alone
+            (None, CollectionMode::Flat) => format!("{}.{}", package_name, extension),
+        };
+        // Collections are written to the current directory by default
+        match config.output_dir() {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        }
+    };
+
+    plan.add(
+        "collect",
+        inputs.to_vec(),
+        &output,
+        Some(config.on_conflict),
+    );
+    plan.add_removals(inputs, config);
+    Ok(plan)
+}
+
+/// Plan decompressing each archive
+pub fn plan_decompress(inputs: &[PathBuf], config: &CompressionConfig) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    if let Some(ref dir) = config.move_to {
+        plan.add_dir(dir)?;
+    }
+
+    for input in inputs {
+        let output = decompress_destination(input, config)?;
+        plan.add(
+            "decompress",
+            vec![input.clone()],
+            &output,
+            Some(config.on_conflict),
+        );
+    }
+    plan.add_removals(inputs, config);
+
+    Ok(plan)
+}
+
+/// Plan converting each archive to another format
+pub fn plan_convert(
+    inputs: &[PathBuf],
+    target: ConvertTarget,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    if let Some(ref dir) = config.move_to {
+        plan.add_dir(dir)?;
+    }
+
+    for (i, input) in inputs.iter().enumerate() {
+        let config = config.clone().with_counter(i + 1);
+        let output = place(convert_output(input, target, &config)?, &config)?;
+        plan.add(
+            "convert",
+            vec![input.clone()],
+            &output,
+            Some(config.on_conflict),
+        );
+    }
+    plan.add_removals(inputs, config);
+
+    Ok(plan)
+}
+
+/// Plan an edit of an existing archive, which is replaced in place
+pub fn plan_edit(archive: &Path, action: &'static str, inputs: &[PathBuf]) -> Plan {
+    let mut plan = Plan::default();
+    plan.add(action, inputs.to_vec(), archive, None);
+    plan
+}
+
+/// Plan an incremental or differential backup of one directory
+pub fn plan_incremental(
+    input: &Path,
+    command: &str,
+    state: &Path,
+    differential: bool,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    // The archive goes where the input's own archive would
+    let archive_config = match config.output_dir() {
+        Some(dir) => config.clone().with_move_to(dir),
+        None => config.clone(),
+    };
+
+    let mut plan = plan_compress(&[input.to_path_buf()], command, &archive_config)?;
+    plan.outputs[0].action = "backup";
+
+    // Differential backups only record the state of the full backup
+    if !differential || !state.exists() {
+        plan.add("snapshot", vec![input.to_path_buf()], state, None);
+    }
+    Ok(plan)
+}
+
+/// Plan storing the inputs as a snapshot of a deduplicating repository
+pub fn plan_dedup(
+    inputs: &[PathBuf],
+    repo: &Path,
+    name: &str,
+    config: &CompressionConfig,
+) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    plan.add_dir(repo)?;
+
+    let first = inputs.first().ok_or(JcError::NoInputFiles)?;
+    let output = snapshot_path_in(repo, &snapshot_name(name, first, config)?);
+    plan.add("store", inputs.to_vec(), &output, Some(config.on_conflict));
+
+    Ok(plan)
+}
+
+/// Plan restoring a chain of backups into a new directory
+pub fn plan_restore(archives: &[PathBuf], target: &Path) -> JcResult<Plan> {
+    let mut plan = Plan::default();
+    plan.add_dir(target)?;
+    plan.add("restore", archives.to_vec(), target, None);
+    Ok(plan)
+}
@@ -0,0 +1,146 @@
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::{CompressionConfig, TimestampOption};
+use crate::core::error::{JcError, JcResult};
+use crate::utils::checksum::remove_sidecars;
+use crate::utils::fs::output_filename_with_stamp;
+use crate::utils::retention::expired;
+use crate::utils::template::stamp_placeholder;
+use crate::utils::timestamp::parse_timestamp;
+use crate::utils::{debug, info};
+
+/// Stands in for the timestamp when naming earlier outputs
+const STAMP_MARK: &str = "\u{1}";
+
+/// strftime pattern of the timestamp in output names
+fn stamp_pattern(config: &CompressionConfig) -> JcResult<String> {
+    let option = match config.output_template {
+        Some(ref template) => match stamp_placeholder(&template.pattern) {
+            Ok("date") => TimestampOption::Date,
+            Ok(_) if config.timestamp == TimestampOption::None => TimestampOption::DateTime,
+            Ok(_) => config.timestamp.clone(),
+            Err(e) => return Err(JcError::Other(e)),
+        },
+        None => config.timestamp.clone(),
+    };
+
+    option
+        .pattern()
+        .map(str::to_string)
+        .ok_or_else(|| JcError::Other("Retention needs timestamped output names".to_string()))
+}
+
+/// Name of the whole output for a file that may be one of its volumes
+fn archive_name(file_name: &str) -> &str {
+    match file_name.rsplit_once('.') {
+        Some((base, n)) if n.len() == 3 && n.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => file_name,
+    }
+}
+
+/// Remove earlier outputs of `name` that fall outside the retention policy
+///
+/// `output` is the output just written for `name` and is always kept. Earlier
+/// outputs are the files next to it whose names differ only in a timestamp
+/// that reads back as a date; their volumes and checksum sidecars go with them.
+/// Returns the archives removed, or those that would be with a dry run.
+pub fn prune_outputs(
+    name: &Path,
+    output: &Path,
+    extension: &str,
+    config: &CompressionConfig,
+) -> JcResult<Vec<PathBuf>> {
+    let Some(ref policy) = config.retention else {
+        return Ok(Vec::new());
+    };
+
+    let pattern = stamp_pattern(config)?;
+    let utc = config.timestamp_style.utc;
+    let marked = output_filename_with_stamp(name, extension, config, STAMP_MARK)?;
+    let marked = marked
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (prefix, suffix) = marked.split_once(STAMP_MARK).ok_or_else(|| {
+        JcError::Other(format!(
+            "Retention needs timestamped output names: {}",
+            output.display()
+        ))
+    })?;
+
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let current = output
+        .file_name()
+        .map(|n| archive_name(&n.to_string_lossy()).to_string())
+        .unwrap_or_default();
+
+    // Group the outputs of the same input by archive, with their volumes
+    let mut archives: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let archive = archive_name(&file_name);
+
+        let stamp = archive
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix));
+        if stamp.is_some_and(|stamp| parse_timestamp(stamp, &pattern, utc).is_some()) {
+            archives
+                .entry(archive.to_string())
+                .or_default()
+                .push(entry.path());
+        }
+    }
+
+    if !archives.contains_key(&current) {
+        return Err(JcError::Other(format!(
+            "Cannot apply retention, {} is not named like {}<timestamp>{}",
+            output.display(),
+            prefix,
+            suffix
+        )));
+    }
+
+    let names: Vec<String> = archives.keys().cloned().collect();
+    let times: Vec<_> = names
+        .iter()
+        .filter_map(|archive| {
+            let stamp = &archive[prefix.len()..archive.len() - suffix.len()];
+            parse_timestamp(stamp, &pattern, utc)
+        })
+        .collect();
+    debug!(
+        "Found {} output(s) like {}<timestamp>{} in {}",
+        names.len(),
+        prefix,
+        suffix,
+        dir.display()
+    );
+
+    let mut removed = Vec::new();
+    for (archive, expired) in names.iter().zip(expired(&times, policy, Utc::now(), utc)) {
+        if !expired || *archive == current {
+            continue;
+        }
+
+        let path = dir.join(archive);
+        if policy.dry_run {
+            info!("Would remove {}", path.display());
+        } else {
+            for file in &archives[archive] {
+                fs::remove_file(file)?;
+            }
+            remove_sidecars(&path)?;
+            info!("Removed {}", path.display());
+        }
+        removed.push(path);
+    }
+
+    Ok(removed)
+}
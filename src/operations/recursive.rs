@@ -0,0 +1,239 @@
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compressors::detect_format;
+use crate::core::config::CompressionConfig;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{ChecksumAlgorithm, CompressionFormat};
+use crate::operations::compress::compress_file;
+use crate::operations::decompress::decompress_file;
+use crate::utils::atomic::TEMP_FILE_PREFIX;
+use crate::utils::crypto::is_encrypted;
+use crate::utils::exclude::is_excluded;
+use crate::utils::probe::check_compressible;
+use crate::utils::split::volume_base;
+use crate::utils::{debug, error, info};
+
+/// A single file found while walking the inputs, with its own destination
+struct FileJob {
+    path: PathBuf,
+    move_to: Option<PathBuf>,
+}
+
+/// Walk a directory and collect regular files accepted by `filter`
+///
+/// Symbolic links are not followed. `member` is `dir` relative to the
+/// directory holding the input, which the `exclude` patterns are matched
+/// against. `dest` mirrors `dir` below the move-to directory, if one was given.
+fn walk_dir(
+    dir: &Path,
+    member: &Path,
+    dest: Option<PathBuf>,
+    exclude: &[String],
+    filter: &dyn Fn(&Path) -> bool,
+    jobs: &mut Vec<FileJob>,
+) -> JcResult<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let name = entry.file_name();
+
+        let entry_member = member.join(&name);
+        if is_excluded(&entry_member, exclude) {
+            debug!("Excluding {}", path.display());
+            continue;
+        }
+
+        if file_type.is_dir() {
+            let sub_dest = dest.as_ref().map(|d| d.join(&name));
+            walk_dir(&path, &entry_member, sub_dest, exclude, filter, jobs)?;
+        } else if file_type.is_file() {
+            if name.to_string_lossy().starts_with(TEMP_FILE_PREFIX) || !filter(&path) {
+                debug!("Skipping {}", path.display());
+                continue;
+            }
+            jobs.push(FileJob {
+                path,
+                move_to: dest.clone(),
+            });
+        } else {
+            debug!("Skipping non-regular file {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand inputs into per-file jobs, mirroring directories into `move_to`
+///
+/// Inputs and the entries below them that match an `exclude` pattern are left out.
+fn collect_jobs(
+    inputs: &[PathBuf],
+    move_to: &Option<PathBuf>,
+    exclude: &[String],
+    filter: &dyn Fn(&Path) -> bool,
+) -> JcResult<Vec<FileJob>> {
+    let mut jobs = Vec::new();
+
+    for input in inputs {
+        let member = Path::new(input.file_name().unwrap_or(input.as_os_str()));
+        if is_excluded(member, exclude) {
+            info!("Excluding {}", input.display());
+        } else if input.is_dir() {
+            let dest = match move_to {
+                Some(dir) => {
+                    let name = input
+                        .file_name()
+                        .ok_or_else(|| JcError::Other("Invalid directory name".to_string()))?;
+                    Some(dir.join(name))
+                }
+                None => None,
+            };
+            walk_dir(input, member, dest, exclude, filter, &mut jobs)?;
+        } else if filter(input) {
+            jobs.push(FileJob {
+                path: input.clone(),
+                move_to: move_to.clone(),
+            });
+        } else {
+            info!("Skipping {}", input.display());
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Accept files that are not outputs of an earlier run
+///
+/// Files carrying the target extension are skipped, and so are their split
+/// volumes, their checksum sidecars and the sums files of a batch.
+fn compress_filter(
+    format: CompressionFormat,
+    config: &CompressionConfig,
+) -> impl Fn(&Path) -> bool {
+    let suffix = format!(".{}", config.output_extension(format.extension()));
+    let is_output = move |path: &Path| {
+        let whole = volume_base(path).unwrap_or_else(|| path.to_path_buf());
+        whole.to_string_lossy().ends_with(&suffix)
+    };
+
+    move |path: &Path| {
+        let is_checksum = ChecksumAlgorithm::ALL.iter().any(|algorithm| {
+            path.file_name()
+                .is_some_and(|name| name == algorithm.sums_name())
+                || (path
+                    .extension()
+                    .is_some_and(|ext| ext == algorithm.extension())
+                    && is_output(&path.with_extension("")))
+        });
+        !is_output(path) && !is_checksum
+    }
+}
+
+/// Accept files with a recognised compression or encryption extension
+fn decompress_filter(path: &Path) -> bool {
+    detect_format(path).is_some() || is_encrypted(path)
+}
+
+/// Files recursive mode would process, each with the directory its output goes to
+///
+/// Compresses with `format`, or decompresses when it is `None`. Nothing is
+/// created; the destination directories appear once the files are processed.
+pub fn recursive_files(
+    inputs: &[PathBuf],
+    format: Option<CompressionFormat>,
+    config: &CompressionConfig,
+) -> JcResult<Vec<(PathBuf, Option<PathBuf>)>> {
+    let jobs = match format {
+        Some(format) => collect_jobs(
+            inputs,
+            &config.move_to,
+            &config.exclude,
+            &compress_filter(format, config),
+        )?,
+        None => collect_jobs(inputs, &config.move_to, &config.exclude, &decompress_filter)?,
+    };
+    Ok(jobs
+        .into_iter()
+        .map(|job| (job.path, job.move_to))
+        .collect())
+}
+
+/// Run a per-file operation over all jobs in parallel
+fn run_jobs(
+    jobs: Vec<FileJob>,
+    config: &CompressionConfig,
+    operation: &str,
+    op: impl Fn(&Path, &CompressionConfig) -> JcResult<PathBuf> + Sync,
+) -> Vec<JcResult<PathBuf>> {
+    jobs.par_iter()
+        .enumerate()
+        .map(|(i, job)| {
+            let mut job_config = config.clone().with_counter(i + 1);
+            if let Some(ref dest) = job.move_to {
+                fs::create_dir_all(dest)?;
+                job_config.move_to = Some(dest.clone());
+            }
+
+            match op(&job.path, &job_config) {
+                Err(e @ (JcError::Skipped(_) | JcError::Incompressible { .. })) => Err(e),
+                Err(e) => {
+                    error!("Failed to {} {}: {}", operation, job.path.display(), e);
+                    Err(e)
+                }
+                ok => ok,
+            }
+        })
+        .collect()
+}
+
+/// Compress every regular file below the inputs individually
+///
+/// Files that already carry the target extension are skipped; files the
+/// skip policy rejects fail with `JcError::Incompressible`.
+pub fn compress_recursive(
+    inputs: Vec<PathBuf>,
+    format: CompressionFormat,
+    config: CompressionConfig,
+) -> JcResult<Vec<JcResult<PathBuf>>> {
+    let jobs = collect_jobs(
+        &inputs,
+        &config.move_to,
+        &config.exclude,
+        &compress_filter(format, &config),
+    )?;
+    info!(
+        "Compressing {} files recursively with {}",
+        jobs.len(),
+        format.name()
+    );
+
+    Ok(run_jobs(jobs, &config, "compress", |path, job_config| {
+        if format != CompressionFormat::Tar {
+            check_compressible(path, job_config.skip)?;
+        }
+        compress_file(path, format, job_config)
+    }))
+}
+
+/// Decompress every compressed file below the inputs
+///
+/// Files without a recognised compression extension are skipped.
+pub fn decompress_recursive(
+    inputs: Vec<PathBuf>,
+    config: CompressionConfig,
+) -> JcResult<Vec<JcResult<PathBuf>>> {
+    let jobs = collect_jobs(
+        &inputs,
+        &config.move_to,
+        &config.exclude,
+        &decompress_filter,
+    )?;
+    info!("Decompressing {} files recursively", jobs.len());
+
+    Ok(run_jobs(jobs, &config, "decompress", decompress_file))
+}
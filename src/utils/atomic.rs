@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::core::error::JcResult;
+use crate::utils::logger::{debug, error};
+
+/// Prefix of the hidden temporary files written next to final outputs
+pub const TEMP_FILE_PREFIX: &str = ".jcztmp_";
+
+/// Prefix of the staging directories used by compound and collection operations
+pub const STAGING_DIR_PREFIX: &str = "jczpkg_";
+
+/// Prefix of the staging directories used by decompression
+pub const DECOMPRESS_DIR_PREFIX: &str = "jczdec_";
+
+static PARTIALS: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn partials() -> &'static Mutex<HashSet<PathBuf>> {
+    PARTIALS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Register a path to be removed if the process is interrupted
+pub fn register_partial(path: &Path) {
+    let mut set = partials().lock().unwrap_or_else(|e| e.into_inner());
+    set.insert(path.to_path_buf());
+}
+
+/// Forget a path registered with `register_partial`
+pub fn unregister_partial(path: &Path) {
+    let mut set = partials().lock().unwrap_or_else(|e| e.into_inner());
+    set.remove(path);
+}
+
+/// Remove every registered partial output
+fn cleanup_partials() {
+    let paths: Vec<PathBuf> = {
+        let mut set = partials().lock().unwrap_or_else(|e| e.into_inner());
+        set.drain().collect()
+    };
+
+    for path in paths {
+        remove_path(&path);
+    }
+}
+
+/// Install a SIGINT/SIGTERM handler that removes partial outputs before exiting
+pub fn install_signal_handler() {
+    let result = ctrlc::set_handler(|| {
+        error!("Interrupted, removing partial outputs");
+        cleanup_partials();
+        std::process::exit(130);
+    });
+
+    if let Err(e) = result {
+        debug!("Failed to install signal handler: {}", e);
+    }
+}
+
+/// Remove a file or directory, ignoring errors
+pub fn remove_path(path: &Path) {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+
+    match result {
+        Ok(()) => debug!("Removed {}", path.display()),
+        Err(e) => debug!("Failed to remove {}: {}", path.display(), e),
+    }
+}
+
+/// Generate a unique name tagged with the current PID, e.g. `<prefix><pid>_<n>`
+pub fn unique_name(prefix: &str) -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{}{}_{:x}_{}", prefix, std::process::id(), nanos, counter)
+}
+
+/// Get a temporary path in the same directory as `target`
+pub fn temp_sibling(target: &Path) -> PathBuf {
+    let dir = target
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    dir.join(format!("{}_{}", unique_name(TEMP_FILE_PREFIX), name))
+}
+
+/// Extract the PID of the process owning a jcz temporary file or staging directory
+///
+/// Only names in the `unique_name` format count, `<prefix><pid>_<nanos>_<n>`,
+/// followed by `_<name>` for temporary files. Anything else is not jcz's.
+pub fn owner_pid(name: &str) -> Option<u32> {
+    let (prefix, rest) = [TEMP_FILE_PREFIX, STAGING_DIR_PREFIX, DECOMPRESS_DIR_PREFIX]
+        .iter()
+        .find_map(|prefix| Some((*prefix, name.strip_prefix(prefix)?)))?;
+
+    let mut parts = rest.splitn(4, '_');
+    let pid = parts.next()?.parse().ok()?;
+    u128::from_str_radix(parts.next()?, 16).ok()?;
+    parts.next()?.parse::<u64>().ok()?;
+    let named = parts.next().is_some_and(|n| !n.is_empty());
+    (named == (prefix == TEMP_FILE_PREFIX)).then_some(pid)
+}
+
+/// Flush a file, or every file below a directory, to stable storage
+pub fn sync_path(path: &Path) -> JcResult<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            sync_path(&entry?.path())?;
+        }
+    }
+
+    File::open(path)?.sync_all()?;
+    Ok(())
+}
+
+/// An output written under a temporary name and renamed into place on success
+///
+/// If dropped without being committed, the temporary file (or directory) is
+/// removed, so interrupted or failed operations never leave a truncated file
+/// at the final name.
+#[derive(Debug)]
+pub struct AtomicOutput {
+    target: PathBuf,
+    temp: PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    pub fn new(target: &Path) -> Self {
+        let temp = temp_sibling(target);
+        register_partial(&temp);
+
+        debug!(
+            "Writing {} via temporary {}",
+            target.display(),
+            temp.display()
+        );
+
+        Self {
+            target: target.to_path_buf(),
+            temp,
+            committed: false,
+        }
+    }
+
+    /// Path to write the output to
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Final path of the output
+    #[allow(dead_code)]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Rename the temporary output into place, optionally syncing it first
+    pub fn commit(mut self, fsync: bool) -> JcResult<PathBuf> {
+        if fsync {
+            sync_path(&self.temp)?;
+        }
+
+        fs::rename(&self.temp, &self.target)?;
+        self.committed = true;
+        unregister_partial(&self.temp);
+
+        if fsync {
+            // Persist the rename itself
+            if let Some(parent) = self.target.parent().filter(|p| !p.as_os_str().is_empty()) {
+                if let Err(e) = File::open(parent).and_then(|d| d.sync_all()) {
+                    debug!("Failed to sync directory {}: {}", parent.display(), e);
+                }
+            }
+        }
+
+        Ok(self.target.clone())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            remove_path(&self.temp);
+            unregister_partial(&self.temp);
+        }
+    }
+}
+
+/// RAII guard for cleaning up a temporary file or directory
+///
+/// The path is also removed if the process is interrupted by a signal.
+#[derive(Debug)]
+pub struct CleanupGuard {
+    path: PathBuf,
+}
+
+impl CleanupGuard {
+    pub fn new(path: PathBuf) -> Self {
+        register_partial(&path);
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        remove_path(&self.path);
+        unregister_partial(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_pid() {
+        let name = unique_name(STAGING_DIR_PREFIX);
+        assert_eq!(owner_pid(&name), Some(std::process::id()));
+
+        let temp = temp_sibling(Path::new("dir/file.txt.gz"));
+        let temp_name = temp.file_name().unwrap().to_str().unwrap();
+        assert!(temp_name.ends_with("_file.txt.gz"));
+        assert_eq!(owner_pid(temp_name), Some(std::process::id()));
+
+        assert_eq!(owner_pid("jczpkg_18a2b3c4d5e6f7a8"), None);
+        assert_eq!(owner_pid("jczpkg_backup"), None);
+        assert_eq!(owner_pid("jczpkg_42_backup"), None);
+        assert_eq!(owner_pid(".jcztmp_notes"), None);
+        assert_eq!(owner_pid(".jcztmp_42_1a2b_0"), None);
+        assert_eq!(owner_pid("jczdec_42_1a2b_0_extra"), None);
+        assert_eq!(owner_pid("file.txt"), None);
+    }
+}
@@ -0,0 +1,353 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ChecksumAlgorithm;
+use crate::utils::atomic::AtomicOutput;
+use crate::utils::logger::{debug, info};
+use crate::utils::split::{first_volume_base, input_files};
+
+/// Incremental digest for one of the supported algorithms
+pub enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Get the digest as lowercase hex
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Writer that hashes everything passed through to `inner`
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Option<Hasher>,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Wrap `inner`, hashing only if an algorithm is given
+    pub fn new(inner: W, algorithm: Option<ChecksumAlgorithm>) -> Self {
+        Self {
+            inner,
+            hasher: algorithm.map(Hasher::new),
+        }
+    }
+
+    /// Unwrap the inner writer and the digest of the data written
+    pub fn finish(self) -> (W, Option<String>) {
+        (self.inner, self.hasher.map(Hasher::finalize))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Path of the sidecar for an output, e.g. `name.tar.gz.sha256`
+pub fn sidecar_path(output: &Path, algorithm: ChecksumAlgorithm) -> PathBuf {
+    let mut name = OsString::from(output.as_os_str());
+    name.push(".");
+    name.push(algorithm.extension());
+    PathBuf::from(name)
+}
+
+/// Remove the sidecars of an output that is being removed
+///
+/// Entries in a sums file are left alone, as the file is shared with other
+/// outputs.
+pub fn remove_sidecars(output: &Path) -> JcResult<()> {
+    for algorithm in ChecksumAlgorithm::ALL {
+        let sidecar = sidecar_path(output, algorithm);
+        if sidecar.is_file() {
+            fs::remove_file(&sidecar)?;
+            debug!("Removed checksum sidecar {}", sidecar.display());
+        }
+    }
+    Ok(())
+}
+
+/// Format a checksum line as written by `sha256sum` and `b3sum`
+fn checksum_line(digest: &str, file: &Path) -> String {
+    format!("{}  {}\n", digest, file_name(file))
+}
+
+/// Parse a checksum line into digest and file name
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let (digest, name) = line.split_once(char::is_whitespace)?;
+    let name = name.trim_start();
+    let name = name.strip_prefix('*').unwrap_or(name);
+    if digest.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((digest, name))
+}
+
+/// Write the sidecar for `output`, with a line for each file it was stored in
+///
+/// `files` is the output itself, or every volume of a split set in order.
+pub fn write_sidecar(
+    output: &Path,
+    algorithm: ChecksumAlgorithm,
+    files: &[(PathBuf, String)],
+    fsync: bool,
+) -> JcResult<PathBuf> {
+    let content: String = files
+        .iter()
+        .map(|(file, digest)| checksum_line(digest, file))
+        .collect();
+
+    let sidecar = AtomicOutput::new(&sidecar_path(output, algorithm));
+    fs::write(sidecar.temp_path(), content)?;
+    let path = sidecar.commit(fsync)?;
+
+    debug!("Wrote checksum {}", path.display());
+    Ok(path)
+}
+
+/// Hash a single file
+fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> JcResult<String> {
+    let mut writer = HashingWriter::new(io::sink(), Some(algorithm));
+    io::copy(&mut File::open(path)?, &mut writer)?;
+
+    let (_, digest) = writer.finish();
+    Ok(digest.unwrap_or_default())
+}
+
+/// File name as it appears in checksum lines
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Find the recorded checksums of the files of an output in a sidecar or a sums file
+///
+/// `files` is the output itself, or every volume of a split set. The digests
+/// are returned in the same order, and only if every file is listed.
+fn recorded_checksums(
+    output: &Path,
+    files: &[PathBuf],
+) -> JcResult<Option<(ChecksumAlgorithm, Vec<String>)>> {
+    let dir = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    for algorithm in ChecksumAlgorithm::ALL {
+        for file in [
+            sidecar_path(output, algorithm),
+            dir.join(algorithm.sums_name()),
+        ] {
+            if !file.exists() {
+                continue;
+            }
+
+            let content = fs::read_to_string(&file)?;
+            let recorded: BTreeMap<&str, &str> = content
+                .lines()
+                .filter_map(parse_line)
+                .map(|(digest, name)| (name, digest))
+                .collect();
+            let found: Option<Vec<String>> = files
+                .iter()
+                .map(|f| {
+                    recorded
+                        .get(file_name(f).as_str())
+                        .map(|d| d.to_lowercase())
+                })
+                .collect();
+            if let Some(digests) = found {
+                debug!(
+                    "Found checksum of {} in {}",
+                    output.display(),
+                    file.display()
+                );
+                return Ok(Some((algorithm, digests)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Check an archive (or every volume of a split set) against its recorded checksums
+pub fn verify_checksum(path: &Path) -> JcResult<()> {
+    let output = first_volume_base(path).unwrap_or_else(|| path.to_path_buf());
+    let files = input_files(path)?;
+
+    let (algorithm, expected) = recorded_checksums(&output, &files)?
+        .ok_or_else(|| JcError::Other(format!("No checksum found for {}", output.display())))?;
+
+    for (file, expected) in files.iter().zip(expected) {
+        let actual = hash_file(file, algorithm)?;
+        if actual != expected {
+            return Err(JcError::ChecksumMismatch {
+                path: file.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+
+    info!("Checksum of {} matches", output.display());
+    Ok(())
+}
+
+/// Check whether an archive (or split set) has a recorded checksum
+pub fn has_checksum(path: &Path) -> JcResult<bool> {
+    let output = first_volume_base(path).unwrap_or_else(|| path.to_path_buf());
+    Ok(recorded_checksums(&output, &input_files(path)?)?.is_some())
+}
+
+/// Check whether a checksum line names `output` or one of its volumes
+fn names_output(name: &str, output: &str) -> bool {
+    name == output
+        || name
+            .strip_prefix(output)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Merge the sidecars of a batch into one sums file per output directory
+///
+/// Entries already in a sums file are kept unless an output replaces them.
+/// Returns the sums files written.
+pub fn merge_sidecars(
+    outputs: &[PathBuf],
+    algorithm: ChecksumAlgorithm,
+    fsync: bool,
+) -> JcResult<Vec<PathBuf>> {
+    let mut by_dir: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for output in outputs {
+        let output = first_volume_base(output).unwrap_or_else(|| output.clone());
+        let dir = output
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        by_dir.entry(dir).or_default().push(output);
+    }
+
+    let mut written = Vec::new();
+    for (dir, outputs) in by_dir {
+        let sums_path = dir.join(algorithm.sums_name());
+
+        let mut entries: BTreeMap<String, String> = BTreeMap::new();
+        if sums_path.exists() {
+            for (digest, name) in fs::read_to_string(&sums_path)?
+                .lines()
+                .filter_map(parse_line)
+            {
+                entries.insert(name.to_string(), digest.to_string());
+            }
+        }
+
+        let mut sidecars = Vec::new();
+        for output in &outputs {
+            // Drop the entries of an earlier, possibly longer, set of the same name
+            let name = file_name(output);
+            entries.retain(|entry, _| !names_output(entry, &name));
+
+            let sidecar = sidecar_path(output, algorithm);
+            let content = fs::read_to_string(&sidecar)?;
+            for (digest, name) in content.lines().filter_map(parse_line) {
+                entries.insert(name.to_string(), digest.to_string());
+            }
+            sidecars.push(sidecar);
+        }
+
+        let sums = AtomicOutput::new(&sums_path);
+        let content: String = entries
+            .iter()
+            .map(|(name, digest)| format!("{}  {}\n", digest, name))
+            .collect();
+        fs::write(sums.temp_path(), content)?;
+        written.push(sums.commit(fsync)?);
+
+        for sidecar in sidecars {
+            fs::remove_file(sidecar)?;
+        }
+
+        info!("Wrote {}", sums_path.display());
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        let mut sha = Hasher::new(ChecksumAlgorithm::Sha256);
+        sha.update(b"abc");
+        assert_eq!(
+            sha.finalize(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut b3 = Hasher::new(ChecksumAlgorithm::Blake3);
+        b3.update(b"abc");
+        assert_eq!(
+            b3.finalize(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("abcd  data.tar.gz"),
+            Some(("abcd", "data.tar.gz"))
+        );
+        assert_eq!(parse_line("abcd *data.bin"), Some(("abcd", "data.bin")));
+        assert_eq!(parse_line("abcd"), None);
+        assert_eq!(parse_line(""), None);
+    }
+
+    #[test]
+    fn test_names_output() {
+        assert!(names_output("data.tar.gz", "data.tar.gz"));
+        assert!(names_output("data.tar.gz.001", "data.tar.gz"));
+        assert!(names_output("data.tar.gz.12", "data.tar.gz"));
+        assert!(!names_output("data.tar.gz.sha256", "data.tar.gz"));
+        assert!(!names_output("data.tar.gz.", "data.tar.gz"));
+        assert!(!names_output("data.tar.gz2", "data.tar.gz"));
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use crate::core::config::ConflictPolicy;
+use crate::core::error::{JcError, JcResult};
+use crate::utils::atomic::remove_path;
+use crate::utils::logger::{debug, info};
+use crate::utils::prompt::prompt_overwrite;
+use crate::utils::split::volume_path;
+
+/// Extensions kept together when generating unique names
+const KNOWN_EXTENSIONS: &[&str] = &[
+    ".tar.gz.age",
+    ".tar.bz2.age",
+    ".tar.xz.age",
+    ".gz.age",
+    ".bz2.age",
+    ".xz.age",
+    ".tar.age",
+    ".tar.gz",
+    ".tar.bz2",
+    ".tar.xz",
+    ".gz",
+    ".bz2",
+    ".xz",
+    ".tar",
+];
+
+/// Split a filename into stem and extension, keeping compound extensions intact
+fn split_extension(name: &str) -> (&str, &str) {
+    for ext in KNOWN_EXTENSIONS {
+        if let Some(stem) = name.strip_suffix(ext) {
+            if !stem.is_empty() {
+                return (stem, &name[stem.len()..]);
+            }
+        }
+    }
+
+    match name.rfind('.') {
+        Some(pos) if pos > 0 => name.split_at(pos),
+        _ => (name, ""),
+    }
+}
+
+/// Check whether an output exists, either whole or as split volumes
+fn output_exists(path: &Path) -> bool {
+    path.exists() || volume_path(path, 1).exists()
+}
+
+/// Path of the form `name (N).ext` next to `path`
+fn numbered_path(path: &Path, counter: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (stem, ext) = split_extension(&name);
+
+    path.with_file_name(format!("{} ({}){}", stem, counter, ext))
+}
+
+/// Find an unused path of the form `name (N).ext` next to `path`
+pub fn unique_path(path: &Path) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let candidate = numbered_path(path, counter);
+        if !output_exists(&candidate) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// A name claimed by the `rename` policy
+///
+/// The placeholder is removed when the reservation is dropped, so a failed
+/// run leaves no empty output behind, unless `keep` is called once the output
+/// has taken its place.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct Reservation {
+    path: Option<PathBuf>,
+}
+
+impl Reservation {
+    /// Keep the name now that the output is in place
+    pub fn keep(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            debug!("Releasing reserved name {}", path.display());
+            remove_path(&path);
+        }
+    }
+}
+
+/// Claim an unused path of the form `name (N).ext` next to `path`
+///
+/// The name is created atomically so parallel workers and concurrent runs
+/// never pick the same one: as an empty directory that a directory output is
+/// copied into, or as an empty file that the output replaces when it is
+/// renamed into place.
+fn reserve_unique_path(path: &Path, dir: bool) -> JcResult<PathBuf> {
+    let mut counter = 1;
+    loop {
+        let candidate = numbered_path(path, counter);
+        counter += 1;
+        if output_exists(&candidate) {
+            continue;
+        }
+
+        let reserved = if dir {
+            fs::create_dir(&candidate)
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+                .map(|_| ())
+        };
+        match reserved {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Work out what the conflict policy would do with an output path, without prompting
+///
+/// Returns the path that would be written and, if the output exists, the
+/// policy that applies to it.
+pub fn planned_conflict(path: &Path, policy: ConflictPolicy) -> (PathBuf, Option<ConflictPolicy>) {
+    if !output_exists(path) {
+        return (path.to_path_buf(), None);
+    }
+
+    match policy {
+        ConflictPolicy::Rename => (unique_path(path), Some(policy)),
+        _ => (path.to_path_buf(), Some(policy)),
+    }
+}
+
+/// Apply the conflict policy to an output path
+///
+/// Returns the path to write to along with the reservation of a renamed
+/// output, `JcError::Skipped` if the input should be skipped, or
+/// `JcError::OutputExists` if the operation must fail.
+pub fn resolve_conflict(path: &Path, policy: ConflictPolicy) -> JcResult<(PathBuf, Reservation)> {
+    resolve(path, policy, false)
+}
+
+/// Apply the conflict policy to the path of a directory output
+pub fn resolve_dir_conflict(
+    path: &Path,
+    policy: ConflictPolicy,
+) -> JcResult<(PathBuf, Reservation)> {
+    resolve(path, policy, true)
+}
+
+fn resolve(path: &Path, policy: ConflictPolicy, dir: bool) -> JcResult<(PathBuf, Reservation)> {
+    let unreserved = || (path.to_path_buf(), Reservation::default());
+
+    if !output_exists(path) {
+        return Ok(unreserved());
+    }
+
+    debug!(
+        "Output {} exists, applying policy {:?}",
+        path.display(),
+        policy
+    );
+
+    match policy {
+        ConflictPolicy::Overwrite => Ok(unreserved()),
+        ConflictPolicy::Skip => Err(JcError::Skipped(path.to_path_buf())),
+        ConflictPolicy::Fail => Err(JcError::OutputExists(path.to_path_buf())),
+        ConflictPolicy::Rename => {
+            let renamed = reserve_unique_path(path, dir)?;
+            info!(
+                "{} exists, writing {} instead",
+                path.display(),
+                renamed.display()
+            );
+            Ok((
+                renamed.clone(),
+                Reservation {
+                    path: Some(renamed),
+                },
+            ))
+        }
+        ConflictPolicy::Ask => {
+            if !std::io::stdin().is_terminal() {
+                return Err(JcError::OutputExists(path.to_path_buf()));
+            }
+
+            if prompt_overwrite(path)? {
+                Ok(unreserved())
+            } else {
+                Err(JcError::Skipped(path.to_path_buf()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_extension() {
+        assert_eq!(split_extension("file.txt.gz"), ("file.txt", ".gz"));
+        assert_eq!(split_extension("dir.tar.xz"), ("dir", ".tar.xz"));
+        assert_eq!(split_extension("notes.txt"), ("notes", ".txt"));
+        assert_eq!(split_extension("dir"), ("dir", ""));
+        assert_eq!(split_extension(".hidden"), (".hidden", ""));
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file.txt.gz");
+        std::fs::write(&path, b"data").unwrap();
+        std::fs::write(dir.path().join("file.txt (1).gz"), b"data").unwrap();
+
+        assert_eq!(unique_path(&path), dir.path().join("file.txt (2).gz"));
+    }
+
+    #[test]
+    fn test_reserve_unique_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("file.txt.gz");
+        std::fs::write(&path, b"data").unwrap();
+
+        // Each reservation takes the name, so the next one moves on
+        let (first, kept) = resolve_conflict(&path, ConflictPolicy::Rename).unwrap();
+        let (second, dropped) = resolve_conflict(&path, ConflictPolicy::Rename).unwrap();
+        assert_eq!(first, dir.path().join("file.txt (1).gz"));
+        assert_eq!(second, dir.path().join("file.txt (2).gz"));
+        assert!(first.is_file() && second.is_file());
+
+        // A kept name stays taken, a dropped one is released
+        kept.keep();
+        drop(dropped);
+        assert!(first.is_file());
+        assert!(!second.exists());
+
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        let (renamed, reservation) = resolve_dir_conflict(&out, ConflictPolicy::Rename).unwrap();
+        assert_eq!(renamed, dir.path().join("out (1)"));
+        assert!(renamed.is_dir());
+        drop(reservation);
+        assert!(!renamed.exists());
+    }
+}
@@ -0,0 +1,157 @@
+use age::secrecy::SecretString;
+use age::stream::{StreamReader, StreamWriter};
+use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::EncryptionKeys;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ENCRYPTED_EXTENSION;
+use crate::utils::logger::{debug, info};
+use crate::utils::split::first_volume_base;
+
+/// Environment variable holding the passphrase when no file is given
+pub const PASSPHRASE_ENV: &str = "JCZ_PASSPHRASE";
+
+/// Check whether a path names an encrypted file (or the first volume of one)
+pub fn is_encrypted(path: &Path) -> bool {
+    let base = first_volume_base(path).unwrap_or_else(|| path.to_path_buf());
+    base.extension()
+        .is_some_and(|ext| ext == ENCRYPTED_EXTENSION)
+}
+
+/// Parse age public keys (`age1...`)
+pub fn parse_recipients(keys: &[String]) -> JcResult<Vec<age::x25519::Recipient>> {
+    keys.iter()
+        .map(|key| {
+            key.trim()
+                .parse::<age::x25519::Recipient>()
+                .map_err(|e| JcError::EncryptionFailed(format!("Invalid recipient {}: {}", key, e)))
+        })
+        .collect()
+}
+
+/// Read a passphrase from the first line of a file
+pub fn read_passphrase_file(path: &Path) -> JcResult<String> {
+    let content = fs::read_to_string(path)?;
+    let passphrase = content.lines().next().unwrap_or("").to_string();
+
+    if passphrase.is_empty() {
+        return Err(JcError::Other(format!(
+            "Passphrase file {} is empty",
+            path.display()
+        )));
+    }
+
+    Ok(passphrase)
+}
+
+/// Wrap `output` so that everything written to it is encrypted
+///
+/// Recipients take precedence over a passphrase. The returned writer must be
+/// finished with `StreamWriter::finish` to write the final authenticated chunk.
+pub fn encrypt_writer<W: Write>(output: W, keys: &EncryptionKeys) -> JcResult<StreamWriter<W>> {
+    let encryptor = if !keys.recipients.is_empty() {
+        let recipients = parse_recipients(&keys.recipients)?;
+        debug!("Encrypting to {} recipient(s)", recipients.len());
+        Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn Recipient))
+            .map_err(|e| JcError::EncryptionFailed(e.to_string()))?
+    } else {
+        let passphrase = keys.passphrase.clone().ok_or_else(|| {
+            JcError::EncryptionFailed("No passphrase or recipient given".to_string())
+        })?;
+        debug!("Encrypting with passphrase");
+        Encryptor::with_user_passphrase(SecretString::from(passphrase))
+    };
+
+    Ok(encryptor.wrap_output(output)?)
+}
+
+/// Load every identity from the given age identity files
+fn load_identities(files: &[PathBuf]) -> JcResult<Vec<Box<dyn Identity>>> {
+    let mut identities = Vec::new();
+
+    for file in files {
+        let identity_file = IdentityFile::from_file(file.to_string_lossy().to_string())
+            .map_err(|e| JcError::Other(format!("Failed to read {}: {}", file.display(), e)))?;
+        identities.extend(identity_file.into_identities().map_err(|e| {
+            JcError::Other(format!("Invalid identity file {}: {}", file.display(), e))
+        })?);
+    }
+
+    Ok(identities)
+}
+
+/// Wrap `input` so that reading from it yields the decrypted content
+///
+/// `path` is only used for error messages. Authentication failures surface as
+/// read errors, so a tampered file never decrypts silently.
+pub fn decrypt_reader<R: Read>(
+    input: R,
+    keys: &EncryptionKeys,
+    path: &Path,
+) -> JcResult<StreamReader<R>> {
+    let failed = |reason: String| JcError::DecryptionFailed {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let decryptor = Decryptor::new(input).map_err(|e| failed(e.to_string()))?;
+
+    let identities: Vec<Box<dyn Identity>> = if decryptor.is_scrypt() {
+        let passphrase = keys
+            .passphrase
+            .clone()
+            .ok_or_else(|| failed("archive is passphrase-encrypted".to_string()))?;
+        vec![Box::new(age::scrypt::Identity::new(SecretString::from(
+            passphrase,
+        )))]
+    } else {
+        if keys.identities.is_empty() {
+            return Err(failed(
+                "archive is encrypted to public keys, use --identity".to_string(),
+            ));
+        }
+        load_identities(&keys.identities)?
+    };
+
+    decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref()))
+        .map_err(|e| failed(e.to_string()))
+}
+
+/// Decrypt `input` into `working_dir`, dropping the encryption extension
+///
+/// The plaintext is only readable by its owner, and `working_dir` must be a
+/// private directory from `create_temp_dir_in` so no other user can reach it.
+pub fn decrypt_in_dir(
+    input: &Path,
+    working_dir: &Path,
+    keys: &EncryptionKeys,
+) -> JcResult<PathBuf> {
+    let name = input
+        .file_stem()
+        .ok_or_else(|| JcError::Other("Invalid encrypted filename".to_string()))?;
+    let output_path = working_dir.join(name);
+
+    info!("Decrypting {}", input.display());
+
+    let mut reader = decrypt_reader(File::open(input)?, keys, input)?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&output_path)?;
+    let mut writer = BufWriter::new(file);
+
+    io::copy(&mut reader, &mut writer).map_err(|e| JcError::DecryptionFailed {
+        path: input.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    writer.flush()?;
+
+    debug!("Decrypted to {}", output_path.display());
+    Ok(output_path)
+}
@@ -0,0 +1,376 @@
+use fastcdc::v2020::StreamCDC;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ChecksumAlgorithm;
+use crate::utils::atomic::AtomicOutput;
+use crate::utils::checksum::Hasher;
+use crate::utils::logger::debug;
+use crate::utils::snapshot::EntryKind;
+
+/// File at the top of a repository holding its settings
+pub const REPO_CONFIG: &str = "dedup.json";
+
+/// Directories of a repository: compressed packs, their indexes and snapshots
+const PACKS_DIR: &str = "packs";
+const INDEX_DIR: &str = "index";
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Settings fixed when a repository is created
+///
+/// The chunk sizes must stay the same for the life of the repository, or
+/// unchanged data would be cut differently and stored again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepoConfig {
+    pub jcz_version: String,
+
+    pub min_chunk: u32,
+    pub avg_chunk: u32,
+    pub max_chunk: u32,
+}
+
+impl Default for RepoConfig {
+    fn default() -> Self {
+        Self {
+            jcz_version: env!("CARGO_PKG_VERSION").to_string(),
+            min_chunk: 16 * 1024,
+            avg_chunk: 64 * 1024,
+            max_chunk: 256 * 1024,
+        }
+    }
+}
+
+/// Where a chunk is in the uncompressed content of its pack
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChunkLocation {
+    /// SHA-256 of the chunk
+    pub hash: String,
+
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Chunks stored in one compressed pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackIndex {
+    /// File name of the pack below `packs/`
+    pub pack: String,
+
+    pub chunks: Vec<ChunkLocation>,
+}
+
+/// One file, directory or symbolic link of a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DedupEntry {
+    /// Path relative to the extraction directory, with `/` separators
+    pub path: String,
+
+    pub kind: EntryKind,
+
+    /// Permission bits in octal, e.g. `0644`
+    pub mode: String,
+
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: i64,
+
+    pub size: u64,
+
+    /// SHA-256 of the content of files
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sha256: String,
+
+    /// Target of symbolic links
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target: String,
+
+    /// Hashes of the file's chunks, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+}
+
+/// One stored collection: its entries and the chunks making up each file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupSnapshot {
+    pub jcz_version: String,
+
+    pub name: String,
+
+    /// Creation time in RFC 3339 format
+    pub created: String,
+
+    pub host: String,
+
+    /// Input paths as given when the snapshot was stored
+    pub inputs: Vec<String>,
+
+    pub entries: Vec<DedupEntry>,
+}
+
+impl DedupSnapshot {
+    /// Total size of the files, before deduplication
+    pub fn size(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|e| e.kind == EntryKind::File)
+            .map(|e| e.size)
+            .sum()
+    }
+}
+
+/// A deduplicating repository on disk
+///
+/// ```text
+/// REPO/dedup.json              settings
+/// REPO/packs/<id>.pack.<ext>   new chunks of one store, compressed together
+/// REPO/index/<id>.json         chunk locations in that pack
+/// REPO/snapshots/<name>.json   entries and chunk lists of one collection
+/// ```
+///
+/// Packs and indexes are written before the snapshot that refers to them,
+/// so an interrupted store leaves at most unreferenced data behind.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub root: PathBuf,
+    pub config: RepoConfig,
+}
+
+/// Read and parse a JSON file of the repository
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> JcResult<T> {
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| JcError::Other(format!("Invalid repository file {}: {}", path.display(), e)))
+}
+
+/// Write a JSON file of the repository, replacing it only once complete
+fn write_json<T: Serialize>(path: &Path, value: &T, fsync: bool) -> JcResult<PathBuf> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| JcError::Other(format!("Failed to write {}: {}", path.display(), e)))?;
+
+    let output = AtomicOutput::new(path);
+    fs::write(output.temp_path(), json + "\n")?;
+    output.commit(fsync)
+}
+
+impl Repository {
+    /// Open an existing repository
+    pub fn open(root: &Path) -> JcResult<Self> {
+        let path = root.join(REPO_CONFIG);
+        if !path.is_file() {
+            return Err(JcError::Other(format!(
+                "{} is not a dedup repository (no {})",
+                root.display(),
+                REPO_CONFIG
+            )));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            config: read_json(&path)?,
+        })
+    }
+
+    /// Open a repository, creating it if the directory is new or empty
+    pub fn open_or_init(root: &Path, fsync: bool) -> JcResult<Self> {
+        if root.join(REPO_CONFIG).is_file() {
+            return Self::open(root);
+        }
+        if root.exists() && (!root.is_dir() || fs::read_dir(root)?.next().is_some()) {
+            return Err(JcError::Other(format!(
+                "{} is not a dedup repository and not an empty directory",
+                root.display()
+            )));
+        }
+
+        let repo = Self {
+            root: root.to_path_buf(),
+            config: RepoConfig::default(),
+        };
+        for dir in [PACKS_DIR, INDEX_DIR, SNAPSHOTS_DIR] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        write_json(&root.join(REPO_CONFIG), &repo.config, fsync)?;
+
+        debug!("Created dedup repository {}", root.display());
+        Ok(repo)
+    }
+
+    pub fn packs_dir(&self) -> PathBuf {
+        self.root.join(PACKS_DIR)
+    }
+
+    pub fn snapshot_path(&self, name: &str) -> PathBuf {
+        snapshot_path_in(&self.root, name)
+    }
+
+    /// Location of every stored chunk, with the file name of its pack
+    pub fn load_index(&self) -> JcResult<HashMap<String, (String, ChunkLocation)>> {
+        let mut chunks = HashMap::new();
+        for entry in fs::read_dir(self.root.join(INDEX_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+
+            let index: PackIndex = read_json(&path)?;
+            for chunk in index.chunks {
+                chunks.insert(chunk.hash.clone(), (index.pack.clone(), chunk));
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// Record the chunks of a pack once the pack itself is in place
+    pub fn write_index(&self, id: &str, index: &PackIndex, fsync: bool) -> JcResult<()> {
+        let path = self.root.join(INDEX_DIR).join(format!("{}.json", id));
+        write_json(&path, index, fsync)?;
+        Ok(())
+    }
+
+    /// Names of the stored snapshots, sorted
+    pub fn snapshot_names(&self) -> JcResult<Vec<String>> {
+        let mut names: Vec<String> = fs::read_dir(self.root.join(SNAPSHOTS_DIR))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter_map(|n| n.strip_suffix(".json").map(|s| s.to_string()))
+            .filter(|n| !n.starts_with('.'))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn read_snapshot(&self, name: &str) -> JcResult<DedupSnapshot> {
+        let path = self.snapshot_path(name);
+        if !path.is_file() {
+            return Err(JcError::Other(format!(
+                "No snapshot {} in {}",
+                name,
+                self.root.display()
+            )));
+        }
+        read_json(&path)
+    }
+
+    pub fn write_snapshot(
+        &self,
+        path: &Path,
+        snapshot: &DedupSnapshot,
+        fsync: bool,
+    ) -> JcResult<PathBuf> {
+        write_json(path, snapshot, fsync)
+    }
+}
+
+/// Path of a snapshot in the repository at `root`, which need not exist yet
+pub fn snapshot_path_in(root: &Path, name: &str) -> PathBuf {
+    root.join(SNAPSHOTS_DIR).join(format!("{}.json", name))
+}
+
+/// Hash a chunk with SHA-256
+pub fn chunk_hash(data: &[u8]) -> String {
+    let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Cut `reader` into content-defined chunks and pass each one to `f`
+///
+/// Boundaries depend on the data around them, so an insertion only changes
+/// the chunks next to it and the rest of the content is found again.
+pub fn for_each_chunk<R: Read>(
+    reader: R,
+    config: &RepoConfig,
+    mut f: impl FnMut(&[u8]) -> JcResult<()>,
+) -> JcResult<()> {
+    let chunker = StreamCDC::new(reader, config.min_chunk, config.avg_chunk, config.max_chunk);
+    for chunk in chunker {
+        let chunk = chunk.map_err(io::Error::from)?;
+        f(&chunk.data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Pseudo-random bytes, so that chunk boundaries are spread out
+    fn sample(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    fn chunks_of(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        for_each_chunk(data, &RepoConfig::default(), |c| {
+            chunks.push(c.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        chunks
+    }
+
+    #[test]
+    fn test_chunks_survive_insertion() {
+        let data = sample(2 * 1024 * 1024, 7);
+        let chunks = chunks_of(&data);
+        assert!(chunks.len() > 4);
+        assert_eq!(chunks.concat(), data);
+
+        // Inserting bytes near the start leaves most later chunks unchanged
+        let mut edited = data[..1000].to_vec();
+        edited.extend_from_slice(b"inserted");
+        edited.extend_from_slice(&data[1000..]);
+        let edited_chunks = chunks_of(&edited);
+        assert_eq!(edited_chunks.concat(), edited);
+
+        let known: Vec<String> = chunks.iter().map(|c| chunk_hash(c)).collect();
+        let shared = edited_chunks
+            .iter()
+            .filter(|c| known.contains(&chunk_hash(c)))
+            .count();
+        assert!(shared + 2 >= chunks.len());
+    }
+
+    #[test]
+    fn test_repository_layout() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("repo");
+
+        let repo = Repository::open_or_init(&root, false).unwrap();
+        assert!(root.join(REPO_CONFIG).is_file());
+        assert!(Repository::open(&root).is_ok());
+        assert!(repo.snapshot_names().unwrap().is_empty());
+
+        let index = PackIndex {
+            pack: "abc.pack.gz".to_string(),
+            chunks: vec![ChunkLocation {
+                hash: chunk_hash(b"data"),
+                offset: 0,
+                length: 4,
+            }],
+        };
+        repo.write_index("abc", &index, false).unwrap();
+        let chunks = repo.load_index().unwrap();
+        assert_eq!(chunks[&chunk_hash(b"data")].0, "abc.pack.gz");
+
+        // Directories with other content are not taken over
+        let other = dir.path().join("other");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("file"), b"x").unwrap();
+        assert!(Repository::open_or_init(&other, false).is_err());
+        assert!(Repository::open(&other).is_err());
+    }
+}
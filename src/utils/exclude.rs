@@ -0,0 +1,95 @@
+use std::path::Path;
+
+/// Match `text` against a shell pattern: `*` matches any run of characters,
+/// `/` included, and `?` matches a single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position after the last `*`, and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character
+                Some((after, tried)) => {
+                    p = after;
+                    t = tried + 1;
+                    star = Some((after, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Check whether an archive member or walked path is excluded by a pattern
+///
+/// `path` is relative to the directory holding the input, e.g. `data/logs/a.log`.
+/// Like `tar --exclude`, a pattern excludes the entry if it matches the entry's
+/// name or a trailing part of its path, so `*.log`, `cache` and `logs/*.log`
+/// all exclude `data/logs/a.log`. Excluded directories are skipped whole.
+pub fn is_excluded(path: &Path, patterns: &[String]) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+
+    let components: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+
+    (0..components.len()).any(|start| {
+        let tail = components[start..].join("/");
+        patterns.iter().any(|pattern| glob_match(pattern, &tail))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "app.log"));
+        assert!(glob_match("app.???", "app.log"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(glob_match("logs/*", "logs/2024/app.log"));
+        assert!(!glob_match("*.log", "app.log.gz"));
+        assert!(!glob_match("app.?", "app.log"));
+        assert!(!glob_match("cache", "cache2"));
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let patterns = vec![
+            "*.log".to_string(),
+            "node_modules".to_string(),
+            "build/*.o".to_string(),
+        ];
+        let excluded = |path: &str| is_excluded(Path::new(path), &patterns);
+
+        assert!(excluded("data/app.log"));
+        assert!(excluded("data/web/node_modules"));
+        assert!(excluded("data/build/main.o"));
+        assert!(excluded("data/build/sub/main.o"));
+        assert!(!excluded("data/app.log.gz"));
+        assert!(!excluded("data/node_modules_old"));
+        assert!(!excluded("data/main.o"));
+        assert!(!excluded("data/src/lib.rs"));
+        assert!(!is_excluded(Path::new("data/app.log"), &[]));
+    }
+}
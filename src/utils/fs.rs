@@ -1,19 +1,59 @@
+use std::env;
 use std::fs;
 use std::io;
+use std::os::unix::fs::DirBuilderExt;
 use std::path::{Path, PathBuf};
 
-use tempfile::TempDir;
-
-use crate::core::config::TimestampOption;
+use crate::core::config::{CompressionConfig, TimestampOption, TimestampPosition};
 use crate::core::error::{JcError, JcResult};
+use crate::utils::atomic::{unique_name, AtomicOutput, CleanupGuard, DECOMPRESS_DIR_PREFIX};
+use crate::utils::exclude::is_excluded;
+use crate::utils::logger::{debug, info};
+use crate::utils::template::{render_stem, render_stem_with_stamp};
 use crate::utils::timestamp::generate_timestamp;
 
 /// Generate output filename with optional timestamp
 pub fn generate_output_filename(
     input: &Path,
     extension: &str,
-    timestamp_opt: TimestampOption,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    generate_output_filename_from(input, input, extension, config)
+}
+
+/// Generate output filename for `input`, taking an mtime timestamp from `source`
+pub fn generate_output_filename_from(
+    input: &Path,
+    source: &Path,
+    extension: &str,
+    config: &CompressionConfig,
+) -> JcResult<PathBuf> {
+    output_filename(input, source, extension, config, None)
+}
+
+/// Generate the output filename for `input` with `stamp` as its timestamp
+///
+/// Names of earlier outputs of the same input differ from this only in the stamp.
+pub fn output_filename_with_stamp(
+    input: &Path,
+    extension: &str,
+    config: &CompressionConfig,
+    stamp: &str,
+) -> JcResult<PathBuf> {
+    output_filename(input, input, extension, config, Some(stamp))
+}
+
+fn output_filename(
+    input: &Path,
+    source: &Path,
+    extension: &str,
+    config: &CompressionConfig,
+    stamp: Option<&str>,
 ) -> JcResult<PathBuf> {
+    if let Some(ref output) = config.output {
+        return Ok(output.clone());
+    }
+
     let mut filename = input.as_os_str().to_string_lossy().to_string();
 
     // Remove trailing slash if present
@@ -21,11 +61,36 @@ pub fn generate_output_filename(
         filename.pop();
     }
 
+    // A template names the output on its own, next to the input
+    if let Some(ref template) = config.output_template {
+        let name = Path::new(&filename);
+        let stem = match stamp {
+            Some(stamp) => {
+                render_stem_with_stamp(template, name, &[source], extension, config, stamp)?
+            }
+            None => render_stem(template, name, &[source], extension, config)?,
+        };
+        let dir = filename.rfind('/').map_or("", |i| &filename[..=i]);
+        return Ok(PathBuf::from(format!("{}{}.{}", dir, stem, extension)));
+    }
+
     // Add timestamp if requested
-    if timestamp_opt != TimestampOption::None {
-        let ts = generate_timestamp(timestamp_opt);
-        filename.push('_');
-        filename.push_str(&ts);
+    if config.timestamp != TimestampOption::None {
+        let ts = match stamp {
+            Some(stamp) => stamp.to_string(),
+            None => generate_timestamp(&config.timestamp, &config.timestamp_style, source)?,
+        };
+
+        let (dir, name) = filename.split_at(filename.rfind('/').map_or(0, |i| i + 1));
+        let name = match config.timestamp_style.position {
+            TimestampPosition::Prefix => format!("{}_{}", ts, name),
+            TimestampPosition::Suffix => format!("{}_{}", name, ts),
+            TimestampPosition::BeforeExtension => match name.rfind('.').filter(|&i| i > 0) {
+                Some(i) => format!("{}_{}{}", &name[..i], ts, &name[i..]),
+                None => format!("{}_{}", name, ts),
+            },
+        };
+        filename = format!("{}{}", dir, name);
     }
 
     // Add extension
@@ -35,6 +100,22 @@ pub fn generate_output_filename(
     Ok(PathBuf::from(filename))
 }
 
+/// Resolve the final location of an output, placing it in `move_to` if specified
+pub fn resolve_output_path(output: &Path, move_to: &Option<PathBuf>) -> JcResult<PathBuf> {
+    if let Some(dest_dir) = move_to {
+        if !dest_dir.is_dir() {
+            return Err(JcError::NotADirectory(dest_dir.to_path_buf()));
+        }
+
+        let filename = output
+            .file_name()
+            .ok_or_else(|| JcError::Other("Invalid output filename".to_string()))?;
+        Ok(dest_dir.join(filename))
+    } else {
+        Ok(output.to_path_buf())
+    }
+}
+
 /// Move file to destination directory if specified
 pub fn move_file_if_needed(source: &Path, move_to: &Option<PathBuf>) -> JcResult<PathBuf> {
     if let Some(dest_dir) = move_to {
@@ -72,13 +153,31 @@ pub fn move_file(source: &Path, dest_dir: &Path) -> JcResult<PathBuf> {
 
 /// Recursively copy file or directory
 pub fn copy_recursive(src: &Path, dst: &Path) -> io::Result<()> {
+    copy_excluding(src, dst, Path::new(""), &[])
+}
+
+/// Recursively copy file or directory, leaving out excluded entries
+///
+/// `member` is the name of `src` in the archive being staged, which the
+/// `exclude` patterns are matched against.
+pub fn copy_excluding(src: &Path, dst: &Path, member: &Path, exclude: &[String]) -> io::Result<()> {
+    if is_excluded(member, exclude) {
+        debug!("Excluding {}", src.display());
+        return Ok(());
+    }
+
     if src.is_dir() {
         fs::create_dir_all(dst)?;
         for entry in fs::read_dir(src)? {
             let entry = entry?;
             let src_path = entry.path();
             let dst_path = dst.join(entry.file_name());
-            copy_recursive(&src_path, &dst_path)?;
+            copy_excluding(
+                &src_path,
+                &dst_path,
+                &member.join(entry.file_name()),
+                exclude,
+            )?;
         }
     } else {
         fs::copy(src, dst)?;
@@ -105,7 +204,24 @@ pub fn copy_directory_contents_except(src: &Path, dst: &Path, exclude: &Path) ->
     Ok(())
 }
 
+/// Remove an input file or directory after it has been processed
+///
+/// Symbolic links are removed themselves, never their targets.
+pub fn remove_input(path: &Path) -> JcResult<()> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+
+    info!("Removed input: {}", path.display());
+    Ok(())
+}
+
 /// Remove file, ignoring errors
+#[allow(dead_code)]
 pub fn remove_file_silent(path: &Path) -> io::Result<()> {
     match fs::remove_file(path) {
         Ok(()) => Ok(()),
@@ -114,28 +230,50 @@ pub fn remove_file_silent(path: &Path) -> io::Result<()> {
     }
 }
 
-/// Create temporary directory with prefix
+/// Create temporary directory with prefix in the current directory
 pub fn create_temp_dir(prefix: &str) -> JcResult<PathBuf> {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
+    create_temp_dir_in(Path::new("."), prefix)
+}
 
-    let dir_name = format!("{}{:x}", prefix, timestamp);
-    let temp_path = PathBuf::from(dir_name);
+/// Create temporary directory with prefix in the given directory
+///
+/// The name embeds the current PID so that `jcz cleanup` can tell stale
+/// directories from ones still in use. Only the owner can read the
+/// directory, since it may hold extracted or decrypted data.
+pub fn create_temp_dir_in(parent: &Path, prefix: &str) -> JcResult<PathBuf> {
+    let temp_path = parent.join(unique_name(prefix));
 
-    fs::create_dir(&temp_path).map_err(|e| JcError::TempDirFailed(e.to_string()))?;
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&temp_path)
+        .map_err(|e| JcError::TempDirFailed(e.to_string()))?;
 
     Ok(temp_path)
 }
 
-/// Create a temporary directory for decompression work in /tmp
-/// Returns a TempDir that will be automatically cleaned up when dropped
-pub fn create_decompress_temp_dir() -> JcResult<TempDir> {
-    TempDir::new_in("/tmp")
-        .map_err(|e| JcError::TempDirFailed(format!("Failed to create temp directory: {}", e)))
+/// Create a temporary directory for decompression work in the system temp dir
+/// Returns a guard that removes the directory when dropped or on interruption
+pub fn create_decompress_temp_dir() -> JcResult<CleanupGuard> {
+    let path = create_temp_dir_in(&env::temp_dir(), DECOMPRESS_DIR_PREFIX)
+        .map_err(|e| JcError::TempDirFailed(format!("Failed to create temp directory: {}", e)))?;
+
+    Ok(CleanupGuard::new(path))
+}
+
+/// Copy a file or directory to its final destination atomically
+///
+/// The copy is written under a temporary name next to `dest` and renamed into
+/// place once complete. Directories are merged into an existing destination
+/// directory instead.
+pub fn place_output(source: &Path, dest: &Path, fsync: bool) -> JcResult<PathBuf> {
+    if source.is_dir() && dest.is_dir() {
+        copy_recursive(source, dest)?;
+        return Ok(dest.to_path_buf());
+    }
+
+    let output = AtomicOutput::new(dest);
+    copy_recursive(source, output.temp_path())?;
+    output.commit(fsync)
 }
 
 /// Copy a file to a target directory, preserving the filename
@@ -152,7 +290,20 @@ pub fn copy_to_dir(source: &Path, target_dir: &Path) -> JcResult<PathBuf> {
         return Ok(dest_path);
     }
 
-    fs::copy(source, &dest_path).map_err(|e| JcError::Io(e))?;
+    fs::copy(source, &dest_path)?;
 
     Ok(dest_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_temp_dirs_are_private() {
+        let guard = create_decompress_temp_dir().unwrap();
+        let mode = fs::metadata(guard.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
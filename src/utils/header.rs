@@ -0,0 +1,356 @@
+use chrono::DateTime;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::CompressionFormat;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const XZ_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const XZ_FOOTER_MAGIC: &[u8] = b"YZ";
+
+/// Size of the xz stream header and footer
+const XZ_STREAM_EDGE: u64 = 12;
+
+/// LZMA2 filter id in xz block headers
+const XZ_FILTER_LZMA2: u64 = 0x21;
+
+/// Dictionary sizes of the xz presets, with the presets using each
+const XZ_PRESET_DICTIONARIES: [(u32, &str); 8] = [
+    (256 << 10, "0"),
+    (1 << 20, "1"),
+    (2 << 20, "2"),
+    (4 << 20, "3-4"),
+    (8 << 20, "5-6"),
+    (16 << 20, "7"),
+    (32 << 20, "8"),
+    (64 << 20, "9"),
+];
+
+/// What the header of a gzip file records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GzipHeader {
+    /// Original file name, if stored
+    pub name: Option<String>,
+
+    /// Modification time of the original file in RFC 3339 format, if stored
+    pub mtime: Option<String>,
+
+    /// Operating system that wrote the file
+    pub os: &'static str,
+
+    /// Compression level, as far as the extra flags tell: `1`, `9` or `2-8`
+    pub level: &'static str,
+}
+
+/// What the headers and index of an xz file record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XzHeader {
+    /// Integrity check of the blocks, e.g. `CRC64`
+    pub check: String,
+
+    /// Number of blocks in every stream of the file
+    pub blocks: u64,
+
+    /// LZMA2 dictionary size of the first block, in bytes
+    pub dictionary: Option<u32>,
+
+    /// Presets using that dictionary size, e.g. `5-6`
+    pub preset: Option<&'static str>,
+}
+
+fn invalid(path: &Path, format: &str) -> JcError {
+    JcError::Other(format!("{} is not a valid {} file", path.display(), format))
+}
+
+/// Read up to `len` bytes from `offset`
+fn read_at(file: &mut File, offset: u64, len: usize) -> JcResult<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Name of the operating system code of a gzip header
+fn gzip_os(code: u8) -> &'static str {
+    match code {
+        0 => "FAT",
+        1 => "Amiga",
+        2 => "VMS",
+        3 => "Unix",
+        4 => "VM/CMS",
+        5 => "Atari TOS",
+        6 => "HPFS",
+        7 => "Macintosh",
+        8 => "Z-System",
+        9 => "CP/M",
+        10 => "TOPS-20",
+        11 => "NTFS",
+        12 => "QDOS",
+        13 => "Acorn RISCOS",
+        _ => "unknown",
+    }
+}
+
+/// Parse the header at the start of a gzip file
+fn parse_gzip_header(data: &[u8]) -> Option<GzipHeader> {
+    if data.len() < 10 || data[..2] != GZIP_MAGIC || data[2] != 8 {
+        return None;
+    }
+
+    let flags = data[3];
+    let mtime = u32::from_le_bytes(data[4..8].try_into().ok()?);
+    let level = match data[8] {
+        2 => "9",
+        4 => "1",
+        _ => "2-8",
+    };
+
+    let mut pos = 10;
+    // FEXTRA: a length-prefixed extra field
+    if flags & 0x04 != 0 {
+        let len = u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        pos += 2 + len;
+    }
+    // FNAME: a NUL-terminated original file name
+    let name = if flags & 0x08 != 0 {
+        let rest = data.get(pos..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).to_string())
+    } else {
+        None
+    };
+
+    Some(GzipHeader {
+        name,
+        mtime: (mtime != 0)
+            .then(|| DateTime::from_timestamp(mtime as i64, 0))
+            .flatten()
+            .map(|t| t.to_rfc3339()),
+        os: gzip_os(data[9]),
+        level,
+    })
+}
+
+/// Tell gzip, bzip2 and xz data apart by the magic bytes it starts with
+pub fn sniff_format(data: &[u8]) -> Option<CompressionFormat> {
+    if data.starts_with(&GZIP_MAGIC) {
+        Some(CompressionFormat::Gzip)
+    } else if data.starts_with(b"BZh") {
+        Some(CompressionFormat::Bzip2)
+    } else if data.starts_with(&XZ_MAGIC) {
+        Some(CompressionFormat::Xz)
+    } else {
+        None
+    }
+}
+
+/// Read the header of a gzip file
+pub fn gzip_header(path: &Path) -> JcResult<GzipHeader> {
+    let mut file = File::open(path)?;
+    // Enough for the fixed fields and any reasonable extra field and name
+    let data = read_at(&mut file, 0, 64 * 1024)?;
+    parse_gzip_header(&data).ok_or_else(|| invalid(path, "gzip"))
+}
+
+/// Read the block size of a bzip2 file, in bytes
+///
+/// `bzip2 -N` uses blocks of N x 100 kB, so this is also the level.
+pub fn bzip2_block_size(path: &Path) -> JcResult<u32> {
+    let mut file = File::open(path)?;
+    let data = read_at(&mut file, 0, 4)?;
+    match data.as_slice() {
+        [b'B', b'Z', b'h', digit @ b'1'..=b'9'] => Ok((digit - b'0') as u32 * 100_000),
+        _ => Err(invalid(path, "bzip2")),
+    }
+}
+
+/// Decode a variable-length integer of the xz format, returning it and its length
+fn xz_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(9).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Name of the check type in xz stream flags
+fn xz_check(flags: u8) -> String {
+    match flags & 0x0f {
+        0x00 => "None".to_string(),
+        0x01 => "CRC32".to_string(),
+        0x04 => "CRC64".to_string(),
+        0x0a => "SHA-256".to_string(),
+        other => format!("unknown ({})", other),
+    }
+}
+
+/// Find the LZMA2 dictionary size in an xz block header
+fn xz_dictionary(header: &[u8]) -> Option<u32> {
+    let size = (*header.first()? as usize + 1) * 4;
+    let header = header.get(..size)?;
+    let flags = header[1];
+    let filters = (flags & 0x03) as usize + 1;
+
+    let mut pos = 2;
+    // Optional compressed and uncompressed sizes
+    for present in [flags & 0x40 != 0, flags & 0x80 != 0] {
+        if present {
+            pos += xz_varint(header.get(pos..)?)?.1;
+        }
+    }
+
+    for _ in 0..filters {
+        let (id, len) = xz_varint(header.get(pos..)?)?;
+        pos += len;
+        let (props_size, len) = xz_varint(header.get(pos..)?)?;
+        pos += len;
+        if id == XZ_FILTER_LZMA2 && props_size == 1 {
+            let bits = (header.get(pos)? & 0x3f) as u32;
+            if bits > 40 {
+                return None;
+            }
+            return Some(if bits == 40 {
+                u32::MAX
+            } else {
+                (2 | (bits & 1)) << (bits / 2 + 11)
+            });
+        }
+        pos += props_size as usize;
+    }
+
+    None
+}
+
+/// Count the blocks of the stream whose footer ends at `end`, returning the
+/// count and where the stream starts
+fn xz_stream_blocks(file: &mut File, end: u64) -> Option<(u64, u64)> {
+    let footer_start = end.checked_sub(XZ_STREAM_EDGE)?;
+    let footer = read_at(file, footer_start, XZ_STREAM_EDGE as usize).ok()?;
+    if footer.len() != XZ_STREAM_EDGE as usize || &footer[10..] != XZ_FOOTER_MAGIC {
+        return None;
+    }
+
+    let backward = (u32::from_le_bytes(footer[4..8].try_into().ok()?) as u64 + 1) * 4;
+    let index_start = footer_start.checked_sub(backward)?;
+    let index = read_at(file, index_start, backward as usize).ok()?;
+    if index.first() != Some(&0) {
+        return None;
+    }
+
+    let (records, mut pos) = xz_varint(index.get(1..)?)?;
+    pos += 1;
+    let mut blocks_size = 0u64;
+    for _ in 0..records {
+        let (unpadded, len) = xz_varint(index.get(pos..)?)?;
+        pos += len;
+        pos += xz_varint(index.get(pos..)?)?.1;
+        blocks_size += unpadded.div_ceil(4) * 4;
+    }
+
+    let start = index_start
+        .checked_sub(blocks_size)?
+        .checked_sub(XZ_STREAM_EDGE)?;
+    Some((records, start))
+}
+
+/// Read the check type, block count and dictionary size of an xz file
+pub fn xz_header(path: &Path) -> JcResult<XzHeader> {
+    let mut file = File::open(path)?;
+    let header = read_at(&mut file, 0, XZ_STREAM_EDGE as usize)?;
+    if header.len() != XZ_STREAM_EDGE as usize || header[..6] != XZ_MAGIC {
+        return Err(invalid(path, "xz"));
+    }
+
+    // Walk the streams from the end, skipping the zero padding between them
+    let mut end = file.metadata()?.len();
+    let mut blocks = 0;
+    while end > 0 {
+        let tail = read_at(&mut file, end.saturating_sub(4), 4)?;
+        if tail == [0, 0, 0, 0] {
+            end -= 4;
+            continue;
+        }
+        let (count, start) = xz_stream_blocks(&mut file, end).ok_or_else(|| invalid(path, "xz"))?;
+        blocks += count;
+        end = start;
+    }
+
+    let block_header = read_at(&mut file, XZ_STREAM_EDGE, 1024)?;
+    let dictionary = xz_dictionary(&block_header);
+    Ok(XzHeader {
+        check: xz_check(header[7]),
+        blocks,
+        dictionary,
+        preset: dictionary.and_then(|size| {
+            XZ_PRESET_DICTIONARIES
+                .iter()
+                .find(|(preset_size, _)| *preset_size == size)
+                .map(|(_, preset)| *preset)
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_gzip_header() {
+        let mut data = vec![0x1f, 0x8b, 8, 0x08, 0x00, 0xe1, 0xf5, 0x05, 2, 3];
+        data.extend_from_slice(b"notes.txt\0");
+        let header = parse_gzip_header(&data).unwrap();
+        assert_eq!(header.name.as_deref(), Some("notes.txt"));
+        assert_eq!(header.mtime.as_deref(), Some("1973-03-03T09:46:40+00:00"));
+        assert_eq!(header.os, "Unix");
+        assert_eq!(header.level, "9");
+
+        // No name and no time, written by a stream
+        let data = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+        let header = parse_gzip_header(&data).unwrap();
+        assert_eq!(header.name, None);
+        assert_eq!(header.mtime, None);
+        assert_eq!(header.level, "2-8");
+        assert!(parse_gzip_header(b"BZh91AY&SY").is_none());
+    }
+
+    #[test]
+    fn test_xz_varint_and_dictionary() {
+        assert_eq!(xz_varint(&[0x05]), Some((5, 1)));
+        assert_eq!(xz_varint(&[0x80, 0x01]), Some((128, 2)));
+        assert_eq!(xz_varint(&[0x80]), None);
+
+        // One LZMA2 filter with an 8 MiB dictionary (props 0x16)
+        let header = [0x02, 0x00, 0x21, 0x01, 0x16, 0x00, 0x00, 0x00, 0, 0, 0, 0];
+        assert_eq!(xz_dictionary(&header), Some(8 << 20));
+    }
+
+    #[test]
+    fn test_xz_header_of_real_files() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("data.txt");
+        fs::write(&input, b"some text to compress\n".repeat(100)).unwrap();
+
+        for (level, preset) in [("-1", "1"), ("-6", "5-6"), ("-9", "9")] {
+            let output = Command::new("xz")
+                .args([level, "-c"])
+                .arg(&input)
+                .output()
+                .unwrap();
+            let archive = dir.path().join("data.txt.xz");
+            fs::write(&archive, &output.stdout).unwrap();
+
+            let header = xz_header(&archive).unwrap();
+            assert_eq!(header.check, "CRC64");
+            assert_eq!(header.blocks, 1);
+            assert_eq!(header.preset, Some(preset));
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ChecksumAlgorithm;
+use crate::utils::checksum::HashingWriter;
+use crate::utils::logger::{debug, error, info};
+
+/// Name of the manifest stored at the top of a collection
+pub const MANIFEST_NAME: &str = "MANIFEST.json";
+
+/// One regular file in a collection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path relative to the manifest, with `/` separators
+    pub path: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Permission bits in octal, e.g. `0644`
+    pub mode: String,
+
+    /// SHA-256 of the content
+    pub sha256: String,
+}
+
+/// Description of a collection archive and its members
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub jcz_version: String,
+
+    /// Creation time in RFC 3339 format
+    pub created: String,
+
+    pub host: String,
+
+    /// Input paths as given when the collection was created
+    pub inputs: Vec<String>,
+
+    pub members: Vec<ManifestEntry>,
+}
+
+/// Problems found when checking extracted files against a manifest
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl ManifestReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Hash a file's content with SHA-256
+fn hash_member(path: &Path) -> JcResult<String> {
+    let mut writer = HashingWriter::new(io::sink(), Some(ChecksumAlgorithm::Sha256));
+    io::copy(&mut File::open(path)?, &mut writer)?;
+
+    let (_, digest) = writer.finish();
+    Ok(digest.unwrap_or_default())
+}
+
+/// Collect entries for every regular file below `dir`
+fn collect_entries(root: &Path, dir: &Path, entries: &mut Vec<ManifestEntry>) -> JcResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+
+        if metadata.is_dir() {
+            collect_entries(root, &path, entries)?;
+        } else if metadata.is_file() {
+            let relative = path.strip_prefix(root).map_err(|_| {
+                JcError::Other(format!("{} is outside the collection", path.display()))
+            })?;
+            if relative == Path::new(MANIFEST_NAME) {
+                continue;
+            }
+
+            entries.push(ManifestEntry {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                size: metadata.len(),
+                mode: format!("{:04o}", metadata.permissions().mode() & 0o7777),
+                sha256: hash_member(&path)?,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of this machine, or `unknown`
+pub fn host_name() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|h| h.trim().to_string())
+        .ok()
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Describe the files below `root`, which will become the archive contents
+///
+/// With a reproducible epoch the creation time is taken from it and the
+/// host is left out, so the manifest only depends on the files.
+pub fn build_manifest(
+    root: &Path,
+    inputs: &[PathBuf],
+    reproducible: Option<i64>,
+) -> JcResult<Manifest> {
+    let mut members = Vec::new();
+    collect_entries(root, root, &mut members)?;
+    members.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let (created, host) = match reproducible {
+        Some(epoch) => {
+            let created = DateTime::from_timestamp(epoch, 0)
+                .ok_or_else(|| JcError::Other(format!("Invalid timestamp: {}", epoch)))?;
+            (created.to_rfc3339(), "unknown".to_string())
+        }
+        None => (Local::now().to_rfc3339(), host_name()),
+    };
+
+    Ok(Manifest {
+        jcz_version: env!("CARGO_PKG_VERSION").to_string(),
+        created,
+        host,
+        inputs: inputs.iter().map(|p| p.display().to_string()).collect(),
+        members,
+    })
+}
+
+/// Write the manifest for the files below `root` into `root`
+pub fn write_manifest(
+    root: &Path,
+    inputs: &[PathBuf],
+    reproducible: Option<i64>,
+) -> JcResult<PathBuf> {
+    let manifest = build_manifest(root, inputs, reproducible)?;
+    let path = root.join(MANIFEST_NAME);
+
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| JcError::Other(format!("Failed to write manifest: {}", e)))?;
+    fs::write(&path, json + "\n")?;
+
+    debug!(
+        "Wrote manifest with {} members to {}",
+        manifest.members.len(),
+        path.display()
+    );
+    Ok(path)
+}
+
+/// Read the manifest in `root`, if there is one written by jcz
+pub fn read_manifest(root: &Path) -> JcResult<Option<Manifest>> {
+    let path = root.join(MANIFEST_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    match serde_json::from_str(&fs::read_to_string(&path)?) {
+        Ok(manifest) => Ok(Some(manifest)),
+        Err(e) => {
+            debug!("Ignoring {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Compare the files below `root` with the manifest's entries
+pub fn check_manifest(root: &Path, manifest: &Manifest) -> JcResult<ManifestReport> {
+    let mut report = ManifestReport::default();
+
+    for entry in &manifest.members {
+        let path = root.join(&entry.path);
+        if !path.is_file() {
+            report.missing.push(entry.path.clone());
+        } else if fs::metadata(&path)?.len() != entry.size || hash_member(&path)? != entry.sha256 {
+            report.modified.push(entry.path.clone());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check extracted files against the manifest in `root`, if present
+///
+/// Every missing or modified member is reported before failing.
+pub fn verify_manifest(root: &Path) -> JcResult<()> {
+    let Some(manifest) = read_manifest(root)? else {
+        return Ok(());
+    };
+
+    let report = check_manifest(root, &manifest)?;
+    for path in &report.missing {
+        error!("Missing from archive: {}", path);
+    }
+    for path in &report.modified {
+        error!("Modified in archive: {}", path);
+    }
+
+    if !report.is_ok() {
+        return Err(JcError::VerificationFailed {
+            path: root.join(MANIFEST_NAME),
+            reason: format!(
+                "{} missing and {} modified member(s)",
+                report.missing.len(),
+                report.modified.len()
+            ),
+        });
+    }
+
+    info!("All {} members match the manifest", manifest.members.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), b"beta").unwrap();
+
+        write_manifest(dir.path(), &[PathBuf::from("a.txt")], None).unwrap();
+        let manifest = read_manifest(dir.path()).unwrap().unwrap();
+        let paths: Vec<&str> = manifest.members.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "sub/b.txt"]);
+        assert_eq!(manifest.members[0].size, 5);
+        assert!(check_manifest(dir.path(), &manifest).unwrap().is_ok());
+
+        fs::write(dir.path().join("a.txt"), b"ALPHA").unwrap();
+        fs::remove_file(dir.path().join("sub/b.txt")).unwrap();
+        let report = check_manifest(dir.path(), &manifest).unwrap();
+        assert_eq!(report.modified, vec!["a.txt"]);
+        assert_eq!(report.missing, vec!["sub/b.txt"]);
+    }
+
+    #[test]
+    fn test_foreign_manifest_ignored() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(MANIFEST_NAME), b"{\"name\": \"other\"}").unwrap();
+        assert!(read_manifest(dir.path()).unwrap().is_none());
+        assert!(verify_manifest(dir.path()).is_ok());
+    }
+}
@@ -1,13 +1,31 @@
+pub mod atomic;
+pub mod checksum;
+pub mod conflict;
+pub mod crypto;
+pub mod dedup;
+pub mod exclude;
 pub mod fs;
+pub mod header;
 pub mod logger;
+pub mod manifest;
+pub mod probe;
 pub mod prompt;
+pub mod retention;
+pub mod settings;
+pub mod snapshot;
+pub mod split;
+pub mod stream;
+pub mod template;
 pub mod timestamp;
 pub mod validation;
 
+pub use atomic::{install_signal_handler, AtomicOutput, CleanupGuard};
+pub use conflict::{resolve_conflict, resolve_dir_conflict, Reservation};
 pub use fs::{
-    copy_recursive, copy_to_dir, create_decompress_temp_dir, create_temp_dir,
-    generate_output_filename, move_file, move_file_if_needed, remove_file_silent,
+    copy_excluding, copy_to_dir, create_decompress_temp_dir, create_temp_dir, create_temp_dir_in,
+    generate_output_filename, generate_output_filename_from, move_file, move_file_if_needed,
+    place_output, remove_input, resolve_output_path,
 };
-pub use logger::{debug, error, info, init_logger};
-pub use prompt::prompt_overwrite;
-pub use validation::{validate_input_files, validate_move_to};
+pub use logger::{debug, error, info, init_logger, warn};
+pub use stream::OutputWriter;
+pub use validation::{check_move_to, read_file_list, validate_input_files, validate_move_to};
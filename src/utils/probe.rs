@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::compressors::detect_format;
+use crate::core::config::SkipPolicy;
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{CompressionFormat, ENCRYPTED_EXTENSION};
+use crate::utils::header::sniff_format;
+
+/// Bytes read from the start of an input to look for magic bytes and
+/// measure its entropy
+const PROBE_SIZE: u64 = 64 * 1024;
+
+/// Entropy in bits per byte above which a sample counts as incompressible;
+/// compressed and encrypted data come close to 8
+const ENTROPY_LIMIT: f64 = 7.9;
+
+/// Extensions of formats that are compressed already, besides the ones jcz
+/// writes: archives, documents stored as ZIP archives, images, audio and video
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "zip", "7z", "rar", "zst", "lz4", "lzma", "lz", "tgz", "tbz2", "txz", "jar", "apk", "whl",
+    "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub", "jpg", "jpeg", "png", "gif", "webp",
+    "heic", "avif", "mp3", "m4a", "aac", "ogg", "opus", "flac", "mp4", "m4v", "mkv", "webm", "mov",
+    "avi",
+];
+
+/// Magic bytes of formats that are compressed already, besides the ones jcz writes
+const COMPRESSED_MAGIC: &[(&str, &[u8])] = &[
+    ("ZIP", b"PK\x03\x04"),
+    ("7z", b"7z\xbc\xaf\x27\x1c"),
+    ("RAR", b"Rar!\x1a\x07"),
+    ("zstd", b"\x28\xb5\x2f\xfd"),
+    ("LZ4", b"\x04\x22\x4d\x18"),
+    ("PNG", b"\x89PNG\r\n\x1a\n"),
+    ("JPEG", b"\xff\xd8\xff"),
+    ("GIF", b"GIF8"),
+    ("Ogg", b"OggS"),
+    ("FLAC", b"fLaC"),
+    ("age", b"age-encryption.org/"),
+];
+
+/// Shannon entropy of `data` in bits per byte
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let total = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Why an input is not worth compressing, or `None` if it is
+///
+/// Inputs are recognised by extension first, then by the magic bytes at the
+/// start of their content. `SkipPolicy::Incompressible` also measures the
+/// entropy of the first bytes. Directories are always worth compressing.
+pub fn skip_reason(path: &Path, policy: SkipPolicy) -> JcResult<Option<String>> {
+    if policy == SkipPolicy::Never || !path.is_file() {
+        return Ok(None);
+    }
+
+    let compressed_extension = detect_format(path).is_some_and(|f| f != CompressionFormat::Tar)
+        || path.extension().is_some_and(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            ext == ENCRYPTED_EXTENSION || COMPRESSED_EXTENSIONS.contains(&ext.as_str())
+        });
+    if compressed_extension {
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        return Ok(Some(format!(".{} files are compressed already", ext)));
+    }
+
+    let mut sample = Vec::new();
+    File::open(path)?
+        .take(PROBE_SIZE)
+        .read_to_end(&mut sample)?;
+    if let Some(format) = sniff_format(&sample) {
+        return Ok(Some(format!("already {} data", format.name())));
+    }
+    if let Some((name, _)) = COMPRESSED_MAGIC
+        .iter()
+        .find(|(_, magic)| sample.starts_with(magic))
+    {
+        return Ok(Some(format!("already {} data", name)));
+    }
+
+    if policy == SkipPolicy::Incompressible && !sample.is_empty() {
+        let bits = entropy(&sample);
+        if bits > ENTROPY_LIMIT {
+            return Ok(Some(format!(
+                "looks incompressible at {:.2} bits per byte",
+                bits
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Fail with `JcError::Incompressible` if an input is not worth compressing
+pub fn check_compressible(path: &Path, policy: SkipPolicy) -> JcResult<()> {
+    match skip_reason(path, policy)? {
+        Some(reason) => Err(JcError::Incompressible {
+            path: path.to_path_buf(),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+    }
+
+    #[test]
+    fn test_skip_reason() {
+        let dir = TempDir::new().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+
+        let text = write("notes.txt", b"plain text, plain text, plain text");
+        let photo = write("photo.JPG", b"not even a JPEG");
+        let gzip = write("data.bin", &[0x1f, 0x8b, 8, 0]);
+        let zip = write("bundle.dat", b"PK\x03\x04rest");
+        let tar = write("files.tar", b"plain text");
+        let noise: Vec<u8> = (0..PROBE_SIZE).map(|i| (i * 7919 % 251) as u8).collect();
+        let noisy = write("noise.raw", &noise);
+
+        let reason = |path: &Path, policy| skip_reason(path, policy).unwrap();
+        assert_eq!(reason(&text, SkipPolicy::Incompressible), None);
+        assert_eq!(reason(&tar, SkipPolicy::Compressed), None);
+        assert_eq!(
+            reason(&photo, SkipPolicy::Compressed).as_deref(),
+            Some(".JPG files are compressed already")
+        );
+        assert_eq!(
+            reason(&gzip, SkipPolicy::Compressed).as_deref(),
+            Some("already gzip data")
+        );
+        assert_eq!(
+            reason(&zip, SkipPolicy::Compressed).as_deref(),
+            Some("already ZIP data")
+        );
+        assert_eq!(reason(&noisy, SkipPolicy::Compressed), None);
+        assert!(reason(&noisy, SkipPolicy::Incompressible)
+            .unwrap()
+            .starts_with("looks incompressible"));
+        assert_eq!(reason(&photo, SkipPolicy::Never), None);
+        assert_eq!(reason(dir.path(), SkipPolicy::Incompressible), None);
+    }
+}
@@ -1,22 +1,44 @@
 use std::io::{self, Write};
 use std::path::Path;
+use std::sync::Mutex;
 
 use crate::core::error::{JcError, JcResult};
 
+/// Serializes prompts from parallel workers
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
 /// Prompt user whether to overwrite an existing file
 /// Returns true if user confirms, false otherwise
 pub fn prompt_overwrite(file_path: &Path) -> JcResult<bool> {
+    let _lock = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
     print!(
         "File '{}' already exists. Overwrite? (y/n): ",
         file_path.display()
     );
-    io::stdout().flush().map_err(|e| JcError::Io(e))?;
+    io::stdout().flush()?;
 
     let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .map_err(|e| JcError::Io(e))?;
+    io::stdin().read_line(&mut input)?;
 
     let response = input.trim().to_lowercase();
     Ok(response == "y" || response == "yes")
 }
+
+/// Prompt for a passphrase without echoing it
+///
+/// With `confirm`, the passphrase must be entered twice.
+pub fn prompt_passphrase(confirm: bool) -> JcResult<String> {
+    let _lock = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(JcError::Other("Empty passphrase".to_string()));
+    }
+
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+        return Err(JcError::Other("Passphrases do not match".to_string()));
+    }
+
+    Ok(passphrase)
+}
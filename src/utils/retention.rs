@@ -0,0 +1,238 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeDelta, Utc};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::core::config::{RetentionPolicy, TimestampOption};
+use crate::utils::timestamp::{format_timestamp, parse_timestamp};
+
+/// Calendar periods of the `keep_daily`, `keep_weekly` and `keep_monthly` rules
+#[derive(Debug, Clone, Copy)]
+enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Key shared by all dates in the same period
+    fn key(self, date: NaiveDate) -> (i32, u32) {
+        match self {
+            Period::Day => (date.year(), date.ordinal()),
+            Period::Week => (date.iso_week().year(), date.iso_week().week()),
+            Period::Month => (date.year(), date.month()),
+        }
+    }
+}
+
+/// Parse an age such as `30d`: a number followed by s, m, h, d or w
+pub fn parse_max_age(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let seconds = match unit.to_ascii_lowercase() {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    let value: u64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    value.checked_mul(seconds).map(Duration::from_secs)
+}
+
+/// Check that names stamped with `pattern` can be dated again for retention
+pub fn validate_stamp_pattern(pattern: &str) -> Result<(), String> {
+    let option = TimestampOption::Custom(pattern.to_string());
+    let sample = format_timestamp(&option, Utc::now(), true).map_err(|e| e.to_string())?;
+    if parse_timestamp(&sample, pattern, true).is_none() {
+        return Err(format!(
+            "Retention needs a timestamp format with a full date that can be read back: {}",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+/// Decide which archives the policy removes, given the time of each one
+///
+/// Days, weeks and months are counted in UTC or local time, like the names.
+pub fn expired(
+    times: &[DateTime<Utc>],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+    utc: bool,
+) -> Vec<bool> {
+    let mut newest_first: Vec<usize> = (0..times.len()).collect();
+    newest_first.sort_by(|&a, &b| times[b].cmp(&times[a]));
+
+    let date = |time: DateTime<Utc>| {
+        if utc {
+            time.date_naive()
+        } else {
+            time.with_timezone(&Local).date_naive()
+        }
+    };
+
+    let has_rules = policy.keep_last.is_some()
+        || policy.keep_daily.is_some()
+        || policy.keep_weekly.is_some()
+        || policy.keep_monthly.is_some();
+    let mut kept = vec![!has_rules; times.len()];
+
+    if let Some(count) = policy.keep_last {
+        for &i in newest_first.iter().take(count) {
+            kept[i] = true;
+        }
+    }
+
+    for (count, period) in [
+        (policy.keep_daily, Period::Day),
+        (policy.keep_weekly, Period::Week),
+        (policy.keep_monthly, Period::Month),
+    ] {
+        let Some(count) = count else { continue };
+
+        // The newest archive of each period stands for it
+        let mut periods = HashSet::new();
+        for &i in &newest_first {
+            if periods.len() == count {
+                break;
+            }
+            if periods.insert(period.key(date(times[i]))) {
+                kept[i] = true;
+            }
+        }
+    }
+
+    let cutoff = policy
+        .max_age
+        .and_then(|age| TimeDelta::from_std(age).ok())
+        .and_then(|age| now.checked_sub_signed(age));
+    if let Some(cutoff) = cutoff {
+        for (i, time) in times.iter().enumerate() {
+            if *time < cutoff {
+                kept[i] = false;
+            }
+        }
+    }
+
+    kept.into_iter().map(|k| !k).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_max_age() {
+        assert_eq!(parse_max_age("30d"), Some(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_max_age("12h"), Some(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_max_age("2W"), Some(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_max_age("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_max_age("30"), None);
+        assert_eq!(parse_max_age("d"), None);
+        assert_eq!(parse_max_age("-1d"), None);
+    }
+
+    #[test]
+    fn test_validate_stamp_pattern() {
+        assert!(validate_stamp_pattern("%Y%m%d").is_ok());
+        assert!(validate_stamp_pattern("%Y-%m-%dT%H%M%SZ").is_ok());
+        assert!(validate_stamp_pattern("%H%M").is_err());
+        assert!(validate_stamp_pattern("%Y%m").is_err());
+    }
+
+    #[test]
+    fn test_keep_last() {
+        let times = [
+            at("2025-01-03T00:00:00Z"),
+            at("2025-01-01T00:00:00Z"),
+            at("2025-01-04T00:00:00Z"),
+            at("2025-01-02T00:00:00Z"),
+        ];
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&times, &policy, times[2], true),
+            vec![false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_keep_periods() {
+        let times = [
+            at("2025-03-10T18:00:00Z"),
+            at("2025-03-10T06:00:00Z"),
+            at("2025-03-09T12:00:00Z"),
+            at("2025-03-02T12:00:00Z"),
+            at("2025-02-20T12:00:00Z"),
+            at("2025-01-15T12:00:00Z"),
+        ];
+        let now = times[0];
+
+        let daily = RetentionPolicy {
+            keep_daily: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&times, &daily, now, true),
+            vec![false, true, false, true, true, true]
+        );
+
+        // 2025-03-09 and 2025-03-02 are Sundays ending ISO weeks
+        let weekly = RetentionPolicy {
+            keep_weekly: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&times, &weekly, now, true),
+            vec![false, true, false, false, true, true]
+        );
+
+        let monthly = RetentionPolicy {
+            keep_monthly: Some(12),
+            keep_last: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&times, &monthly, now, true),
+            vec![false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_max_age() {
+        let times = [
+            at("2025-03-10T00:00:00Z"),
+            at("2025-03-01T00:00:00Z"),
+            at("2025-01-01T00:00:00Z"),
+        ];
+        let now = at("2025-03-10T12:00:00Z");
+
+        let max_age = RetentionPolicy {
+            max_age: parse_max_age("30d"),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired(&times, &max_age, now, true),
+            vec![false, false, true]
+        );
+
+        // Age wins over the keep rules
+        let both = RetentionPolicy {
+            keep_last: Some(3),
+            max_age: parse_max_age("1w"),
+            ..Default::default()
+        };
+        assert_eq!(expired(&times, &both, now, true), vec![false, true, true]);
+    }
+}
@@ -0,0 +1,363 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use crate::core::error::{JcError, JcResult};
+use crate::utils::logger::debug;
+
+/// Name of the project configuration file, looked up from the current directory upwards
+pub const PROJECT_FILE: &str = ".jcz.toml";
+
+/// Environment variable naming the user configuration file
+pub const CONFIG_ENV: &str = "JCZ_CONFIG";
+
+/// Environment variable selecting a profile when --profile is not given
+pub const PROFILE_ENV: &str = "JCZ_PROFILE";
+
+/// Table of a configuration file holding the named profiles
+const PROFILES_KEY: &str = "profiles";
+
+/// Every key of [`Settings`]
+const SETTING_KEYS: &[&str] = &[
+    "command",
+    "level",
+    "auto-goal",
+    "time-budget",
+    "force-compress",
+    "skip-incompressible",
+    "exclude",
+    "timestamp",
+    "timestamp-format",
+    "utc",
+    "timestamp-position",
+    "timestamp-from-mtime",
+    "output-template",
+    "move-to",
+    "on-conflict",
+    "keep-last",
+    "keep-daily",
+    "keep-weekly",
+    "keep-monthly",
+    "max-age",
+    "split-size",
+    "checksum",
+    "verify-checksum",
+    "reproducible",
+    "manifest",
+    "recipient",
+    "identity",
+    "fsync",
+];
+
+/// Keys that are paths, resolved against the directory of the file setting them
+const PATH_KEYS: [&str; 2] = ["move-to", "identity"];
+
+/// Keys holding lists, which only configuration files can set
+const LIST_KEYS: [&str; 3] = ["exclude", "recipient", "identity"];
+
+/// Defaults for command-line options, read from configuration files and the environment
+///
+/// Keys are the long option names, e.g. `move-to = "/backups"` for `-C`.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    pub command: Option<String>,
+    pub level: Option<u8>,
+    pub auto_goal: Option<String>,
+    pub time_budget: Option<String>,
+    pub force_compress: Option<bool>,
+    pub skip_incompressible: Option<bool>,
+    pub exclude: Option<Vec<String>>,
+    pub timestamp: Option<u8>,
+    pub timestamp_format: Option<String>,
+    pub utc: Option<bool>,
+    pub timestamp_position: Option<String>,
+    pub timestamp_from_mtime: Option<bool>,
+    pub output_template: Option<String>,
+    pub move_to: Option<PathBuf>,
+    pub on_conflict: Option<String>,
+    pub keep_last: Option<usize>,
+    pub keep_daily: Option<usize>,
+    pub keep_weekly: Option<usize>,
+    pub keep_monthly: Option<usize>,
+    pub max_age: Option<String>,
+    pub split_size: Option<String>,
+    pub checksum: Option<String>,
+    pub verify_checksum: Option<bool>,
+    pub reproducible: Option<bool>,
+    pub manifest: Option<bool>,
+    pub recipient: Option<Vec<String>>,
+    pub identity: Option<Vec<PathBuf>>,
+    pub fsync: Option<bool>,
+}
+
+/// Settings of one configuration file: its defaults and its profiles
+#[derive(Debug)]
+struct SettingsFile {
+    path: PathBuf,
+    defaults: Table,
+    profiles: Table,
+}
+
+/// Check a layer of settings, naming where it came from on error
+fn check_layer(table: &Table, source: &str) -> JcResult<()> {
+    Value::Table(table.clone())
+        .try_into::<Settings>()
+        .map(|_| ())
+        .map_err(|e| JcError::Other(format!("Invalid settings in {}: {}", source, e)))
+}
+
+/// Resolve relative paths of a layer against the directory of its file
+fn resolve_paths(table: &mut Table, dir: &Path) {
+    for key in PATH_KEYS {
+        match table.get_mut(key) {
+            Some(Value::String(path)) => {
+                *path = dir.join(&*path).to_string_lossy().to_string();
+            }
+            Some(Value::Array(paths)) => {
+                for path in paths.iter_mut() {
+                    if let Value::String(path) = path {
+                        *path = dir.join(&*path).to_string_lossy().to_string();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SettingsFile {
+    /// Read a configuration file, checking its defaults and every profile
+    fn load(path: &Path) -> JcResult<Self> {
+        let source = path.display().to_string();
+        let mut defaults: Table = fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| JcError::Other(format!("Invalid settings in {}: {}", source, e)))?;
+
+        let profiles = match defaults.remove(PROFILES_KEY) {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(JcError::Other(format!(
+                    "Invalid settings in {}: [{}] must be a table of profiles",
+                    source, PROFILES_KEY
+                )))
+            }
+            None => Table::new(),
+        };
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        resolve_paths(&mut defaults, dir);
+        check_layer(&defaults, &source)?;
+
+        let mut file = Self {
+            path: path.to_path_buf(),
+            defaults,
+            profiles: Table::new(),
+        };
+        for (name, profile) in profiles {
+            let Value::Table(mut profile) = profile else {
+                return Err(JcError::Other(format!(
+                    "Invalid settings in {}: profile {} must be a table",
+                    source, name
+                )));
+            };
+            resolve_paths(&mut profile, dir);
+            check_layer(&profile, &format!("{} (profile {})", source, name))?;
+            file.profiles.insert(name, Value::Table(profile));
+        }
+
+        debug!("Loaded settings from {}", source);
+        Ok(file)
+    }
+
+    fn profile(&self, name: &str) -> Option<&Table> {
+        self.profiles.get(name).and_then(Value::as_table)
+    }
+}
+
+/// Path of the user configuration file: $JCZ_CONFIG, else jcz/config.toml in the
+/// XDG configuration directory
+fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|p| !p.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("jcz").join("config.toml"))
+}
+
+/// Nearest project configuration file in `dir` or one of its parents
+fn project_config_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Environment variable holding a setting, e.g. `JCZ_MOVE_TO` for `move-to`
+fn env_name(key: &str) -> String {
+    format!("JCZ_{}", key.replace('-', "_").to_uppercase())
+}
+
+/// Settings given as `JCZ_*` environment variables
+///
+/// Values are read as TOML scalars where they parse as one (`9`, `true`) and
+/// as strings otherwise. List settings are only read from files.
+fn env_layer(vars: impl Fn(&str) -> Option<String>) -> Table {
+    let mut table = Table::new();
+    for key in SETTING_KEYS {
+        if LIST_KEYS.contains(key) {
+            continue;
+        }
+        let Some(raw) = vars(&env_name(key)) else {
+            continue;
+        };
+        let value = format!("value = {}", raw)
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .filter(|v| !v.is_table() && !v.is_array())
+            .unwrap_or(Value::String(raw));
+        table.insert(key.to_string(), value);
+    }
+    table
+}
+
+/// Merge layers of settings, later layers replacing the keys they set
+fn merge(layers: Vec<Table>) -> JcResult<Settings> {
+    let mut merged = Table::new();
+    for layer in layers {
+        merged.extend(layer);
+    }
+    Value::Table(merged)
+        .try_into()
+        .map_err(|e| JcError::Other(format!("Invalid settings: {}", e)))
+}
+
+/// Load the settings that apply to a run from `dir`
+///
+/// From lowest to highest precedence: the defaults of the user file, the
+/// defaults of the project file, the profile of the user file, the profile
+/// of the project file and `JCZ_*` environment variables. Command-line
+/// options override all of them.
+pub fn load_settings(profile: Option<&str>, dir: &Path) -> JcResult<Settings> {
+    let mut files = Vec::new();
+    if let Some(path) = user_config_path() {
+        if path.is_file() {
+            files.push(SettingsFile::load(&path)?);
+        } else if env::var_os(CONFIG_ENV).is_some_and(|p| !p.is_empty()) {
+            return Err(JcError::FileNotFound(path));
+        }
+    }
+    if let Some(path) = project_config_path(dir) {
+        files.push(SettingsFile::load(&path)?);
+    }
+
+    let mut layers: Vec<Table> = files.iter().map(|f| f.defaults.clone()).collect();
+    if let Some(name) = profile {
+        let profiles: Vec<Table> = files
+            .iter()
+            .filter_map(|f| f.profile(name))
+            .cloned()
+            .collect();
+        if profiles.is_empty() {
+            let searched: Vec<String> =
+                files.iter().map(|f| f.path.display().to_string()).collect();
+            return Err(JcError::Other(format!(
+                "No profile {} in {}",
+                name,
+                if searched.is_empty() {
+                    "any configuration file".to_string()
+                } else {
+                    searched.join(" or ")
+                }
+            )));
+        }
+        layers.extend(profiles);
+    }
+
+    let from_env = env_layer(|name| env::var(name).ok().filter(|v| !v.is_empty()));
+    check_layer(&from_env, "the environment")?;
+    layers.push(from_env);
+
+    merge(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profiles_override_defaults() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+level = 4
+move-to = "archives"
+
+[profiles.backup]
+command = "txz"
+level = 9
+keep-daily = 7
+"#,
+        )
+        .unwrap();
+
+        let file = SettingsFile::load(&path).unwrap();
+        assert_eq!(
+            file.defaults["move-to"].as_str(),
+            Some(dir.path().join("archives").to_str().unwrap())
+        );
+
+        let base = merge(vec![file.defaults.clone()]).unwrap();
+        assert_eq!(base.level, Some(4));
+        assert_eq!(base.command, None);
+
+        let backup = merge(vec![
+            file.defaults.clone(),
+            file.profile("backup").unwrap().clone(),
+        ])
+        .unwrap();
+        assert_eq!(backup.level, Some(9));
+        assert_eq!(backup.command.as_deref(), Some("txz"));
+        assert_eq!(backup.keep_daily, Some(7));
+        assert_eq!(backup.move_to, Some(dir.path().join("archives")));
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_FILE);
+
+        fs::write(&path, "levle = 9\n").unwrap();
+        let err = SettingsFile::load(&path).unwrap_err().to_string();
+        assert!(err.contains("levle"), "{}", err);
+
+        fs::write(&path, "[profiles.fast]\nlevel = \"one\"\n").unwrap();
+        let err = SettingsFile::load(&path).unwrap_err().to_string();
+        assert!(err.contains("profile fast"), "{}", err);
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let vars = |name: &str| match name {
+            "JCZ_LEVEL" => Some("9".to_string()),
+            "JCZ_MOVE_TO" => Some("/backups".to_string()),
+            "JCZ_FSYNC" => Some("true".to_string()),
+            "JCZ_MAX_AGE" => Some("30d".to_string()),
+            _ => None,
+        };
+        let settings = merge(vec![env_layer(vars)]).unwrap();
+        assert_eq!(settings.level, Some(9));
+        assert_eq!(settings.move_to, Some(PathBuf::from("/backups")));
+        assert_eq!(settings.fsync, Some(true));
+        assert_eq!(settings.max_age.as_deref(), Some("30d"));
+    }
+}
@@ -0,0 +1,393 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path};
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ChecksumAlgorithm;
+use crate::utils::atomic::AtomicOutput;
+use crate::utils::checksum::{Hasher, HashingWriter};
+use crate::utils::logger::debug;
+
+/// Name of the marker stored at the top of incremental-mode archives
+pub const SNAPSHOT_MARKER: &str = ".jcz-snapshot.json";
+
+/// Number of hex digits in a snapshot id
+const SNAPSHOT_ID_LEN: usize = 16;
+
+/// Type of an entry in a snapshot
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// One file, directory or symbolic link below the backed-up directory
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    pub kind: EntryKind,
+
+    pub inode: u64,
+
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: i64,
+
+    pub size: u64,
+
+    /// SHA-256 of the content, or of the target for symbolic links
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sha256: String,
+}
+
+impl SnapshotEntry {
+    /// Whether the entry looks untouched, without reading its content
+    fn same_metadata(&self, other: &SnapshotEntry) -> bool {
+        self.kind == other.kind
+            && self.inode == other.inode
+            && self.mtime_ns == other.mtime_ns
+            && self.size == other.size
+    }
+}
+
+/// State of a directory tree at its last backup, kept between runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotState {
+    pub jcz_version: String,
+
+    /// Identifies the archive made from this state
+    pub id: String,
+
+    /// Creation time in RFC 3339 format
+    pub created: String,
+
+    /// Absolute path of the backed-up directory
+    pub root: String,
+
+    /// Entries by path relative to the root, with `/` separators
+    pub entries: BTreeMap<String, SnapshotEntry>,
+}
+
+/// How an archive relates to the backups before it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupKind {
+    /// Every entry of the directory
+    Full,
+
+    /// Entries changed since the previous backup of any kind
+    Incremental,
+
+    /// Entries changed since the full backup
+    Differential,
+}
+
+impl BackupKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackupKind::Full => "full",
+            BackupKind::Incremental => "incremental",
+            BackupKind::Differential => "differential",
+        }
+    }
+}
+
+/// Marker at the top of an incremental-mode archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMarker {
+    pub jcz_version: String,
+
+    pub kind: BackupKind,
+
+    /// Id of the snapshot this archive brings the directory to
+    pub id: String,
+
+    /// Id of the snapshot the changes were taken against
+    pub base: Option<String>,
+
+    /// Creation time in RFC 3339 format
+    pub created: String,
+
+    /// Paths removed since the base snapshot
+    pub deleted: Vec<String>,
+}
+
+/// Hash a file's content with SHA-256
+fn hash_content(path: &Path) -> JcResult<String> {
+    let mut writer = HashingWriter::new(io::sink(), Some(ChecksumAlgorithm::Sha256));
+    io::copy(&mut File::open(path)?, &mut writer)?;
+
+    let (_, digest) = writer.finish();
+    Ok(digest.unwrap_or_default())
+}
+
+/// Describe one entry, reusing the previous hash when its metadata is unchanged
+fn scan_entry(
+    path: &Path,
+    metadata: &fs::Metadata,
+    previous: Option<&SnapshotEntry>,
+) -> JcResult<SnapshotEntry> {
+    let kind = if metadata.is_dir() {
+        EntryKind::Dir
+    } else if metadata.file_type().is_symlink() {
+        EntryKind::Symlink
+    } else {
+        EntryKind::File
+    };
+
+    let mut entry = SnapshotEntry {
+        kind,
+        inode: metadata.ino(),
+        mtime_ns: metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec(),
+        size: metadata.len(),
+        sha256: String::new(),
+    };
+
+    entry.sha256 = match kind {
+        EntryKind::Dir => String::new(),
+        _ if previous.is_some_and(|p| p.same_metadata(&entry)) => {
+            previous.map(|p| p.sha256.clone()).unwrap_or_default()
+        }
+        EntryKind::File => hash_content(path)?,
+        EntryKind::Symlink => {
+            let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+            hasher.update(fs::read_link(path)?.as_os_str().as_bytes());
+            hasher.finalize()
+        }
+    };
+
+    Ok(entry)
+}
+
+/// Collect every entry below `dir` that a backup can store
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    previous: Option<&SnapshotState>,
+    entries: &mut BTreeMap<String, SnapshotEntry>,
+) -> JcResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = fs::symlink_metadata(&path)?;
+        let file_type = metadata.file_type();
+        if !(file_type.is_dir() || file_type.is_file() || file_type.is_symlink()) {
+            debug!("Skipping special file {}", path.display());
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|_| JcError::Other(format!("{} is outside the backup", path.display())))?
+            .to_string_lossy()
+            .replace('\\', "/");
+        if relative == SNAPSHOT_MARKER {
+            return Err(JcError::Other(format!(
+                "Cannot back up {}, it already contains {}",
+                root.display(),
+                SNAPSHOT_MARKER
+            )));
+        }
+
+        let before = previous.and_then(|p| p.entries.get(&relative));
+        entries.insert(relative, scan_entry(&path, &metadata, before)?);
+
+        if file_type.is_dir() {
+            scan_dir(root, &path, previous, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Take a snapshot of the directory tree at `root`
+///
+/// Files whose inode, mtime and size match `previous` keep their recorded
+/// hash instead of being read again.
+pub fn scan_tree(root: &Path, previous: Option<&SnapshotState>) -> JcResult<SnapshotState> {
+    let mut entries = BTreeMap::new();
+    scan_dir(root, root, previous, &mut entries)?;
+
+    let created = Local::now().to_rfc3339();
+    let mut hasher = Hasher::new(ChecksumAlgorithm::Sha256);
+    hasher.update(created.as_bytes());
+    for (path, entry) in &entries {
+        hasher.update(path.as_bytes());
+        hasher.update(entry.sha256.as_bytes());
+    }
+    let mut id = hasher.finalize();
+    id.truncate(SNAPSHOT_ID_LEN);
+
+    Ok(SnapshotState {
+        jcz_version: env!("CARGO_PKG_VERSION").to_string(),
+        id,
+        created,
+        root: std::path::absolute(root)?.to_string_lossy().to_string(),
+        entries,
+    })
+}
+
+/// Paths added or changed since `previous`, and paths deleted since then
+///
+/// Entries count as changed when their type or content differs; a new
+/// mtime alone does not make a file part of the next archive.
+pub fn diff_snapshots(
+    previous: &SnapshotState,
+    current: &SnapshotState,
+) -> (Vec<String>, Vec<String>) {
+    let changed = current
+        .entries
+        .iter()
+        .filter(|(path, entry)| {
+            previous
+                .entries
+                .get(*path)
+                .is_none_or(|before| before.kind != entry.kind || before.sha256 != entry.sha256)
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+
+    let deleted = previous
+        .entries
+        .keys()
+        .filter(|path| !current.entries.contains_key(*path))
+        .cloned()
+        .collect();
+
+    (changed, deleted)
+}
+
+/// Check that a path from a marker stays inside the directory it applies to
+pub fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Read the snapshot state file, if it exists
+pub fn read_state(path: &Path) -> JcResult<Option<SnapshotState>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map(Some)
+        .map_err(|e| JcError::Other(format!("Invalid snapshot state {}: {}", path.display(), e)))
+}
+
+/// Write the snapshot state file, replacing it only once complete
+pub fn write_state(path: &Path, state: &SnapshotState, fsync: bool) -> JcResult<()> {
+    let json = serde_json::to_string_pretty(state)
+        .map_err(|e| JcError::Other(format!("Failed to write snapshot state: {}", e)))?;
+
+    let output = AtomicOutput::new(path);
+    fs::write(output.temp_path(), json + "\n")?;
+    output.commit(fsync)?;
+
+    debug!(
+        "Wrote snapshot {} with {} entries to {}",
+        state.id,
+        state.entries.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Read the marker at the top of an extracted archive, if there is one
+pub fn read_marker(dir: &Path) -> JcResult<Option<BackupMarker>> {
+    let path = dir.join(SNAPSHOT_MARKER);
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&fs::read_to_string(&path)?)
+        .map(Some)
+        .map_err(|e| JcError::Other(format!("Invalid backup marker {}: {}", path.display(), e)))
+}
+
+/// Write the marker into the directory that will become the archive
+pub fn write_marker(dir: &Path, marker: &BackupMarker) -> JcResult<()> {
+    let json = serde_json::to_string_pretty(marker)
+        .map_err(|e| JcError::Other(format!("Failed to write backup marker: {}", e)))?;
+    fs::write(dir.join(SNAPSHOT_MARKER), json + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_snapshot_diff() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), b"alpha").unwrap();
+        fs::write(root.join("sub/b.txt"), b"beta").unwrap();
+        symlink("a.txt", root.join("link")).unwrap();
+
+        let first = scan_tree(root, None).unwrap();
+        let paths: Vec<&str> = first.entries.keys().map(|p| p.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "link", "sub", "sub/b.txt"]);
+        assert_eq!(first.entries["sub"].kind, EntryKind::Dir);
+        assert_eq!(first.entries["link"].kind, EntryKind::Symlink);
+
+        fs::write(root.join("a.txt"), b"ALPHA").unwrap();
+        fs::remove_file(root.join("sub/b.txt")).unwrap();
+        fs::write(root.join("c.txt"), b"gamma").unwrap();
+
+        let second = scan_tree(root, Some(&first)).unwrap();
+        assert_ne!(first.id, second.id);
+        let (changed, deleted) = diff_snapshots(&first, &second);
+        assert_eq!(changed, vec!["a.txt", "c.txt"]);
+        assert_eq!(deleted, vec!["sub/b.txt"]);
+    }
+
+    #[test]
+    fn test_unchanged_content_not_reported() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        let first = scan_tree(dir.path(), None).unwrap();
+
+        // Rewriting the same bytes changes the mtime but not the hash
+        fs::remove_file(dir.path().join("a.txt")).unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        let second = scan_tree(dir.path(), Some(&first)).unwrap();
+
+        let (changed, deleted) = diff_snapshots(&first, &second);
+        assert!(changed.is_empty() && deleted.is_empty());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.txt"), b"alpha").unwrap();
+        let state = scan_tree(dir.path(), None).unwrap();
+
+        let path = dir.path().join("state.json");
+        assert!(read_state(&path).unwrap().is_none());
+        write_state(&path, &state, false).unwrap();
+        let read = read_state(&path).unwrap().unwrap();
+        assert_eq!(read.id, state.id);
+        assert_eq!(read.entries, state.entries);
+
+        fs::write(&path, b"not json").unwrap();
+        assert!(read_state(&path).is_err());
+    }
+
+    #[test]
+    fn test_is_safe_relative() {
+        assert!(is_safe_relative("a/b.txt"));
+        assert!(!is_safe_relative("../b.txt"));
+        assert!(!is_safe_relative("/etc/passwd"));
+        assert!(!is_safe_relative("a/../../b"));
+        assert!(!is_safe_relative(""));
+    }
+}
@@ -0,0 +1,360 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::ChecksumAlgorithm;
+use crate::utils::atomic::AtomicOutput;
+use crate::utils::checksum::{write_sidecar, Hasher};
+use crate::utils::conflict::Reservation;
+use crate::utils::logger::{debug, info};
+
+/// Parse a size such as `4G`, `512M` or `1048576` into bytes
+///
+/// Suffixes are binary multiples (K = 1024) and may be followed by `B` or `iB`.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(digits_end);
+    let number: u64 = number.parse().ok()?;
+
+    let suffix = suffix.trim().to_uppercase();
+    let suffix = suffix
+        .strip_suffix("IB")
+        .or_else(|| suffix.strip_suffix('B'))
+        .unwrap_or(&suffix);
+
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+
+    number.checked_mul(multiplier)
+}
+
+/// Path of volume `n` (1-based) of a split output, e.g. `name.tar.gz.001`
+pub fn volume_path(base: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(base.as_os_str());
+    name.push(format!(".{:03}", n));
+    PathBuf::from(name)
+}
+
+/// If `path` is the first volume of a split set, get the path of the whole output
+pub fn first_volume_base(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_some_and(|ext| ext == "001") {
+        Some(path.with_extension(""))
+    } else {
+        None
+    }
+}
+
+/// If `path` is any volume of a split set, get the path of the whole output
+pub fn volume_base(path: &Path) -> Option<PathBuf> {
+    let ext = path.extension()?.to_str()?;
+    if ext.len() >= 3 && ext.bytes().all(|b| b.is_ascii_digit()) {
+        Some(path.with_extension(""))
+    } else {
+        None
+    }
+}
+
+/// Find all volumes of the split set starting at `first`, in order
+///
+/// Fails if a volume is missing from the sequence or if any volume except the
+/// last differs in size from the first one.
+pub fn collect_volumes(first: &Path) -> JcResult<Vec<PathBuf>> {
+    let base = first_volume_base(first)
+        .ok_or_else(|| JcError::InvalidExtension(first.to_path_buf(), "001".to_string()))?;
+    let base_name = base
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| JcError::Other("Invalid volume name".to_string()))?;
+    let dir = base
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    let prefix = format!("{}.", base_name);
+    let mut numbers: Vec<usize> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let digits = name.strip_prefix(&prefix)?;
+            if digits.len() >= 3 && digits.chars().all(|c| c.is_ascii_digit()) {
+                digits.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect();
+    numbers.sort_unstable();
+
+    if numbers.first() != Some(&1) {
+        return Err(JcError::FileNotFound(first.to_path_buf()));
+    }
+    for (expected, n) in (1..).zip(&numbers) {
+        if *n != expected {
+            return Err(JcError::MissingVolume(volume_path(&base, expected)));
+        }
+    }
+
+    let volumes: Vec<PathBuf> = numbers.iter().map(|n| volume_path(&base, *n)).collect();
+
+    // Every volume but the last is filled up to the split size
+    let volume_size = fs::metadata(&volumes[0])?.len();
+    for (i, volume) in volumes.iter().enumerate().skip(1) {
+        let size = fs::metadata(volume)?.len();
+        let is_last = i == volumes.len() - 1;
+        if size > volume_size || (!is_last && size != volume_size) {
+            return Err(JcError::VerificationFailed {
+                path: volume.clone(),
+                reason: format!(
+                    "expected {} bytes like the first volume, found {}",
+                    volume_size, size
+                ),
+            });
+        }
+    }
+
+    debug!("Found {} volumes of {}", volumes.len(), base.display());
+    Ok(volumes)
+}
+
+/// Files making up an input: every volume of a split set, or the file itself
+pub fn input_files(path: &Path) -> JcResult<Vec<PathBuf>> {
+    match first_volume_base(path) {
+        Some(_) => collect_volumes(path),
+        None => Ok(vec![path.to_path_buf()]),
+    }
+}
+
+/// Spawn `cat` writing the concatenation of `volumes` to a pipe
+pub fn spawn_concat(volumes: &[PathBuf]) -> JcResult<Child> {
+    let mut cmd = Command::new("cat");
+    cmd.args(volumes)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    debug!("Executing: {:?}", cmd);
+
+    cmd.spawn()
+        .map_err(|e| JcError::Other(format!("Failed to spawn cat: {}", e)))
+}
+
+/// Concatenate the split set starting at `first` into `dir`
+///
+/// Returns the joined file, named after the set without the volume number.
+pub fn join_volumes(first: &Path, dir: &Path) -> JcResult<PathBuf> {
+    let volumes = collect_volumes(first)?;
+    let base = first_volume_base(first)
+        .ok_or_else(|| JcError::InvalidExtension(first.to_path_buf(), "001".to_string()))?;
+    let name = base
+        .file_name()
+        .ok_or_else(|| JcError::Other("Invalid volume name".to_string()))?;
+    let joined = dir.join(name);
+
+    info!("Joining {} volumes of {}", volumes.len(), base.display());
+
+    let mut writer = BufWriter::new(File::create(&joined)?);
+    for volume in &volumes {
+        io::copy(&mut File::open(volume)?, &mut writer)?;
+    }
+    writer.flush()?;
+
+    Ok(joined)
+}
+
+/// Writer for a single file or a set of fixed-size volumes
+///
+/// With a split size, data goes to `name.001`, `name.002`, ... as it is
+/// written, each volume holding exactly `split_size` bytes except the last.
+/// Nothing appears under the final names until `commit` succeeds.
+///
+/// With a checksum algorithm, each volume is hashed as it is written and the
+/// sidecar lists every volume, so `sha256sum -c` can check the set.
+pub struct VolumeWriter {
+    target: PathBuf,
+    split_size: Option<u64>,
+    volumes: Vec<AtomicOutput>,
+    current: BufWriter<File>,
+    written: u64,
+    checksum: Option<ChecksumAlgorithm>,
+    hasher: Option<Hasher>,
+    digests: Vec<String>,
+    reservation: Reservation,
+}
+
+impl VolumeWriter {
+    pub fn new(
+        target: &Path,
+        split_size: Option<u64>,
+        checksum: Option<ChecksumAlgorithm>,
+        reservation: Reservation,
+    ) -> JcResult<Self> {
+        let first = match split_size {
+            Some(_) => AtomicOutput::new(&volume_path(target, 1)),
+            None => AtomicOutput::new(target),
+        };
+        let current = BufWriter::new(File::create(first.temp_path())?);
+
+        Ok(Self {
+            target: target.to_path_buf(),
+            split_size,
+            volumes: vec![first],
+            current,
+            written: 0,
+            checksum,
+            hasher: checksum.map(Hasher::new),
+            digests: Vec::new(),
+            reservation,
+        })
+    }
+
+    /// Finish the digest of the volume being written
+    fn finish_digest(&mut self) {
+        if let Some(hasher) = self.hasher.take() {
+            self.digests.push(hasher.finalize());
+        }
+        self.hasher = self.checksum.map(Hasher::new);
+    }
+
+    /// Start writing the next volume
+    fn next_volume(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        self.finish_digest();
+
+        let volume = AtomicOutput::new(&volume_path(&self.target, self.volumes.len() + 1));
+        self.current = BufWriter::new(File::create(volume.temp_path())?);
+        self.volumes.push(volume);
+        self.written = 0;
+
+        Ok(())
+    }
+
+    /// Move all volumes into place and return the path of the output
+    ///
+    /// For split outputs this is the first volume. Leftover volumes of an
+    /// older, longer set with the same name are removed. The checksum
+    /// sidecar is written once the output itself is in place.
+    pub fn commit(mut self, fsync: bool) -> JcResult<PathBuf> {
+        self.current.flush()?;
+        self.finish_digest();
+
+        let count = self.volumes.len();
+        let mut committed = Vec::with_capacity(count);
+        for volume in self.volumes.drain(..) {
+            committed.push(volume.commit(fsync)?);
+        }
+
+        let reservation = std::mem::take(&mut self.reservation);
+        if self.split_size.is_none() {
+            // The output replaced the reserved name
+            reservation.keep();
+        } else {
+            // The volumes took the name, so the reserved placeholder goes
+            drop(reservation);
+
+            let mut n = count + 1;
+            while volume_path(&self.target, n).exists() {
+                debug!(
+                    "Removing stale volume {}",
+                    volume_path(&self.target, n).display()
+                );
+                fs::remove_file(volume_path(&self.target, n))?;
+                n += 1;
+            }
+            info!("Wrote {} volumes of {}", count, self.target.display());
+        }
+
+        if let Some(algorithm) = self.checksum {
+            let files: Vec<(PathBuf, String)> = committed
+                .iter()
+                .cloned()
+                .zip(self.digests.drain(..))
+                .collect();
+            write_sidecar(&self.target, algorithm, &files, fsync)?;
+        }
+
+        Ok(committed.swap_remove(0))
+    }
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(split_size) = self.split_size else {
+            let n = self.current.write(buf)?;
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(&buf[..n]);
+            }
+            return Ok(n);
+        };
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.written == split_size {
+            self.next_volume()?;
+        }
+
+        let room = (split_size - self.written).min(buf.len() as u64) as usize;
+        let n = self.current.write(&buf[..room])?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..n]);
+        }
+        self.written += n as u64;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("4G"), Some(4 << 30));
+        assert_eq!(parse_size("512m"), Some(512 << 20));
+        assert_eq!(parse_size("10KiB"), Some(10 << 10));
+        assert_eq!(parse_size("2TB"), Some(2 << 40));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("4X"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn test_volume_paths() {
+        let base = Path::new("/backups/data.tar.gz");
+        assert_eq!(
+            volume_path(base, 2),
+            PathBuf::from("/backups/data.tar.gz.002")
+        );
+        assert_eq!(
+            first_volume_base(Path::new("/backups/data.tar.gz.001")),
+            Some(base.to_path_buf())
+        );
+        assert_eq!(
+            first_volume_base(Path::new("/backups/data.tar.gz.002")),
+            None
+        );
+        assert_eq!(first_volume_base(base), None);
+        assert_eq!(
+            volume_base(Path::new("/backups/data.tar.gz.002")),
+            Some(base.to_path_buf())
+        );
+        assert_eq!(volume_base(Path::new("/backups/notes.v2")), None);
+        assert_eq!(volume_base(base), None);
+    }
+}
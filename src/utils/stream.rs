@@ -0,0 +1,236 @@
+use age::stream::StreamWriter;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread::{self, JoinHandle};
+
+use crate::core::config::{CompressionConfig, EncryptionKeys};
+use crate::core::error::{JcError, JcResult};
+use crate::utils::conflict::Reservation;
+use crate::utils::crypto::{decrypt_reader, encrypt_writer, is_encrypted};
+use crate::utils::logger::debug;
+use crate::utils::split::{first_volume_base, input_files, VolumeWriter};
+
+/// Writer for a final output, applying the split, encryption and checksum settings
+///
+/// Data is encrypted first (if requested), then cut into volumes that are
+/// hashed as they are written. Nothing appears under the final names until
+/// `commit` succeeds.
+pub enum OutputWriter {
+    Plain(VolumeWriter),
+    Encrypted(StreamWriter<VolumeWriter>),
+}
+
+impl OutputWriter {
+    /// Create the writer for `target`, taking over the name reserved for it, if any
+    pub fn new(
+        target: &Path,
+        reservation: Reservation,
+        config: &CompressionConfig,
+    ) -> JcResult<Self> {
+        let volumes = VolumeWriter::new(target, config.split_size, config.checksum, reservation)?;
+
+        if config.encrypt {
+            Ok(Self::Encrypted(encrypt_writer(volumes, &config.keys)?))
+        } else {
+            Ok(Self::Plain(volumes))
+        }
+    }
+
+    /// Finish the output and move it into place, returning its path
+    pub fn commit(self, fsync: bool) -> JcResult<PathBuf> {
+        let volumes = match self {
+            Self::Plain(volumes) => volumes,
+            Self::Encrypted(writer) => writer.finish()?,
+        };
+
+        volumes.commit(fsync)
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Encrypted(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Encrypted(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Open a final output for reading, joining volumes and decrypting as needed
+pub fn open_input(path: &Path, keys: &EncryptionKeys) -> JcResult<Box<dyn Read + Send>> {
+    let mut reader: Box<dyn Read + Send> = Box::new(io::empty());
+    for file in input_files(path)? {
+        reader = Box::new(reader.chain(File::open(file)?));
+    }
+
+    if is_encrypted(path) {
+        let base = first_volume_base(path).unwrap_or_else(|| path.to_path_buf());
+        Ok(Box::new(decrypt_reader(reader, keys, &base)?))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Run `cmd` and stream its standard output into `writer`
+///
+/// Standard error is drained on its own thread, so a tool that writes many
+/// warnings cannot block on a full pipe while its output is copied. Returns
+/// the exit status and standard error; standard output is left empty.
+pub fn run_into_writer(mut cmd: Command, writer: &mut impl Write) -> JcResult<Output> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    debug!("Executing: {:?}", cmd);
+
+    let child = cmd
+        .spawn()
+        .map_err(|e| JcError::Other(format!("Failed to spawn {}: {}", tool, e)))?;
+
+    copy_output(child, writer)
+}
+
+/// Copy the standard output of `child` into `writer` and wait for it
+fn copy_output(mut child: Child, writer: &mut impl Write) -> JcResult<Output> {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (copied, stderr) = thread::scope(|scope| {
+        let drain = scope.spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut stderr) = stderr {
+                // Whatever was read is still worth reporting
+                let _ = stderr.read_to_end(&mut buf);
+            }
+            buf
+        });
+        let copied = match stdout {
+            Some(mut stdout) => io::copy(&mut stdout, writer).map(|_| ()),
+            None => Ok(()),
+        };
+        // stdout is closed here, so a tool still writing after a failed copy exits
+        (copied, drain.join().unwrap_or_default())
+    });
+
+    let status = child.wait()?;
+    copied?;
+
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr,
+    })
+}
+
+/// Spawn `cmd` with the contents of the final output `path` fed to its stdin
+/// from a thread of its own
+fn spawn_with_input(
+    cmd: &mut Command,
+    path: &Path,
+    keys: &EncryptionKeys,
+) -> JcResult<(Child, JoinHandle<io::Result<()>>)> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    let mut input = open_input(path, keys)?;
+
+    debug!("Executing: {:?} < {}", cmd, path.display());
+
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| JcError::Other(format!("Failed to execute {}: {}", tool, e)))?;
+
+    let stdin = child.stdin.take();
+    let feeder = thread::spawn(move || -> io::Result<()> {
+        if let Some(mut stdin) = stdin {
+            io::copy(&mut input, &mut stdin)?;
+        }
+        Ok(())
+    });
+
+    Ok((child, feeder))
+}
+
+/// Wait for the thread feeding `path` to a tool that exited with `output`
+fn finish_input(
+    feeder: JoinHandle<io::Result<()>>,
+    tool: &str,
+    path: &Path,
+    output: &Output,
+) -> JcResult<()> {
+    let fed = feeder
+        .join()
+        .map_err(|_| JcError::Other(format!("Failed to feed {}", tool)))?;
+
+    // A tool may stop reading once it has what it needs (or fails early), so
+    // a closed pipe is not an error; a failure to read the input is
+    if let Err(e) = fed {
+        if output.status.success() && e.kind() != io::ErrorKind::BrokenPipe {
+            return Err(JcError::Other(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `cmd` with the contents of the final output `path` on stdin
+///
+/// Split sets are fed in whole and encrypted outputs are decrypted on the way.
+pub fn run_with_input(mut cmd: Command, path: &Path, keys: &EncryptionKeys) -> JcResult<Output> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    let (child, feeder) = spawn_with_input(&mut cmd, path, keys)?;
+
+    let output = child.wait_with_output()?;
+    finish_input(feeder, &tool, path, &output)?;
+
+    Ok(output)
+}
+
+/// Run `cmd` with the contents of the final output `path` on stdin, streaming
+/// its standard output into `writer`
+///
+/// Like `run_with_input`, but the output never has to fit in memory, and
+/// like `run_into_writer`, standard output is left empty in the result.
+pub fn run_with_input_into_writer(
+    mut cmd: Command,
+    path: &Path,
+    keys: &EncryptionKeys,
+    writer: &mut impl Write,
+) -> JcResult<Output> {
+    let tool = cmd.get_program().to_string_lossy().to_string();
+    let (child, feeder) = spawn_with_input(&mut cmd, path, keys)?;
+
+    let output = copy_output(child, writer)?;
+    finish_input(feeder, &tool, path, &output)?;
+
+    Ok(output)
+}
+
+/// Check the final output `path` with a test command such as `gzip -t`
+///
+/// The command reads the output on stdin, like `run_with_input`; a failure is
+/// reported as `JcError::VerificationFailed` with the tool's stderr.
+pub fn verify_with(cmd: Command, path: &Path, keys: &EncryptionKeys) -> JcResult<()> {
+    let output = run_with_input(cmd, path, keys)?;
+
+    if !output.status.success() {
+        return Err(JcError::VerificationFailed {
+            path: path.to_path_buf(),
+            reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
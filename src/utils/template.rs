@@ -0,0 +1,331 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::core::config::{CompressionConfig, OutputTemplate, TimestampOption};
+use crate::core::error::{JcError, JcResult};
+use crate::core::types::{
+    ChecksumAlgorithm, CompoundFormat, CompressionFormat, ENCRYPTED_EXTENSION,
+};
+use crate::utils::checksum::HashingWriter;
+use crate::utils::manifest::host_name;
+use crate::utils::timestamp::{format_timestamp, stamp_time};
+
+/// Placeholders understood in output templates
+pub const PLACEHOLDERS: [&str; 11] = [
+    "stem",
+    "basename",
+    "parent",
+    "ext",
+    "format",
+    "level",
+    "timestamp",
+    "date",
+    "host",
+    "counter",
+    "hash",
+];
+
+/// Number of hex digits in `{hash}`
+const SHORT_HASH_LEN: usize = 8;
+
+/// Piece of a parsed template
+enum Piece<'a> {
+    Literal(String),
+    Placeholder(&'a str),
+}
+
+/// Split a template into literal text and placeholders
+fn parse(pattern: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = pattern;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..i]);
+        let brace = &rest[i..];
+
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            literal.push_str(&brace[..1]);
+            rest = &brace[2..];
+        } else if let Some(after) = brace.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("Unclosed '{{' in output template: {}", pattern))?;
+            let name = &after[..end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "Unknown placeholder {{{}}} in output template",
+                    name
+                ));
+            }
+
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+            }
+            pieces.push(Piece::Placeholder(name));
+            rest = &after[end + 1..];
+        } else {
+            return Err(format!("Unmatched '}}' in output template: {}", pattern));
+        }
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+/// Check an `--output-template` before any input is processed
+pub fn validate_template(pattern: &str) -> Result<(), String> {
+    let pieces = parse(strip_extension(pattern))?;
+    if pieces.is_empty() {
+        return Err("Output template is empty".to_string());
+    }
+    if pattern.contains('/') {
+        return Err(format!("Output template must not contain '/': {}", pattern));
+    }
+    Ok(())
+}
+
+/// The template without a trailing `.{ext}`, which is always added back
+fn strip_extension(pattern: &str) -> &str {
+    pattern.strip_suffix(".{ext}").unwrap_or(pattern)
+}
+
+/// Name of the format that writes `extension`, e.g. `tgz` for `tar.gz`
+fn format_name(extension: &str) -> &str {
+    let extension = extension
+        .strip_suffix(ENCRYPTED_EXTENSION)
+        .and_then(|e| e.strip_suffix('.'))
+        .unwrap_or(extension);
+
+    if let Some(format) = CompressionFormat::from_extension(extension) {
+        return format.name();
+    }
+    ["tgz", "tbz2", "txz"]
+        .into_iter()
+        .find(|name| CompoundFormat::from_str(name).is_some_and(|f| f.extension() == extension))
+        .unwrap_or(extension)
+}
+
+/// Hash the regular files below `path` with their relative paths, in name order
+fn hash_tree(root: &Path, path: &Path, writer: &mut HashingWriter<io::Sink>) -> JcResult<()> {
+    let metadata = fs::symlink_metadata(path)?;
+
+    if metadata.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<io::Result<_>>()?;
+        entries.sort();
+        for entry in entries {
+            hash_tree(root, &entry, writer)?;
+        }
+    } else if metadata.is_file() {
+        if path != root {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            io::Write::write_all(writer, relative.to_string_lossy().as_bytes())?;
+            io::Write::write_all(writer, b"\0")?;
+        }
+        io::copy(&mut File::open(path)?, writer)?;
+    }
+
+    Ok(())
+}
+
+/// Short SHA-256 of the content of `sources`
+fn content_hash<P: AsRef<Path>>(sources: &[P]) -> JcResult<String> {
+    let mut writer = HashingWriter::new(io::sink(), Some(ChecksumAlgorithm::Sha256));
+    for source in sources {
+        hash_tree(source.as_ref(), source.as_ref(), &mut writer)?;
+    }
+
+    let (_, digest) = writer.finish();
+    let mut digest = digest.unwrap_or_default();
+    digest.truncate(SHORT_HASH_LEN);
+    Ok(digest)
+}
+
+/// Placeholder that dates outputs for retention: `timestamp` or `date`
+///
+/// Retention groups outputs whose names differ only in this placeholder, so
+/// the template needs exactly one of them and no per-run `{hash}` or `{counter}`.
+pub fn stamp_placeholder(pattern: &str) -> Result<&'static str, String> {
+    let pieces = parse(strip_extension(pattern))?;
+    let placeholders: Vec<&str> = pieces
+        .iter()
+        .filter_map(|piece| match piece {
+            Piece::Placeholder(name) => Some(*name),
+            Piece::Literal(_) => None,
+        })
+        .collect();
+
+    if placeholders.iter().any(|p| ["hash", "counter"].contains(p)) {
+        return Err(format!(
+            "Retention cannot group outputs named with {{hash}} or {{counter}}: {}",
+            pattern
+        ));
+    }
+    match placeholders
+        .iter()
+        .filter(|p| ["timestamp", "date"].contains(p))
+        .collect::<Vec<_>>()[..]
+    {
+        [&"timestamp"] => Ok("timestamp"),
+        [&"date"] => Ok("date"),
+        _ => Err(format!(
+            "Retention needs exactly one {{timestamp}} or {{date}} in the output template: {}",
+            pattern
+        )),
+    }
+}
+
+/// Render the output name for `name`, without its extension
+///
+/// `sources` are the files the output is made from, for `{hash}` and for
+/// timestamps taken from modification times. `extension` is the full
+/// extension the output gets, e.g. `tar.gz`.
+pub fn render_stem<P: AsRef<Path>>(
+    template: &OutputTemplate,
+    name: &Path,
+    sources: &[P],
+    extension: &str,
+    config: &CompressionConfig,
+) -> JcResult<String> {
+    render(template, name, sources, extension, config, None)
+}
+
+/// Render the output name for `name` with `stamp` as its `{timestamp}` and `{date}`
+pub fn render_stem_with_stamp<P: AsRef<Path>>(
+    template: &OutputTemplate,
+    name: &Path,
+    sources: &[P],
+    extension: &str,
+    config: &CompressionConfig,
+    stamp: &str,
+) -> JcResult<String> {
+    render(template, name, sources, extension, config, Some(stamp))
+}
+
+fn render<P: AsRef<Path>>(
+    template: &OutputTemplate,
+    name: &Path,
+    sources: &[P],
+    extension: &str,
+    config: &CompressionConfig,
+    stamp: Option<&str>,
+) -> JcResult<String> {
+    let pieces = parse(strip_extension(&template.pattern)).map_err(JcError::Other)?;
+    let style = &config.timestamp_style;
+
+    let mut stem = String::new();
+    for piece in pieces {
+        let value = match piece {
+            Piece::Literal(text) => text,
+            Piece::Placeholder("stem") => name
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Piece::Placeholder("basename") => name
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Piece::Placeholder("parent") => std::path::absolute(name)?
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Piece::Placeholder("ext") => extension.to_string(),
+            Piece::Placeholder("format") => format_name(extension).to_string(),
+            Piece::Placeholder("level") => config.level.to_string(),
+            Piece::Placeholder("timestamp" | "date") if stamp.is_some() => {
+                stamp.unwrap_or_default().to_string()
+            }
+            Piece::Placeholder("timestamp") => {
+                let option = match config.timestamp {
+                    TimestampOption::None => &TimestampOption::DateTime,
+                    ref option => option,
+                };
+                format_timestamp(option, stamp_time(style, sources)?, style.utc)?
+            }
+            Piece::Placeholder("date") => format_timestamp(
+                &TimestampOption::Date,
+                stamp_time(style, sources)?,
+                style.utc,
+            )?,
+            Piece::Placeholder("host") => host_name(),
+            Piece::Placeholder("counter") => template.counter.to_string(),
+            Piece::Placeholder("hash") => content_hash(sources)?,
+            Piece::Placeholder(other) => {
+                return Err(JcError::Other(format!(
+                    "Unknown placeholder {{{}}} in output template",
+                    other
+                )))
+            }
+        };
+        stem.push_str(&value);
+    }
+
+    if stem.is_empty() || stem.contains('/') {
+        return Err(JcError::Other(format!(
+            "Output template {} gives an invalid name for {}: '{}'",
+            template.pattern,
+            name.display(),
+            stem
+        )));
+    }
+
+    Ok(stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_placeholders() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("report.txt");
+        fs::write(&input, b"abc").unwrap();
+
+        let config = CompressionConfig::new().with_level(9);
+        let template =
+            OutputTemplate::new("{stem}_{basename}_{format}_{level}_{counter}_{hash}.{ext}");
+        let stem = render_stem(&template, &input, &[&input], "tar.gz", &config).unwrap();
+        assert_eq!(stem, "report_report.txt_tgz_9_1_ba7816bf");
+
+        let parent = dir.path().file_name().unwrap().to_string_lossy();
+        let template = OutputTemplate::new("{parent}-{{x}}");
+        let stem = render_stem(&template, &input, &[&input], "gz.age", &config).unwrap();
+        assert_eq!(stem, format!("{}-{{x}}", parent));
+    }
+
+    #[test]
+    fn test_literal_template() {
+        let config = CompressionConfig::new();
+        let template = OutputTemplate::literal("odd {name}");
+        let stem = render_stem(&template, Path::new("x"), &[] as &[&Path], "tar", &config).unwrap();
+        assert_eq!(stem, "odd {name}");
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert!(validate_template("{stem}-{host}-{date}.{ext}").is_ok());
+        assert!(validate_template("{stem}-{nope}").is_err());
+        assert!(validate_template("{stem").is_err());
+        assert!(validate_template("stem}").is_err());
+        assert!(validate_template("{parent}/{stem}").is_err());
+        assert!(validate_template(".{ext}").is_err());
+    }
+
+    #[test]
+    fn test_stamp_placeholder() {
+        assert_eq!(stamp_placeholder("{stem}-{date}.{ext}"), Ok("date"));
+        assert_eq!(stamp_placeholder("{host}_{timestamp}"), Ok("timestamp"));
+        assert!(stamp_placeholder("{stem}").is_err());
+        assert!(stamp_placeholder("{date}-{timestamp}").is_err());
+        assert!(stamp_placeholder("{stem}-{hash}-{date}").is_err());
+    }
+}
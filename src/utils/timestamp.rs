@@ -1,40 +1,180 @@
-use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
 
-use crate::core::config::TimestampOption;
+use crate::core::config::{TimestampOption, TimestampStyle};
+use crate::core::error::{JcError, JcResult};
 
-/// Generate timestamp string based on option
-pub fn generate_timestamp(option: TimestampOption) -> String {
-    let now = Local::now();
+/// Format `time` with the option's pattern, in UTC or local time
+pub fn format_timestamp(
+    option: &TimestampOption,
+    time: DateTime<Utc>,
+    utc: bool,
+) -> JcResult<String> {
+    let Some(pattern) = option.pattern() else {
+        return Ok(String::new());
+    };
 
-    match option {
-        TimestampOption::None => String::new(),
-        TimestampOption::Date => now.format("%Y%m%d").to_string(),
-        TimestampOption::DateTime => now.format("%Y%m%d_%H%M%S").to_string(),
-        TimestampOption::Nanoseconds => now.timestamp_subsec_nanos().to_string(),
+    // Invalid patterns fail while formatting rather than when parsed
+    let mut stamp = String::new();
+    let result = if utc {
+        write!(stamp, "{}", time.format(pattern))
+    } else {
+        write!(stamp, "{}", time.with_timezone(&Local).format(pattern))
+    };
+    result.map_err(|_| JcError::Other(format!("Invalid timestamp format: {}", pattern)))?;
+
+    Ok(stamp)
+}
+
+/// Time to stamp an output with: now, or the newest mtime of its sources
+pub fn stamp_time<P: AsRef<Path>>(
+    style: &TimestampStyle,
+    sources: &[P],
+) -> JcResult<DateTime<Utc>> {
+    if !style.from_mtime {
+        return Ok(Utc::now());
+    }
+
+    let mut newest = None;
+    for source in sources {
+        let modified = DateTime::<Utc>::from(fs::metadata(source)?.modified()?);
+        newest = newest.max(Some(modified));
+    }
+    Ok(newest.unwrap_or_else(Utc::now))
+}
+
+/// Generate the timestamp for an output made from `source`
+pub fn generate_timestamp(
+    option: &TimestampOption,
+    style: &TimestampStyle,
+    source: &Path,
+) -> JcResult<String> {
+    format_timestamp(option, stamp_time(style, &[source])?, style.utc)
+}
+
+/// Read back a timestamp written with `pattern`
+///
+/// Patterns without a time of day give midnight. Returns `None` when the text
+/// is not exactly what the pattern writes or the pattern has no date.
+pub fn parse_timestamp(stamp: &str, pattern: &str, utc: bool) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(stamp, pattern)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(stamp, pattern)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    // The parser accepts unpadded fields, which the formatter never writes
+    let mut written = String::new();
+    write!(written, "{}", naive.format(pattern)).ok()?;
+    if written != stamp {
+        return None;
+    }
+
+    if utc {
+        Some(naive.and_utc())
+    } else {
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+/// Check that a `--timestamp-format` pattern gives a usable file name part
+pub fn validate_timestamp_format(pattern: &str) -> Result<(), String> {
+    if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid timestamp format: {}", pattern));
     }
+
+    let sample = format_timestamp(
+        &TimestampOption::Custom(pattern.to_string()),
+        Utc::now(),
+        true,
+    )
+    .map_err(|e| e.to_string())?;
+    if sample.is_empty() || sample.contains('/') {
+        return Err(format!(
+            "Timestamp format must give a non-empty name without '/': {}",
+            pattern
+        ));
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn now(option: TimestampOption) -> String {
+        generate_timestamp(&option, &TimestampStyle::default(), Path::new(".")).unwrap()
+    }
+
     #[test]
     fn test_timestamp_none() {
-        let ts = generate_timestamp(TimestampOption::None);
+        let ts = now(TimestampOption::None);
         assert_eq!(ts, "");
     }
 
     #[test]
     fn test_timestamp_date() {
-        let ts = generate_timestamp(TimestampOption::Date);
+        let ts = now(TimestampOption::Date);
         assert_eq!(ts.len(), 8); // YYYYMMDD
         assert!(ts.chars().all(|c| c.is_ascii_digit()));
     }
 
     #[test]
     fn test_timestamp_datetime() {
-        let ts = generate_timestamp(TimestampOption::DateTime);
+        let ts = now(TimestampOption::DateTime);
         assert_eq!(ts.len(), 15); // YYYYMMDD_HHMMSS
         assert!(ts.contains('_'));
     }
+
+    #[test]
+    fn test_timestamp_custom_utc() {
+        let time = DateTime::from_timestamp(1_700_000_000, 5).unwrap();
+        let custom = TimestampOption::Custom("%Y-%m-%dT%H%M%SZ".to_string());
+        assert_eq!(
+            format_timestamp(&custom, time, true).unwrap(),
+            "2023-11-14T221320Z"
+        );
+        assert_eq!(
+            format_timestamp(&TimestampOption::Nanoseconds, time, true).unwrap(),
+            "20231114_221320_000000005"
+        );
+    }
+
+    #[test]
+    fn test_validate_timestamp_format() {
+        assert!(validate_timestamp_format("%Y%m%d").is_ok());
+        assert!(validate_timestamp_format("%Q").is_err());
+        assert!(validate_timestamp_format("%Y/%m").is_err());
+        assert!(validate_timestamp_format("").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let time = DateTime::from_timestamp(1_700_000_000, 5).unwrap();
+        for option in [
+            TimestampOption::DateTime,
+            TimestampOption::Nanoseconds,
+            TimestampOption::Custom("%Y-%m-%dT%H%M%SZ".to_string()),
+        ] {
+            let stamp = format_timestamp(&option, time, true).unwrap();
+            let parsed = parse_timestamp(&stamp, option.pattern().unwrap(), true).unwrap();
+            assert_eq!(parsed.timestamp(), 1_700_000_000);
+        }
+
+        let parsed = parse_timestamp("20231114", "%Y%m%d", true).unwrap();
+        assert_eq!(parsed.to_rfc3339(), "2023-11-14T00:00:00+00:00");
+
+        assert!(parse_timestamp("2023111", "%Y%m%d", true).is_none());
+        assert!(parse_timestamp("old", "%Y%m%d", true).is_none());
+        assert!(parse_timestamp("2213", "%H%M", true).is_none());
+    }
 }
@@ -1,5 +1,8 @@
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStringExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
@@ -53,6 +56,38 @@ pub fn validate_input_files(paths: Vec<PathBuf>) -> JcResult<Vec<InputFile>> {
     Ok(validated)
 }
 
+/// Read a list of input paths from a file, or from stdin for `-`
+///
+/// Paths are separated by newlines, or by NUL characters with `null`, and
+/// taken as given: relative paths resolve against the current directory.
+/// Empty entries are ignored.
+pub fn read_file_list(source: &Path, null: bool) -> JcResult<Vec<PathBuf>> {
+    let mut data = Vec::new();
+    if source == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut data)?;
+    } else {
+        data = fs::read(source).map_err(|_| JcError::FileNotFound(source.to_path_buf()))?;
+    }
+
+    let separator = if null { b'\0' } else { b'\n' };
+    let paths: Vec<PathBuf> = data
+        .split(|&b| b == separator)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(OsString::from_vec(entry.to_vec())))
+        .collect();
+    debug!(
+        "Read {} inputs from {}",
+        paths.len(),
+        if source == Path::new("-") {
+            "stdin".to_string()
+        } else {
+            source.display().to_string()
+        }
+    );
+
+    Ok(paths)
+}
+
 /// Check if there are duplicate basenames
 #[allow(dead_code)]
 pub fn check_duplicate_basenames(files: &[InputFile]) -> Option<Vec<String>> {
@@ -93,6 +128,47 @@ fn resolve_symlink(path: &Path) -> JcResult<PathBuf> {
     Ok(PathBuf::from(real_path))
 }
 
+/// Check whether a directory is writable, without writing to it
+fn is_writable(dir: &Path) -> bool {
+    Command::new("test")
+        .arg("-w")
+        .arg(dir)
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Check a destination directory without changing anything
+///
+/// Returns whether the directory would have to be created. Its nearest
+/// existing ancestor must be a directory that can be written to.
+pub fn check_move_to(path: &Path) -> JcResult<bool> {
+    if path.exists() && !path.is_dir() {
+        return Err(JcError::NotADirectory(path.to_path_buf()));
+    }
+
+    let existing = path
+        .ancestors()
+        .map(|p| {
+            if p.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                p
+            }
+        })
+        .find(|p| p.exists())
+        .unwrap_or_else(|| Path::new("."));
+    if !existing.is_dir() {
+        return Err(JcError::NotADirectory(existing.to_path_buf()));
+    }
+    if !is_writable(existing) {
+        return Err(JcError::MoveToError(
+            "Directory is not writable".to_string(),
+        ));
+    }
+
+    Ok(!path.exists())
+}
+
 /// Validate destination directory
 pub fn validate_move_to(path: &Path) -> JcResult<()> {
     if !path.exists() {
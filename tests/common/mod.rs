@@ -19,13 +19,18 @@ pub fn create_test_files(dir: &Path, files: &[(&str, &[u8])]) -> Vec<PathBuf> {
         .collect()
 }
 
-/// Helper to create a jcz command
+/// Helper to create a jcz command, without the user's configuration file
 pub fn jcz_command() -> Command {
     #[allow(deprecated)]
-    Command::cargo_bin("jcz").expect("Failed to find jcz binary")
+    let mut cmd = Command::cargo_bin("jcz").expect("Failed to find jcz binary");
+    cmd.env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .env_remove("JCZ_CONFIG")
+        .env_remove("JCZ_PROFILE");
+    cmd
 }
 
 /// Helper to verify a file exists
+#[allow(dead_code)]
 pub fn file_exists(path: &Path) -> bool {
     path.exists() && path.is_file()
 }
@@ -88,6 +93,32 @@ pub fn verify_decompressed_content(compressed_path: &Path, expected_content: &[u
     }
 }
 
+/// Helper to list the entry names in a directory, sorted
+#[allow(dead_code)]
+pub fn names_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .expect("Failed to read directory")
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Helper to generate incompressible data, the same for the same seed
+#[allow(dead_code)]
+pub fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u8
+        })
+        .collect()
+}
+
 /// Helper to create a test directory structure
 #[allow(dead_code)]
 pub fn create_test_dir_structure(base: &Path, structure: &[&str]) -> Vec<PathBuf> {
@@ -107,7 +138,41 @@ pub fn create_test_dir_structure(base: &Path, structure: &[&str]) -> Vec<PathBuf
     paths
 }
 
+/// Helper to create `data/` below `root` holding files at the given relative paths
+#[allow(dead_code)]
+pub fn create_tree(root: &Path, files: &[(&str, &[u8])]) -> PathBuf {
+    let data_dir = root.join("data");
+    fs::create_dir_all(&data_dir).expect("Failed to create directory");
+    for (name, content) in files {
+        let path = data_dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create parent directory");
+        }
+        fs::write(&path, content).expect("Failed to create file");
+    }
+    data_dir
+}
+
+/// Helper to list the members of a TAR archive, compressed or not, sorted and
+/// without the trailing slash of directories
+#[allow(dead_code)]
+pub fn tar_members(archive: &Path) -> Vec<String> {
+    let output = std::process::Command::new("tar")
+        .arg("-tf")
+        .arg(archive)
+        .output()
+        .expect("Failed to run tar");
+    assert!(output.status.success());
+    let mut members: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim_end_matches('/').to_string())
+        .collect();
+    members.sort();
+    members
+}
+
 /// Test data content
+#[allow(dead_code)]
 pub const TEST_DATA_SMALL: &[u8] = b"Hello, World! This is a test file for compression.";
 #[allow(dead_code)]
 pub const TEST_DATA_MEDIUM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. \
@@ -0,0 +1,172 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_compress_leaves_no_temp_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(names_in(temp_dir.path()), vec!["test.txt", "test.txt.xz"]);
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("test.txt.xz"),
+        TEST_DATA_MEDIUM
+    ));
+}
+
+#[test]
+fn test_compound_leaves_no_intermediate_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(
+        names_in(temp_dir.path()),
+        vec!["test.txt", "test.txt.tar.gz"]
+    );
+}
+
+#[test]
+fn test_compound_with_move_to_leaves_no_intermediate_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tbz2")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(names_in(temp_dir.path()), vec!["output", "test.txt"]);
+    assert_eq!(names_in(&output_dir), vec!["test.txt.tar.bz2"]);
+}
+
+#[test]
+fn test_compress_with_fsync() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--fsync")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("test.txt.gz"),
+        TEST_DATA_SMALL
+    ));
+}
+
+#[test]
+fn test_decompress_with_fsync() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("txz")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--fsync")
+        .arg(temp_dir.path().join("test.txt.tar.xz"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), TEST_DATA_SMALL);
+    assert_eq!(
+        names_in(temp_dir.path()),
+        vec!["test.txt", "test.txt.tar.xz"]
+    );
+}
+
+#[test]
+fn test_cleanup_removes_stale_entries() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // PID 0 is never a user process, so these are always stale
+    fs::create_dir(temp_dir.path().join("jczpkg_0_1a2b_0")).unwrap();
+    create_test_file(
+        temp_dir.path().join("jczpkg_0_1a2b_0").as_path(),
+        "file.txt",
+        TEST_DATA_SMALL,
+    );
+    create_test_file(temp_dir.path(), ".jcztmp_0_1a2b_1_file.gz", b"partial");
+
+    // Names without an owner PID may be the user's and are left alone,
+    // as are unrelated files
+    fs::create_dir(temp_dir.path().join("jczpkg_18a2b3c4d5e6f7a8")).unwrap();
+    fs::create_dir(temp_dir.path().join("jczpkg_backup")).unwrap();
+    create_test_file(temp_dir.path(), ".jcztmp_notes", TEST_DATA_SMALL);
+    create_test_file(temp_dir.path(), "keep.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("cleanup")
+        .arg(temp_dir.path())
+        .assert()
+        .success();
+
+    assert_eq!(
+        names_in(temp_dir.path()),
+        vec![
+            ".jcztmp_notes",
+            "jczpkg_18a2b3c4d5e6f7a8",
+            "jczpkg_backup",
+            "keep.txt"
+        ]
+    );
+}
+
+#[test]
+fn test_cleanup_keeps_entries_of_running_processes() {
+    let temp_dir = TempDir::new().unwrap();
+    let live = format!("jczpkg_{}_1a2b_0", std::process::id());
+    fs::create_dir(temp_dir.path().join(&live)).unwrap();
+
+    jcz_command()
+        .arg("cleanup")
+        .arg(temp_dir.path())
+        .assert()
+        .success();
+
+    assert_eq!(names_in(temp_dir.path()), vec![live]);
+}
+
+#[test]
+fn test_cleanup_rejects_missing_directory() {
+    let temp_dir = TempDir::new().unwrap();
+
+    jcz_command()
+        .arg("cleanup")
+        .arg(temp_dir.path().join("missing"))
+        .assert()
+        .failure();
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Outputs named after `stem` with any of the given extensions
+fn outputs_of(dir: &Path, stem: &str, extensions: &[&str]) -> Vec<PathBuf> {
+    extensions
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+        .filter(|path| file_exists(path))
+        .collect()
+}
+
+/// Text that every format compresses well, about 1 MB of it
+fn compressible_text() -> Vec<u8> {
+    (0..100_000)
+        .map(|i| format!("line {} of the sample\n", i))
+        .collect::<String>()
+        .into_bytes()
+}
+
+#[test]
+fn test_auto_compresses_each_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let text = compressible_text();
+    let file = create_test_file(temp_dir.path(), "text.txt", &text);
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "sub/two.txt"]);
+
+    jcz_command()
+        .args(["-c", "auto"])
+        .arg(&file)
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Chose"))
+        .stderr(predicates::str::contains("text.txt: "));
+
+    // Files get a single-file format, directories a compound one
+    let outputs = outputs_of(temp_dir.path(), "text.txt", &["gz", "bz2", "xz"]);
+    assert_eq!(outputs.len(), 1);
+    let archives = outputs_of(temp_dir.path(), "data", &["tar.gz", "tar.bz2", "tar.xz"]);
+    assert_eq!(archives.len(), 1);
+
+    fs::remove_file(&file).unwrap();
+    jcz_command().arg("-d").arg(&outputs[0]).assert().success();
+    assert_eq!(read_file(&file), text);
+}
+
+#[test]
+fn test_auto_goals() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "text.txt", &compressible_text());
+
+    jcz_command()
+        .args(["compress", "-c", "auto", "--auto-goal", "fastest"])
+        .arg(&file)
+        .assert()
+        .success();
+    assert!(file_exists(&temp_dir.path().join("text.txt.gz")));
+
+    // A generous budget fits every format, so the smallest output wins
+    jcz_command()
+        .args(["-c", "auto", "--time-budget", "1m", "--dry-run"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("text.txt -> "));
+}
+
+#[test]
+fn test_auto_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_MEDIUM), ("b.txt", TEST_DATA_SMALL)],
+    );
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-c", "auto", "-a", "pkg", "--dry-run", "a.txt", "b.txt"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "collect     a.txt, b.txt -> pkg.tar.",
+        ));
+    assert!(outputs_of(temp_dir.path(), "pkg", &["tar.gz", "tar.bz2", "tar.xz"]).is_empty());
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["collect", "pkg", "-c", "auto", "a.txt", "b.txt"])
+        .assert()
+        .success();
+    assert_eq!(
+        outputs_of(temp_dir.path(), "pkg", &["tar.gz", "tar.bz2", "tar.xz"]).len(),
+        1
+    );
+}
+
+#[test]
+fn test_auto_invalid_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    for args in [
+        vec!["-c", "auto", "--auto-goal", "budget"],
+        vec![
+            "-c",
+            "auto",
+            "--auto-goal",
+            "fastest",
+            "--time-budget",
+            "2s",
+        ],
+        vec!["-c", "auto", "--time-budget", "soon"],
+        vec!["-c", "auto", "--auto-goal", "tiny"],
+        vec!["-c", "gzip", "--auto-goal", "fastest"],
+        vec!["-c", "auto", "-t", "1", "--keep-last", "2"],
+        vec!["-c", "auto", "-r"],
+    ] {
+        jcz_command().args(&args).arg(&file).assert().failure();
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::*;
+use tempfile::TempDir;
+
+#[test]
+fn test_bench_table() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_MEDIUM);
+
+    let output = jcz_command()
+        .args(["bench", "-l", "1,9"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // A header and one row per format and level
+    let table = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert!(lines[0].starts_with("FILE"));
+    for format in ["gzip", "bzip2", "xz"] {
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.contains(&format!(" {} ", format)))
+                .count(),
+            2
+        );
+    }
+
+    // Nothing is left next to the input
+    assert!(!file_exists(&temp_dir.path().join("data.txt.gz")));
+}
+
+#[test]
+fn test_bench_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_MEDIUM);
+
+    let output = jcz_command()
+        .args(["bench", "-c", "gzip,xz", "-l", "1-3", "--json"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 6);
+    for result in results {
+        assert_eq!(result["size"], TEST_DATA_MEDIUM.len() as u64);
+        assert!(result["compressed_size"].as_u64().unwrap() > 0);
+        assert!(result["compress_speed"].as_f64().unwrap() > 0.0);
+        assert!(result["compress_memory"].as_u64().unwrap() > 0);
+    }
+    assert_eq!(results[0]["format"], "gzip");
+    assert_eq!(results[3]["format"], "xz");
+}
+
+#[test]
+fn test_bench_invalid_arguments() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    for args in [
+        vec!["bench", "-l", "9-1"],
+        vec!["bench", "-l", "high"],
+        vec!["bench", "-c", "tgz"],
+        vec!["bench", "-c", "gzip", "-l", "0"],
+    ] {
+        jcz_command().args(&args).arg(&file).assert().failure();
+    }
+
+    jcz_command()
+        .arg("bench")
+        .arg(temp_dir.path())
+        .assert()
+        .failure();
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+#[test]
+fn test_checksum_sidecar_sha256() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--checksum")
+        .arg("sha256")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let sidecar = temp_dir.path().join("test.txt.gz.sha256");
+    assert!(file_exists(&sidecar));
+    let content = fs::read_to_string(&sidecar).unwrap();
+    assert!(content.ends_with("  test.txt.gz\n"));
+
+    // The sidecar is understood by sha256sum
+    let status = StdCommand::new("sha256sum")
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("test.txt.gz.sha256")
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+}
+
+#[test]
+fn test_checksum_batch_sums_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--checksum")
+        .arg("blake3")
+        .arg(&files[0])
+        .arg(&files[1])
+        .assert()
+        .success();
+
+    let sums = fs::read_to_string(temp_dir.path().join("BLAKE3SUMS")).unwrap();
+    let names: Vec<&str> = sums
+        .lines()
+        .map(|l| l.split_whitespace().nth(1).unwrap())
+        .collect();
+    assert_eq!(names, vec!["a.txt.tar.gz", "b.txt.tar.gz"]);
+    assert!(sums
+        .lines()
+        .all(|l| l.split_whitespace().next().unwrap().len() == 64));
+    assert!(!file_exists(&temp_dir.path().join("a.txt.tar.gz.blake3")));
+
+    // The intermediate TAR gets no checksum
+    assert!(!sums.contains("a.txt.tar\n"));
+
+    fs::remove_file(&files[0]).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg("--verify-checksum")
+        .arg(temp_dir.path().join("a.txt.tar.gz"))
+        .assert()
+        .success();
+    assert_eq!(read_file(&files[0]), TEST_DATA_SMALL);
+}
+
+#[test]
+fn test_verify_checksum_mismatch_refuses_extraction() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("--checksum")
+        .arg("sha256")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+
+    // Replace the archive with a valid one of different content
+    let other = create_test_file(temp_dir.path(), "other.txt", TEST_DATA_SMALL);
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg(&other)
+        .assert()
+        .success();
+    fs::rename(
+        temp_dir.path().join("other.txt.xz"),
+        temp_dir.path().join("test.txt.xz"),
+    )
+    .unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--verify-checksum")
+        .arg(temp_dir.path().join("test.txt.xz"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Checksum mismatch"));
+
+    assert!(!test_file.exists());
+
+    // Without the check the archive is extracted as usual
+    jcz_command()
+        .arg("-d")
+        .arg(temp_dir.path().join("test.txt.xz"))
+        .assert()
+        .success();
+    assert!(test_file.exists());
+}
+
+#[test]
+fn test_verify_checksum_missing_sidecar() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--verify-checksum")
+        .arg(temp_dir.path().join("test.txt.bz2"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No checksum found"));
+
+    assert!(!test_file.exists());
+}
+
+#[test]
+fn test_checksum_split_and_encrypted_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let data: Vec<u8> = (0..50_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "checksum")
+        .arg("-c")
+        .arg("gzip")
+        .arg("--encrypt")
+        .arg("--split-size")
+        .arg("4K")
+        .arg("--checksum")
+        .arg("sha256")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    // One sidecar covers the stored bytes of the whole set
+    let sidecar = temp_dir.path().join("data.bin.gz.age.sha256");
+    assert!(file_exists(&sidecar));
+    assert!(!file_exists(
+        &temp_dir.path().join("data.bin.gz.age.001.sha256")
+    ));
+
+    fs::remove_file(&test_file).unwrap();
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "checksum")
+        .arg("-d")
+        .arg("--verify-checksum")
+        .arg(temp_dir.path().join("data.bin.gz.age.001"))
+        .assert()
+        .success();
+    assert_eq!(read_file(&test_file), data);
+}
+
+#[test]
+fn test_checksum_split_output_checks_with_sha256sum() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "a.bin", &noise(250 * 1024, 1));
+
+    jcz_command()
+        .args(["-c", "gzip", "--split-size", "100K", "--checksum", "sha256"])
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    // The sidecar lists each volume as it is stored
+    let content = fs::read_to_string(temp_dir.path().join("a.bin.gz.sha256")).unwrap();
+    let names: Vec<&str> = content
+        .lines()
+        .map(|l| l.split_whitespace().nth(1).unwrap())
+        .collect();
+    assert_eq!(names, ["a.bin.gz.001", "a.bin.gz.002", "a.bin.gz.003"]);
+
+    let output = StdCommand::new("sha256sum")
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("a.bin.gz.sha256")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // A damaged volume is named in the error
+    let mut volume = read_file(&temp_dir.path().join("a.bin.gz.002"));
+    volume[100] ^= 0xff;
+    fs::write(temp_dir.path().join("a.bin.gz.002"), volume).unwrap();
+    fs::remove_file(&test_file).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg("--verify-checksum")
+        .arg(temp_dir.path().join("a.bin.gz.001"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("a.bin.gz.002"));
+    assert!(!test_file.exists());
+}
+
+#[test]
+fn test_remove_archive_removes_sidecar() {
+    for (algorithm, split, archive) in [
+        ("sha256", None, "data.bin.gz"),
+        ("blake3", Some("4K"), "data.bin.gz.001"),
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let data = noise(10_000, 3);
+        let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+        let mut cmd = jcz_command();
+        cmd.args(["-c", "gzip", "--remove-source", "--checksum", algorithm]);
+        if let Some(size) = split {
+            cmd.arg("--split-size").arg(size);
+        }
+        cmd.arg(&test_file).assert().success();
+        assert!(file_exists(
+            &temp_dir.path().join(format!("data.bin.gz.{}", algorithm))
+        ));
+
+        // The sidecar goes with the archive it describes
+        jcz_command()
+            .arg("-d")
+            .arg("--remove-archive")
+            .arg(temp_dir.path().join(archive))
+            .assert()
+            .success();
+
+        assert_eq!(names_in(temp_dir.path()), vec!["data.bin"], "{}", algorithm);
+        assert_eq!(read_file(&test_file), data);
+    }
+}
+
+#[test]
+fn test_invalid_checksum_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--checksum")
+        .arg("md5")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--verify-checksum")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&temp_dir.path().join("test.txt.gz")));
+}
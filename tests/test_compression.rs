@@ -1,3 +1,5 @@
+#![allow(clippy::unnecessary_map_or)]
+
 mod common;
 
 use common::*;
@@ -0,0 +1,149 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_project_profile() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_dir_structure(&temp_dir.path().join("data"), &["a.txt", "sub/b.txt"]);
+    fs::write(
+        temp_dir.path().join(".jcz.toml"),
+        r#"
+[profiles.backup]
+command = "txz"
+level = 9
+timestamp = 1
+move-to = "archives"
+keep-last = 2
+"#,
+    )
+    .unwrap();
+
+    // Profiles also apply from a subdirectory of the project
+    let work = temp_dir.path().join("data");
+    jcz_command()
+        .current_dir(&work)
+        .arg("--profile")
+        .arg("backup")
+        .arg("sub")
+        .assert()
+        .success();
+
+    let archives = names_in(&temp_dir.path().join("archives"));
+    assert_eq!(archives.len(), 1);
+    assert!(archives[0].starts_with("sub_") && archives[0].ends_with(".tar.xz"));
+
+    jcz_command()
+        .current_dir(&work)
+        .env("JCZ_PROFILE", "nightly")
+        .arg("sub")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("No profile nightly"));
+}
+
+#[test]
+fn test_settings_precedence() {
+    let temp_dir = TempDir::new().unwrap();
+    let user_config = temp_dir.path().join("user.toml");
+    fs::write(&user_config, "command = \"bzip2\"\nlevel = 1\n").unwrap();
+    let project = temp_dir.path().join("project");
+    fs::create_dir(&project).unwrap();
+    let test_file = create_test_file(&project, "data.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .current_dir(&project)
+        .env("JCZ_CONFIG", &user_config)
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert!(file_exists(&project.join("data.txt.bz2")));
+
+    // The project file overrides the user file
+    fs::write(project.join(".jcz.toml"), "command = \"xz\"\n").unwrap();
+    jcz_command()
+        .current_dir(&project)
+        .env("JCZ_CONFIG", &user_config)
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert!(file_exists(&project.join("data.txt.xz")));
+
+    // Environment variables override both, and options override everything
+    jcz_command()
+        .current_dir(&project)
+        .env("JCZ_CONFIG", &user_config)
+        .env("JCZ_COMMAND", "gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert!(file_exists(&project.join("data.txt.gz")));
+
+    jcz_command()
+        .current_dir(&project)
+        .env("JCZ_CONFIG", &user_config)
+        .env("JCZ_COMMAND", "gzip")
+        .args(["-c", "tar"])
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert!(file_exists(&project.join("data.txt.tar")));
+}
+
+#[test]
+fn test_compression_settings_skip_decompression() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+    jcz_command()
+        .args(["-c", "gzip"])
+        .arg(&test_file)
+        .assert()
+        .success();
+    fs::remove_file(&test_file).unwrap();
+
+    // Retention and timestamps would be rejected with -d
+    fs::write(
+        temp_dir.path().join(".jcz.toml"),
+        "command = \"txz\"\ntimestamp = 2\nkeep-last = 3\nutc = true\n",
+    )
+    .unwrap();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-d")
+        .arg("data.txt.gz")
+        .assert()
+        .success();
+    assert_eq!(read_file(&test_file), TEST_DATA_SMALL);
+}
+
+#[test]
+fn test_invalid_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    fs::write(temp_dir.path().join(".jcz.toml"), "levle = 9\n").unwrap();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(".jcz.toml"))
+        .stderr(predicates::str::contains("levle"));
+
+    fs::remove_file(temp_dir.path().join(".jcz.toml")).unwrap();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .env("JCZ_CONFIG", temp_dir.path().join("missing.toml"))
+        .arg(&test_file)
+        .assert()
+        .failure();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .env("JCZ_LEVEL", "high")
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("environment"));
+}
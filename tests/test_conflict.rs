@@ -0,0 +1,376 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+// Compression conflict tests
+
+#[test]
+fn test_compress_existing_output_fails_without_terminal() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let existing = create_test_file(temp_dir.path(), "test.txt.gz", b"existing");
+
+    // Default policy is "ask", which degrades to "fail" when stdin is not a terminal
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert_eq!(read_file(&existing), b"existing");
+}
+
+#[test]
+fn test_compress_on_conflict_skip() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let existing = create_test_file(temp_dir.path(), "test.txt.gz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--on-conflict")
+        .arg("skip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&existing), b"existing");
+}
+
+#[test]
+fn test_compress_on_conflict_overwrite() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let existing = create_test_file(temp_dir.path(), "test.txt.gz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--on-conflict")
+        .arg("overwrite")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(verify_decompressed_content(&existing, TEST_DATA_SMALL));
+}
+
+#[test]
+fn test_compress_force_overwrites() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let existing = create_test_file(temp_dir.path(), "test.txt.xz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-f")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(verify_decompressed_content(&existing, TEST_DATA_SMALL));
+}
+
+#[test]
+fn test_compress_on_conflict_rename() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let existing = create_test_file(temp_dir.path(), "test.txt.gz", b"existing");
+    create_test_file(temp_dir.path(), "test.txt (1).gz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--on-conflict")
+        .arg("rename")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&existing), b"existing");
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("test.txt (2).gz"),
+        TEST_DATA_SMALL
+    ));
+}
+
+#[test]
+fn test_compress_on_conflict_rename_same_name_in_parallel() {
+    let temp_dir = TempDir::new().unwrap();
+    let dest = temp_dir.path().join("dest");
+    fs::create_dir(&dest).unwrap();
+    create_test_file(&dest, "test.txt.gz", b"existing");
+
+    // Inputs with the same name land on the same output and must not share a renamed one
+    let inputs: Vec<_> = (0..8)
+        .map(|i| {
+            let dir = temp_dir.path().join(format!("in{}", i));
+            fs::create_dir(&dir).unwrap();
+            create_test_file(&dir, "test.txt", format!("input {}", i).as_bytes())
+        })
+        .collect();
+
+    jcz_command()
+        .args(["-c", "gzip", "--on-conflict", "rename", "-C"])
+        .arg(&dest)
+        .args(&inputs)
+        .assert()
+        .success();
+
+    let mut expected = vec!["test.txt.gz".to_string()];
+    expected.extend((1..=8).map(|n| format!("test.txt ({}).gz", n)));
+    expected.sort();
+    assert_eq!(names_in(&dest), expected);
+}
+
+#[test]
+fn test_compress_on_conflict_rename_split() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &noise(150 * 1024, 1));
+    create_test_file(temp_dir.path(), "data.bin.gz", b"existing");
+
+    // The reserved name gives way to the volumes
+    jcz_command()
+        .args([
+            "-c",
+            "gzip",
+            "--split-size",
+            "100K",
+            "--on-conflict",
+            "rename",
+        ])
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(
+        names_in(temp_dir.path()),
+        [
+            "data.bin",
+            "data.bin (1).gz.001",
+            "data.bin (1).gz.002",
+            "data.bin.gz"
+        ]
+    );
+}
+
+#[test]
+fn test_failed_rename_releases_reserved_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+    create_test_file(temp_dir.path(), "test.txt.xz", b"existing");
+
+    // A truncated archive fails to convert after the new name was claimed
+    let archive = temp_dir.path().join("test.txt.gz");
+    let status = std::process::Command::new("gzip")
+        .arg("-k")
+        .arg(&test_file)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::write(&archive, &read_file(&archive)[..10]).unwrap();
+
+    jcz_command()
+        .args(["--convert", "xz", "--on-conflict", "rename"])
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    assert_eq!(
+        names_in(temp_dir.path()),
+        ["test.txt", "test.txt.gz", "test.txt.xz"]
+    );
+}
+
+#[test]
+fn test_compound_on_conflict_rename() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    create_test_file(temp_dir.path(), "test.txt.tar.gz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--on-conflict")
+        .arg("rename")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(file_exists(&temp_dir.path().join("test.txt (1).tar.gz")));
+}
+
+#[test]
+fn test_compress_on_conflict_fail() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    create_test_file(temp_dir.path(), "test.txt.bz2", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg("--on-conflict")
+        .arg("fail")
+        .arg(&test_file)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_invalid_conflict_policy() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--on-conflict")
+        .arg("invalid")
+        .arg(&test_file)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_force_conflicts_with_on_conflict() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-f")
+        .arg("--on-conflict")
+        .arg("skip")
+        .arg(&test_file)
+        .assert()
+        .failure();
+}
+
+// Decompression conflict tests
+
+#[test]
+fn test_decompress_on_conflict_rename() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--on-conflict")
+        .arg("rename")
+        .arg(temp_dir.path().join("test.txt.gz"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), TEST_DATA_SMALL);
+    assert_eq!(
+        read_file(&temp_dir.path().join("test (1).txt")),
+        TEST_DATA_SMALL
+    );
+}
+
+#[test]
+fn test_decompress_existing_output_fails_without_terminal() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::write(&test_file, b"modified").unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg(temp_dir.path().join("test.txt.gz"))
+        .assert()
+        .failure();
+
+    assert_eq!(read_file(&test_file), b"modified");
+}
+
+#[test]
+fn test_decompress_on_conflict_skip() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::write(&test_file, b"modified").unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--on-conflict")
+        .arg("skip")
+        .arg(temp_dir.path().join("test.txt.gz"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), b"modified");
+}
+
+// Collection conflict tests
+
+#[test]
+fn test_collect_on_conflict_skip() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = create_test_file(temp_dir.path(), "file1.txt", b"Content 1");
+    let file2 = create_test_file(temp_dir.path(), "file2.txt", b"Content 2");
+    let existing = create_test_file(temp_dir.path(), "archive.tar.gz", b"existing");
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("tgz")
+        .arg("-A")
+        .arg("archive")
+        .arg("--on-conflict")
+        .arg("skip")
+        .arg(&file1)
+        .arg(&file2)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&existing), b"existing");
+}
+
+#[test]
+fn test_collect_on_conflict_fail() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = create_test_file(temp_dir.path(), "file1.txt", b"Content 1");
+    let file2 = create_test_file(temp_dir.path(), "file2.txt", b"Content 2");
+    create_test_file(temp_dir.path(), "archive.tar.xz", b"existing");
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("txz")
+        .arg("-A")
+        .arg("archive")
+        .arg("--on-conflict")
+        .arg("fail")
+        .arg(&file1)
+        .arg(&file2)
+        .assert()
+        .failure();
+}
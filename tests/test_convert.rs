@@ -0,0 +1,206 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+/// Decompress a file with a system tool and return the raw payload
+fn decompress_payload(tool: &str, path: &std::path::Path) -> Vec<u8> {
+    let output = StdCommand::new(tool)
+        .arg("-dc")
+        .arg(path)
+        .output()
+        .expect("Failed to run decompressor");
+    assert!(output.status.success());
+    output.stdout
+}
+
+#[test]
+fn test_convert_tbz2_to_txz_preserves_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tbz2")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let old = temp_dir.path().join("data.tar.bz2");
+    let old_tar = decompress_payload("bzip2", &old);
+
+    jcz_command()
+        .arg("--convert")
+        .arg("txz")
+        .arg(&old)
+        .assert()
+        .success();
+
+    let new = temp_dir.path().join("data.tar.xz");
+    assert!(file_exists(&new));
+    assert!(file_exists(&old), "Original should remain");
+    assert_eq!(decompress_payload("xz", &new), old_tar);
+}
+
+#[test]
+fn test_convert_single_file_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("--convert")
+        .arg("bzip2")
+        .arg(temp_dir.path().join("test.txt.gz"))
+        .assert()
+        .success();
+
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("test.txt.bz2"),
+        TEST_DATA_MEDIUM
+    ));
+}
+
+#[test]
+fn test_convert_multiple_files_with_verify_and_remove() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut archives = Vec::new();
+    for name in ["one.txt", "two.txt", "three.txt"] {
+        let file = create_test_file(temp_dir.path(), name, name.as_bytes());
+        jcz_command()
+            .arg("-c")
+            .arg("tgz")
+            .arg(&file)
+            .assert()
+            .success();
+        fs::remove_file(&file).unwrap();
+        archives.push(temp_dir.path().join(format!("{}.tar.gz", name)));
+    }
+
+    jcz_command()
+        .arg("--convert")
+        .arg("tbz2")
+        .arg("--verify")
+        .arg("--remove-source")
+        .args(&archives)
+        .assert()
+        .success();
+
+    for archive in &archives {
+        assert!(!archive.exists(), "Old archive should be removed");
+    }
+
+    let converted = temp_dir.path().join("two.txt.tar.bz2");
+    jcz_command().arg("-d").arg(&converted).assert().success();
+    assert_eq!(read_file(&temp_dir.path().join("two.txt")), b"two.txt");
+}
+
+#[test]
+fn test_convert_into_move_to() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tar")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("--convert")
+        .arg("txz")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(temp_dir.path().join("test.txt.tar"))
+        .assert()
+        .success();
+
+    assert!(file_exists(&output_dir.join("test.txt.tar.xz")));
+}
+
+#[test]
+fn test_convert_corrupt_input_keeps_original() {
+    let temp_dir = TempDir::new().unwrap();
+    let corrupt = create_test_file(temp_dir.path(), "bad.tar.gz", b"not gzip data");
+
+    jcz_command()
+        .arg("--convert")
+        .arg("txz")
+        .arg("--remove-source")
+        .arg(&corrupt)
+        .assert()
+        .failure();
+
+    assert!(file_exists(&corrupt));
+    assert!(!temp_dir.path().join("bad.tar.xz").exists());
+}
+
+#[test]
+fn test_convert_compound_requires_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("--convert")
+        .arg("txz")
+        .arg(temp_dir.path().join("test.txt.gz"))
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_convert_same_format_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("test.txt.xz");
+    let before = read_file(&archive);
+
+    jcz_command()
+        .arg("--convert")
+        .arg("xz")
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    assert_eq!(read_file(&archive), before);
+}
+
+#[test]
+fn test_convert_invalid_target() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt.gz", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("--convert")
+        .arg("tar")
+        .arg(&test_file)
+        .assert()
+        .failure();
+}
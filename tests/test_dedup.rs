@@ -0,0 +1,266 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Create `data/` below `root` with a large file and a nested small one
+fn create_data(root: &Path) -> PathBuf {
+    create_tree(
+        root,
+        &[
+            ("big.bin", &noise(2 * 1024 * 1024, 1)),
+            ("sub/a.txt", TEST_DATA_SMALL),
+        ],
+    )
+}
+
+/// Total size of the packs in a repository
+fn packs_size(repo: &Path) -> u64 {
+    fs::read_dir(repo.join("packs"))
+        .unwrap()
+        .map(|e| e.unwrap().metadata().unwrap().len())
+        .sum()
+}
+
+fn store(repo: &Path, name: &str, inputs: &[&Path]) {
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--dedup")
+        .arg(repo)
+        .arg("-a")
+        .arg(name)
+        .args(inputs)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_dedup_stores_only_new_data() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_data(temp_dir.path());
+    let repo = temp_dir.path().join("repo");
+
+    store(&repo, "day1", &[&data_dir]);
+    let first = packs_size(&repo);
+    assert!(first > 1024 * 1024);
+
+    // A small change adds a pack with only the chunks around it
+    let mut content = fs::read(data_dir.join("big.bin")).unwrap();
+    content[1024 * 1024] ^= 0xff;
+    fs::write(data_dir.join("big.bin"), &content).unwrap();
+    store(&repo, "day2", &[&data_dir]);
+    let added = packs_size(&repo) - first;
+    assert!(added < first / 4, "{} new bytes stored", added);
+
+    // Unchanged input adds no pack at all
+    store(&repo, "day3", &[&data_dir]);
+    assert_eq!(names_in(&repo.join("packs")).len(), 2);
+    assert_eq!(
+        names_in(&repo.join("snapshots")),
+        vec!["day1.json", "day2.json", "day3.json"]
+    );
+
+    jcz_command()
+        .arg("dedup")
+        .arg("list")
+        .arg(&repo)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("day2"));
+    jcz_command()
+        .arg("dedup")
+        .arg("list")
+        .arg(&repo)
+        .arg("day1")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("day1/data/sub/a.txt"));
+}
+
+#[test]
+fn test_dedup_extract() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_data(temp_dir.path());
+    let original = fs::read(data_dir.join("big.bin")).unwrap();
+    let repo = temp_dir.path().join("repo");
+
+    // A single input is stored under its own name
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("--dedup")
+        .arg(&repo)
+        .arg(&data_dir)
+        .assert()
+        .success();
+    fs::write(data_dir.join("sub/a.txt"), TEST_DATA_MEDIUM).unwrap();
+    store(&repo, "later", &[&data_dir]);
+
+    let out = temp_dir.path().join("out");
+    jcz_command()
+        .arg("dedup")
+        .arg("extract")
+        .arg(&repo)
+        .arg("data")
+        .arg("-C")
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(fs::read(out.join("data/big.bin")).unwrap(), original);
+    assert_eq!(read_file(&out.join("data/sub/a.txt")), TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("dedup")
+        .arg("extract")
+        .arg(&repo)
+        .arg("later")
+        .arg("-C")
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(
+        read_file(&out.join("later/data/sub/a.txt")),
+        TEST_DATA_MEDIUM
+    );
+
+    // Extracted entries are never replaced
+    jcz_command()
+        .arg("dedup")
+        .arg("extract")
+        .arg(&repo)
+        .arg("data")
+        .arg("-C")
+        .arg(&out)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_dedup_extract_reordered_chunks() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir_all(&data_dir).unwrap();
+    let (first, second) = (noise(1024 * 1024, 2), noise(1024 * 1024, 3));
+    fs::write(data_dir.join("big.bin"), [&first[..], &second[..]].concat()).unwrap();
+    let repo = temp_dir.path().join("repo");
+    store(&repo, "day1", &[&data_dir]);
+
+    // The same data in the other order reads the first pack out of order
+    let swapped = [&second[..], &first[..]].concat();
+    fs::write(data_dir.join("big.bin"), &swapped).unwrap();
+    store(&repo, "day2", &[&data_dir]);
+
+    let out = temp_dir.path().join("out");
+    jcz_command()
+        .arg("dedup")
+        .arg("extract")
+        .arg(&repo)
+        .arg("day2")
+        .arg("-C")
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(fs::read(out.join("day2/data/big.bin")).unwrap(), swapped);
+
+    // Packs are streamed, nothing is left decompressed in the repository
+    assert_eq!(
+        names_in(&repo),
+        ["dedup.json", "index", "packs", "snapshots"]
+    );
+}
+
+#[test]
+fn test_dedup_verify_detects_missing_pack() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_data(temp_dir.path());
+    let repo = temp_dir.path().join("repo");
+
+    store(&repo, "day1", &[&data_dir]);
+    fs::write(data_dir.join("new.txt"), TEST_DATA_MEDIUM).unwrap();
+    store(&repo, "day2", &[&data_dir]);
+
+    jcz_command()
+        .arg("dedup")
+        .arg("verify")
+        .arg(&repo)
+        .assert()
+        .success();
+
+    // Remove the pack holding only the chunks day2 added
+    let index = names_in(&repo.join("index"));
+    let newest = index
+        .iter()
+        .find(|name| {
+            let content = fs::read_to_string(repo.join("index").join(name)).unwrap();
+            content.matches("\"hash\"").count() == 1
+        })
+        .unwrap();
+    let pack = newest.replace(".json", ".pack.gz");
+    fs::remove_file(repo.join("packs").join(pack)).unwrap();
+
+    jcz_command()
+        .arg("dedup")
+        .arg("verify")
+        .arg(&repo)
+        .arg("day1")
+        .assert()
+        .success();
+    jcz_command()
+        .arg("dedup")
+        .arg("verify")
+        .arg(&repo)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("new.txt"));
+}
+
+#[test]
+fn test_invalid_dedup_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_data(temp_dir.path());
+    let other = create_test_file(temp_dir.path(), "other.txt", TEST_DATA_SMALL);
+    let repo = temp_dir.path().join("repo");
+
+    let invalid: [&[&str]; 4] = [
+        &["-c", "tgz"],
+        &["-c", "gzip", "-d"],
+        &["-c", "gzip", "--encrypt"],
+        &["-c", "gzip", "--keep-last", "2", "-t", "1"],
+    ];
+    for args in invalid {
+        jcz_command()
+            .args(args)
+            .arg("--dedup")
+            .arg(&repo)
+            .arg(&data_dir)
+            .assert()
+            .failure();
+    }
+
+    jcz_command()
+        .args(["-c", "gzip", "--dedup"])
+        .arg(&repo)
+        .arg(&data_dir)
+        .arg(&other)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("-a or -A"));
+    assert!(!dir_exists(&repo));
+
+    // Only repositories are opened, and only empty directories become one
+    jcz_command()
+        .args(["-c", "gzip", "--dedup"])
+        .arg(&data_dir)
+        .arg(&other)
+        .assert()
+        .failure();
+    jcz_command()
+        .arg("dedup")
+        .arg("list")
+        .arg(&data_dir)
+        .assert()
+        .failure();
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_dry_run_writes_nothing() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+    let dest = temp_dir.path().join("out/sub");
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-C")
+        .arg(&dest)
+        .arg("--remove-source")
+        .arg("--dry-run")
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("mkdir"))
+        .stdout(predicates::str::contains("out/sub/data.txt.xz"))
+        .stdout(predicates::str::contains("remove"));
+
+    // No destination directory, probe file, output or removal
+    assert_eq!(names_in(temp_dir.path()), vec!["data.txt"]);
+}
+
+#[test]
+fn test_dry_run_reports_conflicts() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+    fs::write(temp_dir.path().join("b.txt.gz"), b"old").unwrap();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--on-conflict")
+        .arg("rename")
+        .arg("--dry-run")
+        .args(&files)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("a.txt.gz\n"))
+        .stdout(predicates::str::contains("b.txt (1).gz (exists: rename)"));
+
+    // A dry run never prompts, even when the policy is to ask
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--dry-run")
+        .arg(&files[1])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("(exists: ask)"));
+
+    assert_eq!(read_file(&temp_dir.path().join("b.txt.gz")), b"old");
+    assert!(!file_exists(&temp_dir.path().join("a.txt.gz")));
+}
+
+#[test]
+fn test_dry_run_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+
+    let output = jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-c", "tbz2", "-a", "pkg", "--timestamp-format", "%Y"])
+        .args(["--dry-run", "--json"])
+        .args(&files)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let outputs = plan["outputs"].as_array().unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0]["action"], "collect");
+    assert_eq!(outputs[0]["inputs"].as_array().unwrap().len(), 2);
+    let name = outputs[0]["output"].as_str().unwrap();
+    assert!(name.starts_with("pkg_2") && name.ends_with(".tar.bz2"));
+    assert!(plan["create_dirs"].as_array().unwrap().is_empty());
+
+    assert_eq!(names_in(temp_dir.path()), vec!["a.txt", "b.txt"]);
+}
+
+#[test]
+fn test_dry_run_decompress_and_recursive() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "sub/two.txt"]);
+    let archive = temp_dir.path().join("data.tar.gz");
+    fs::write(&archive, b"not read by a dry run").unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("-C")
+        .arg(temp_dir.path().join("restored"))
+        .arg("--dry-run")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("restored/data\n"));
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-r")
+        .arg("-C")
+        .arg(temp_dir.path().join("mirror"))
+        .arg("--dry-run")
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("mirror/data/sub/two.txt.gz"));
+
+    assert!(!dir_exists(&temp_dir.path().join("restored")));
+    assert!(!dir_exists(&temp_dir.path().join("mirror")));
+
+    // --json only describes a dry run
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--json")
+        .arg(&archive)
+        .assert()
+        .failure();
+}
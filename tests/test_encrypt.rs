@@ -0,0 +1,315 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Fixed key pair for recipient mode
+const IDENTITY: &str = "AGE-SECRET-KEY-1SYQPS4SUNKW8AQ9MFHYJCHSSEGA2D79D9WUVWYG4C53NRA4LUD7S5CEN5E";
+const RECIPIENT: &str = "age1sxdpvqvu6tj4ju4zjc6cjhpf02550c2h0gkk4jlv9yrnj4lu9agqmel267";
+
+/// Header every age file starts with
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1";
+
+fn passphrase_file(dir: &Path, passphrase: &str) -> std::path::PathBuf {
+    create_test_file(
+        dir,
+        "passphrase.txt",
+        format!("{}\n", passphrase).as_bytes(),
+    )
+}
+
+#[test]
+fn test_encrypt_compound_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    create_test_file(&data_dir, "file.txt", TEST_DATA_MEDIUM);
+    let pass = passphrase_file(temp_dir.path(), "correct horse");
+
+    jcz_command()
+        .arg("-c")
+        .arg("txz")
+        .arg("--encrypt")
+        .arg("--passphrase-file")
+        .arg(&pass)
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("data.tar.xz.age");
+    assert!(file_exists(&archive));
+    assert!(!file_exists(&temp_dir.path().join("data.tar.xz")));
+    assert!(read_file(&archive).starts_with(AGE_HEADER));
+
+    fs::remove_dir_all(&data_dir).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--passphrase-file")
+        .arg(&pass)
+        .arg(&archive)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&data_dir.join("file.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_encrypt_passphrase_from_env() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "from the environment")
+        .arg("-c")
+        .arg("gzip")
+        .arg("--encrypt")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "from the environment")
+        .arg("-d")
+        .arg(temp_dir.path().join("test.txt.gz.age"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), TEST_DATA_SMALL);
+}
+
+#[test]
+fn test_decrypt_recursive() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    let files = create_test_dir_structure(&data_dir, &["one.txt", "sub/two.txt"]);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "from the environment")
+        .args(["-c", "xz", "-r", "--encrypt", "--remove-source"])
+        .arg(&data_dir)
+        .assert()
+        .success();
+    assert!(file_exists(&data_dir.join("sub/two.txt.xz.age")));
+
+    // Archives found below the directory get the passphrase too
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "from the environment")
+        .args(["-d", "-r"])
+        .arg(&data_dir)
+        .assert()
+        .success();
+    assert_eq!(read_file(&files[1]), b"Content of sub/two.txt");
+}
+
+#[test]
+fn test_decrypt_wrong_passphrase_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "right")
+        .arg("-c")
+        .arg("bzip2")
+        .arg("--encrypt")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "wrong")
+        .arg("-d")
+        .arg(temp_dir.path().join("test.txt.bz2.age"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Decryption of"));
+
+    assert!(!test_file.exists());
+}
+
+#[test]
+fn test_encrypt_to_recipient() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+    let identity = create_test_file(
+        temp_dir.path(),
+        "key.txt",
+        format!("{}\n", IDENTITY).as_bytes(),
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--recipient")
+        .arg(RECIPIENT)
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("test.txt.tar.gz.age");
+    assert!(file_exists(&archive));
+    fs::remove_file(&test_file).unwrap();
+
+    // A passphrase cannot open an archive encrypted to a public key
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "irrelevant")
+        .arg("-d")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--identity"));
+
+    jcz_command()
+        .arg("-d")
+        .arg("--identity")
+        .arg(&identity)
+        .arg(&archive)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_encrypt_split_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let data: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "volumes")
+        .arg("-c")
+        .arg("xz")
+        .arg("--encrypt")
+        .arg("--split-size")
+        .arg("8K")
+        .arg("--remove-source")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(!test_file.exists());
+    let first = temp_dir.path().join("data.bin.xz.age.001");
+    assert!(file_exists(&first));
+    assert!(file_exists(&temp_dir.path().join("data.bin.xz.age.002")));
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "volumes")
+        .arg("-d")
+        .arg(&first)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&test_file), data);
+}
+
+#[test]
+fn test_encrypt_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .env("JCZ_PASSPHRASE", "bundle")
+        .arg("-c")
+        .arg("tgz")
+        .arg("-A")
+        .arg("bundle")
+        .arg("--encrypt")
+        .arg(&files[0])
+        .arg(&files[1])
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("bundle.tar.gz.age");
+    assert!(file_exists(&archive));
+    assert!(!file_exists(&temp_dir.path().join("bundle.tar.gz")));
+
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "bundle")
+        .arg("-d")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&archive)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&output_dir.join("b.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_tampered_archive_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "tamper")
+        .arg("-c")
+        .arg("gzip")
+        .arg("--encrypt")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+    let archive = temp_dir.path().join("test.txt.gz.age");
+    let mut content = read_file(&archive);
+    let last = content.len() - 1;
+    content[last] ^= 0xff;
+    fs::write(&archive, content).unwrap();
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "tamper")
+        .arg("-d")
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    assert!(!test_file.exists());
+}
+
+#[test]
+fn test_invalid_encryption_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--recipient")
+        .arg("age1notakey")
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid recipient"));
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "x")
+        .arg("-d")
+        .arg("--encrypt")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--identity")
+        .arg(&test_file)
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&temp_dir.path().join("test.txt.gz")));
+}
@@ -321,10 +321,11 @@ fn test_already_compressed_file() {
 
     let compressed_file = temp_dir.path().join("test.txt.gz");
 
-    // Try to compress the .gz file (should work, creating .gz.gz)
+    // Compress the .gz file again when forced (creating .gz.gz)
     jcz_command()
         .arg("-c")
         .arg("gzip")
+        .arg("--force-compress")
         .arg(&compressed_file)
         .assert()
         .success();
@@ -0,0 +1,136 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_exclude_tar_and_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(
+        &data_dir,
+        &[
+            "keep.txt",
+            "app.log",
+            "cache/blob.bin",
+            "sub/notes.txt",
+            "sub/old.log",
+        ],
+    );
+
+    jcz_command()
+        .args(["-c", "tar", "--exclude", "*.log", "--exclude", "cache"])
+        .arg(&data_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        tar_members(&temp_dir.path().join("data.tar")),
+        ["data", "data/keep.txt", "data/sub", "data/sub/notes.txt"]
+    );
+
+    // Collections leave out excluded inputs and entries below directories
+    let notes = create_test_file(temp_dir.path(), "notes.txt", TEST_DATA_SMALL);
+    let trace = create_test_file(temp_dir.path(), "trace.log", TEST_DATA_SMALL);
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args([
+            "-c",
+            "tgz",
+            "-A",
+            "bundle",
+            "--manifest",
+            "--exclude",
+            "*.log",
+        ])
+        .args(["--exclude", "sub/*"])
+        .arg(&data_dir)
+        .arg(&notes)
+        .arg(&trace)
+        .assert()
+        .success();
+    assert_eq!(
+        tar_members(&temp_dir.path().join("bundle.tar.gz")),
+        [
+            "MANIFEST.json",
+            "data",
+            "data/cache",
+            "data/cache/blob.bin",
+            "data/keep.txt",
+            "data/sub",
+            "notes.txt"
+        ]
+    );
+}
+
+#[test]
+fn test_exclude_recursive() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "two.log", "build/out.txt"]);
+
+    jcz_command()
+        .args([
+            "-c",
+            "gzip",
+            "-r",
+            "--exclude",
+            "*.log",
+            "--exclude",
+            "build",
+        ])
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(verify_decompressed_content(
+        &data_dir.join("one.txt.gz"),
+        b"Content of one.txt"
+    ));
+    assert!(file_exists(&data_dir.join("two.log")));
+    assert!(!file_exists(&data_dir.join("two.log.gz")));
+    assert!(!file_exists(&data_dir.join("build/out.txt.gz")));
+}
+
+#[test]
+fn test_exclude_setting() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["keep.txt", "skip.tmp"]);
+    fs::write(
+        temp_dir.path().join(".jcz.toml"),
+        "[profiles.backup]\ncommand = \"tgz\"\nexclude = [\"*.tmp\"]\n",
+    )
+    .unwrap();
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["--profile", "backup", "data"])
+        .assert()
+        .success();
+
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(temp_dir.path().join("data.tar.gz"))
+        .output()
+        .unwrap();
+    let members = String::from_utf8_lossy(&output.stdout);
+    assert!(members.contains("data/keep.txt"));
+    assert!(!members.contains("skip.tmp"));
+
+    // Removing a directory would lose the entries left out of its archive
+    jcz_command()
+        .args(["-c", "tgz", "--exclude", "*.tmp", "--remove-source", "-f"])
+        .arg(&data_dir)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "--exclude cannot be used with --remove-source, except with -r",
+        ));
+    jcz_command()
+        .args(["-d", "--exclude", "*.tmp"])
+        .arg(temp_dir.path().join("data.tar.gz"))
+        .assert()
+        .failure();
+}
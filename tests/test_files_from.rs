@@ -0,0 +1,101 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_files_from_stdin() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b c.txt", TEST_DATA_MEDIUM)],
+    );
+
+    // Blank lines are ignored, and listed inputs add to the command line
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-c", "gzip", "-T", "-"])
+        .write_stdin("a.txt\n\nb c.txt\n")
+        .assert()
+        .success();
+    assert!(file_exists(&temp_dir.path().join("a.txt.gz")));
+    assert!(file_exists(&temp_dir.path().join("b c.txt.gz")));
+}
+
+#[test]
+fn test_files_from_null_separated() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[
+            ("one.txt", TEST_DATA_SMALL),
+            ("two\nlines.txt", TEST_DATA_SMALL),
+        ],
+    );
+    let list = temp_dir.path().join("inputs.lst");
+    fs::write(&list, "one.txt\0two\nlines.txt\0").unwrap();
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["collect", "pkg", "--null", "-T"])
+        .arg(&list)
+        .assert()
+        .success();
+    let out = temp_dir.path().join("out");
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-d", "-C", "out", "pkg.tar.gz"])
+        .assert()
+        .success();
+    assert_eq!(read_file(&out.join("pkg/one.txt")), TEST_DATA_SMALL);
+    assert_eq!(read_file(&out.join("pkg/two\nlines.txt")), TEST_DATA_SMALL);
+
+    // Decompression takes lists too
+    for file in &files {
+        jcz_command()
+            .args(["-c", "xz"])
+            .arg(file)
+            .assert()
+            .success();
+        fs::remove_file(file).unwrap();
+    }
+    fs::write(&list, "one.txt.xz\0two\nlines.txt.xz\0").unwrap();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-d", "--null", "--files-from"])
+        .arg(&list)
+        .assert()
+        .success();
+    for file in &files {
+        assert_eq!(read_file(file), TEST_DATA_SMALL);
+    }
+}
+
+#[test]
+fn test_files_from_errors() {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-T", "missing.lst"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("missing.lst"));
+
+    // --null only applies to a list
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["--null", "data.txt"])
+        .assert()
+        .failure();
+
+    // A list naming nothing is an empty input set
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-T", "-"])
+        .write_stdin("\n")
+        .assert()
+        .failure();
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Files of the tree backed up, with one nested
+const TREE: &[(&str, &[u8])] = &[("a.txt", TEST_DATA_SMALL), ("sub/b.txt", TEST_DATA_MEDIUM)];
+
+/// Run a backup of `data_dir` and return the archive it wrote
+fn backup(root: &Path, data_dir: &Path, mode: &str, name: &str) -> PathBuf {
+    let archive = root.join(name);
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg(mode)
+        .arg(root.join("state.json"))
+        .arg("-o")
+        .arg(&archive)
+        .arg(data_dir)
+        .assert()
+        .success();
+    assert!(file_exists(&archive));
+    archive
+}
+
+#[test]
+fn test_incremental_chain_restore() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_tree(temp_dir.path(), TREE);
+
+    let full = backup(temp_dir.path(), &data_dir, "--incremental", "full.tar.gz");
+    assert!(tar_members(&full).contains(&"data/sub/b.txt".to_string()));
+    assert!(file_exists(&temp_dir.path().join("state.json")));
+
+    // Change one file, delete one, add one
+    fs::write(data_dir.join("a.txt"), TEST_DATA_MEDIUM).unwrap();
+    fs::remove_file(data_dir.join("sub/b.txt")).unwrap();
+    fs::write(data_dir.join("c.txt"), TEST_DATA_BINARY).unwrap();
+    let first = backup(temp_dir.path(), &data_dir, "--incremental", "inc1.tar.gz");
+    assert_eq!(
+        tar_members(&first),
+        vec![
+            "data",
+            "data/.jcz-snapshot.json",
+            "data/a.txt",
+            "data/c.txt"
+        ]
+    );
+
+    // Nothing changed: only the marker is archived
+    let second = backup(temp_dir.path(), &data_dir, "--incremental", "inc2.tar.gz");
+    assert_eq!(
+        tar_members(&second),
+        vec!["data", "data/.jcz-snapshot.json"]
+    );
+
+    let restored = temp_dir.path().join("restored");
+    jcz_command()
+        .arg("-d")
+        .arg("--restore")
+        .arg(&restored)
+        .arg(&full)
+        .arg(&first)
+        .arg(&second)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&restored.join("a.txt")), TEST_DATA_MEDIUM);
+    assert_eq!(read_file(&restored.join("c.txt")), TEST_DATA_BINARY);
+    assert!(!file_exists(&restored.join("sub/b.txt")));
+    assert!(dir_exists(&restored.join("sub")));
+    assert!(!file_exists(&restored.join(".jcz-snapshot.json")));
+}
+
+#[test]
+fn test_differential_since_full() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_tree(temp_dir.path(), TREE);
+    let state = temp_dir.path().join("state.json");
+
+    let full = backup(temp_dir.path(), &data_dir, "--differential", "full.tar.gz");
+    let recorded = fs::read_to_string(&state).unwrap();
+
+    fs::write(data_dir.join("a.txt"), TEST_DATA_MEDIUM).unwrap();
+    let first = backup(temp_dir.path(), &data_dir, "--differential", "diff1.tar.gz");
+
+    fs::write(data_dir.join("c.txt"), TEST_DATA_SMALL).unwrap();
+    let second = backup(temp_dir.path(), &data_dir, "--differential", "diff2.tar.gz");
+
+    // Each differential holds every change since the full backup
+    assert_eq!(
+        tar_members(&second),
+        vec![
+            "data",
+            "data/.jcz-snapshot.json",
+            "data/a.txt",
+            "data/c.txt"
+        ]
+    );
+    assert_eq!(fs::read_to_string(&state).unwrap(), recorded);
+
+    // Only the full backup and one differential make a chain
+    jcz_command()
+        .arg("-d")
+        .arg("--restore")
+        .arg(temp_dir.path().join("broken"))
+        .arg(&full)
+        .arg(&first)
+        .arg(&second)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("was not made against"));
+    assert!(!dir_exists(&temp_dir.path().join("broken")));
+
+    let restored = temp_dir.path().join("restored");
+    jcz_command()
+        .arg("-d")
+        .arg("--restore")
+        .arg(&restored)
+        .arg(&full)
+        .arg(&second)
+        .assert()
+        .success();
+    assert_eq!(read_file(&restored.join("a.txt")), TEST_DATA_MEDIUM);
+    assert_eq!(read_file(&restored.join("c.txt")), TEST_DATA_SMALL);
+    assert_eq!(read_file(&restored.join("sub/b.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_restore_requires_full_backup_first() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_tree(temp_dir.path(), TREE);
+
+    let full = backup(temp_dir.path(), &data_dir, "--incremental", "full.tar.gz");
+    fs::write(data_dir.join("a.txt"), TEST_DATA_MEDIUM).unwrap();
+    let incremental = backup(temp_dir.path(), &data_dir, "--incremental", "inc.tar.gz");
+
+    jcz_command()
+        .arg("-d")
+        .arg("--restore")
+        .arg(temp_dir.path().join("restored"))
+        .arg(&incremental)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("full backup"));
+
+    // A target with content is never overwritten
+    jcz_command()
+        .arg("-d")
+        .arg("--restore")
+        .arg(&data_dir)
+        .arg(&full)
+        .assert()
+        .failure();
+    assert_eq!(read_file(&data_dir.join("a.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_invalid_incremental_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_tree(temp_dir.path(), TREE);
+    let other_dir = temp_dir.path().join("other");
+    fs::create_dir(&other_dir).unwrap();
+    let state = temp_dir.path().join("state.json");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--incremental")
+        .arg(&state)
+        .arg(&data_dir)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--incremental")
+        .arg(&state)
+        .arg(&data_dir)
+        .arg(&other_dir)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--incremental")
+        .arg(&state)
+        .arg(data_dir.join("a.txt"))
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("--restore")
+        .arg(temp_dir.path().join("restored"))
+        .arg(&data_dir)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&state));
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+/// Read one member of a gzip-compressed TAR archive
+fn tar_member(archive: &Path, member: &str) -> String {
+    let output = StdCommand::new("tar")
+        .arg("-xOzf")
+        .arg(archive)
+        .arg(member)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{} not in archive", member);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Create `pkg.tar.gz` with a manifest from two files
+fn create_package(dir: &Path) {
+    let files = create_test_files(
+        dir,
+        &[
+            ("file1.txt", TEST_DATA_SMALL),
+            ("file2.txt", TEST_DATA_MEDIUM),
+        ],
+    );
+
+    jcz_command()
+        .current_dir(dir)
+        .arg("-c")
+        .arg("tgz")
+        .arg("-a")
+        .arg("pkg")
+        .arg("--manifest")
+        .arg(&files[0])
+        .arg(&files[1])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_manifest_contents() {
+    let temp_dir = TempDir::new().unwrap();
+    create_package(temp_dir.path());
+
+    let manifest = tar_member(&temp_dir.path().join("pkg.tar.gz"), "pkg/MANIFEST.json");
+    assert!(manifest.contains("\"jcz_version\""));
+    assert!(manifest.contains("\"host\""));
+    assert!(manifest.contains("\"path\": \"file1.txt\""));
+    assert!(manifest.contains(&format!("\"size\": {}", TEST_DATA_MEDIUM.len())));
+    assert!(manifest.contains("\"mode\": \"0"));
+    assert!(manifest.contains("\"sha256\""));
+    assert!(manifest.contains("file2.txt\""));
+    assert!(!manifest.contains("\"path\": \"MANIFEST.json\""));
+}
+
+#[test]
+fn test_manifest_verified_on_extraction() {
+    let temp_dir = TempDir::new().unwrap();
+    create_package(temp_dir.path());
+
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(temp_dir.path().join("pkg.tar.gz"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        read_file(&output_dir.join("pkg/file2.txt")),
+        TEST_DATA_MEDIUM
+    );
+    assert!(file_exists(&output_dir.join("pkg/MANIFEST.json")));
+}
+
+#[test]
+fn test_manifest_flat_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir_all(data_dir.join("nested")).unwrap();
+    create_test_file(&data_dir.join("nested"), "deep.txt", TEST_DATA_BINARY);
+    let loose = create_test_file(temp_dir.path(), "loose.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("tgz")
+        .arg("-A")
+        .arg("flat")
+        .arg("--manifest")
+        .arg(&data_dir)
+        .arg(&loose)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("flat.tar.gz");
+    let manifest = tar_member(&archive, "MANIFEST.json");
+    assert!(manifest.contains("\"path\": \"data/nested/deep.txt\""));
+    assert!(manifest.contains("\"path\": \"loose.txt\""));
+
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&archive)
+        .assert()
+        .success();
+
+    assert_eq!(
+        read_file(&output_dir.join("data/nested/deep.txt")),
+        TEST_DATA_BINARY
+    );
+}
+
+#[test]
+fn test_manifest_reports_modified_and_missing() {
+    let temp_dir = TempDir::new().unwrap();
+    create_package(temp_dir.path());
+
+    // Rebuild the archive with one member changed and one removed
+    let work = temp_dir.path().join("work");
+    fs::create_dir(&work).unwrap();
+    let status = StdCommand::new("tar")
+        .arg("-xzf")
+        .arg(temp_dir.path().join("pkg.tar.gz"))
+        .arg("-C")
+        .arg(&work)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::write(work.join("pkg/file1.txt"), b"tampered").unwrap();
+    fs::remove_file(work.join("pkg/file2.txt")).unwrap();
+
+    let tampered = temp_dir.path().join("tampered.tar.gz");
+    let status = StdCommand::new("tar")
+        .arg("-czf")
+        .arg(&tampered)
+        .arg("-C")
+        .arg(&work)
+        .arg("pkg")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&tampered)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Modified in archive: file1.txt"))
+        .stderr(predicates::str::contains("Missing from archive: file2.txt"));
+
+    assert!(!dir_exists(&output_dir.join("pkg")));
+}
+
+#[test]
+fn test_manifest_requires_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--manifest")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&temp_dir.path().join("test.txt.tar.gz")));
+}
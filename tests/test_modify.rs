@@ -0,0 +1,241 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command as StdCommand;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// Extract a single member to stdout
+fn extract_member(archive: &Path, member: &str) -> Vec<u8> {
+    let output = StdCommand::new("tar")
+        .arg("-xOf")
+        .arg(archive)
+        .arg(member)
+        .output()
+        .expect("Failed to run tar");
+    assert!(output.status.success());
+    output.stdout
+}
+
+/// Create `<name>.<ext>` from a single file in `dir` and return its path
+fn create_archive(dir: &Path, name: &str, command: &str, ext: &str) -> std::path::PathBuf {
+    let file = create_test_file(dir, name, format!("Content of {}", name).as_bytes());
+    jcz_command()
+        .arg("-c")
+        .arg(command)
+        .arg(&file)
+        .assert()
+        .success();
+    dir.join(format!("{}.{}", name, ext))
+}
+
+#[test]
+fn test_append_to_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "day1.log", "tar", "tar");
+    let day2 = create_test_file(temp_dir.path(), "day2.log", b"Content of day2.log");
+
+    jcz_command()
+        .arg("--append")
+        .arg(&archive)
+        .arg(&day2)
+        .assert()
+        .success();
+
+    assert_eq!(tar_members(&archive), vec!["day1.log", "day2.log"]);
+}
+
+#[test]
+fn test_append_to_compound_archives() {
+    for (command, ext) in [("tgz", "tar.gz"), ("tbz2", "tar.bz2"), ("txz", "tar.xz")] {
+        let temp_dir = TempDir::new().unwrap();
+        let archive = create_archive(temp_dir.path(), "day1.log", command, ext);
+        let data_dir = temp_dir.path().join("day2");
+        create_test_dir_structure(&data_dir, &["a.log"]);
+
+        jcz_command()
+            .arg("--append")
+            .arg(&archive)
+            .arg(&data_dir)
+            .assert()
+            .success();
+
+        let members = tar_members(&archive);
+        assert!(members.contains(&"day1.log".to_string()), "{}", command);
+        assert!(members.contains(&"day2/a.log".to_string()), "{}", command);
+        assert_eq!(extract_member(&archive, "day2/a.log"), b"Content of a.log");
+
+        // No staging directories or temporary files are left behind
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(".jcztmp_"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+}
+
+#[test]
+fn test_modify_compound_archive_recompresses_at_level() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = create_test_file(temp_dir.path(), "day1.log", b"Content of day1.log");
+    jcz_command()
+        .args(["-c", "tbz2", "-l", "9"])
+        .arg(&file)
+        .assert()
+        .success();
+    let archive = temp_dir.path().join("day1.log.tar.bz2");
+    let day2 = create_test_file(temp_dir.path(), "day2.log", b"Content of day2.log");
+    let day3 = create_test_file(temp_dir.path(), "day3.log", b"Content of day3.log");
+
+    // The bzip2 header records the level as the block size: BZh1 to BZh9
+    let block_size = |archive: &Path| read_file(archive)[3];
+    assert_eq!(block_size(&archive), b'9');
+
+    // Without -l the archive drops to the default level, with a warning
+    let output = jcz_command()
+        .arg("--append")
+        .arg(&archive)
+        .arg(&day2)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("default level 6"));
+    assert_eq!(block_size(&archive), b'6');
+
+    // -l picks the level the archive is recompressed at
+    let output = jcz_command()
+        .args(["-l", "9", "--append"])
+        .arg(&archive)
+        .arg(&day3)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("default level"));
+    assert_eq!(block_size(&archive), b'9');
+    assert_eq!(
+        tar_members(&archive),
+        vec!["day1.log", "day2.log", "day3.log"]
+    );
+}
+
+#[test]
+fn test_update_replaces_newer_members() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "state.txt", "tgz", "tar.gz");
+
+    // Make the file on disk newer than the archived copy
+    let file = temp_dir.path().join("state.txt");
+    fs::write(&file, b"updated").unwrap();
+    let newer = SystemTime::now() + Duration::from_secs(10);
+    fs::File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(newer)
+        .unwrap();
+    let extra = create_test_file(temp_dir.path(), "extra.txt", b"extra");
+
+    jcz_command()
+        .arg("--update")
+        .arg(&archive)
+        .arg(&file)
+        .arg(&extra)
+        .assert()
+        .success();
+
+    assert_eq!(tar_members(&archive), vec!["extra.txt", "state.txt"]);
+    assert_eq!(extract_member(&archive, "state.txt"), b"updated");
+}
+
+#[test]
+fn test_update_keeps_current_members() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "state.txt", "tar", "tar");
+    let before = read_file(&archive);
+
+    jcz_command()
+        .arg("--update")
+        .arg(&archive)
+        .arg(temp_dir.path().join("state.txt"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&archive), before);
+}
+
+#[test]
+fn test_delete_members() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "b.txt", "sub/c.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("txz")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("data.tar.xz");
+    jcz_command()
+        .arg("--delete")
+        .arg(&archive)
+        .arg("data/a.txt")
+        .arg("data/sub")
+        .assert()
+        .success();
+
+    let members = tar_members(&archive);
+    assert!(members.contains(&"data/b.txt".to_string()));
+    assert!(!members.contains(&"data/a.txt".to_string()));
+    assert!(!members.iter().any(|m| m.starts_with("data/sub")));
+}
+
+#[test]
+fn test_delete_missing_member_keeps_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "test.txt", "tbz2", "tar.bz2");
+    let before = read_file(&archive);
+
+    jcz_command()
+        .arg("--delete")
+        .arg(&archive)
+        .arg("missing.txt")
+        .assert()
+        .failure();
+
+    assert_eq!(read_file(&archive), before);
+}
+
+#[test]
+fn test_modify_rejects_non_tar_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "test.txt", "gzip", "gz");
+    let extra = create_test_file(temp_dir.path(), "extra.txt", b"extra");
+
+    jcz_command()
+        .arg("--append")
+        .arg(&archive)
+        .arg(&extra)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_modify_operations_are_exclusive() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_archive(temp_dir.path(), "test.txt", "tar", "tar");
+    let extra = create_test_file(temp_dir.path(), "extra.txt", b"extra");
+
+    jcz_command()
+        .arg("--append")
+        .arg(&archive)
+        .arg("--update")
+        .arg(&archive)
+        .arg(&extra)
+        .assert()
+        .failure();
+}
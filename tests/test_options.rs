@@ -1,7 +1,11 @@
+#![allow(clippy::len_zero)]
+
 mod common;
 
 use common::*;
 use std::fs;
+use std::path::Path;
+use std::process::Command as StdCommand;
 use tempfile::TempDir;
 
 // Timestamp Option Tests
@@ -130,6 +134,102 @@ fn test_timestamp_default_is_none() {
     );
 }
 
+/// Set a file's modification time to 2023-11-14T22:13:20Z
+fn set_fixed_mtime(path: &Path) {
+    let status = StdCommand::new("touch")
+        .arg("-d")
+        .arg("@1700000000")
+        .arg(path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_timestamp_format_from_mtime_utc() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    set_fixed_mtime(&test_file);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--timestamp-format")
+        .arg("%Y-%m-%dT%H%M%SZ")
+        .arg("--utc")
+        .arg("--timestamp-from-mtime")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(file_exists(
+        &temp_dir.path().join("test.txt_2023-11-14T221320Z.gz")
+    ));
+}
+
+#[test]
+fn test_timestamp_positions() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    set_fixed_mtime(&test_file);
+
+    for (position, command, expected) in [
+        ("prefix", "gzip", "20231114_test.txt.gz"),
+        ("suffix", "bzip2", "test.txt_20231114.bz2"),
+        ("before-extension", "xz", "test_20231114.txt.xz"),
+        ("before-extension", "tgz", "test_20231114.txt.tar.gz"),
+    ] {
+        jcz_command()
+            .arg("-c")
+            .arg(command)
+            .arg("-t")
+            .arg("1")
+            .arg("--utc")
+            .arg("--timestamp-from-mtime")
+            .arg("--timestamp-position")
+            .arg(position)
+            .arg(&test_file)
+            .assert()
+            .success();
+
+        assert!(
+            file_exists(&temp_dir.path().join(expected)),
+            "{} not created",
+            expected
+        );
+    }
+}
+
+#[test]
+fn test_invalid_timestamp_format_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    for args in [
+        vec!["--timestamp-format", "%Q"],
+        vec!["--timestamp-format", "%Y/%m"],
+        vec!["-t", "1", "--timestamp-format", "%Y"],
+        vec!["-t", "1", "--timestamp-position", "middle"],
+        vec!["--utc"],
+        vec!["--timestamp-from-mtime"],
+    ] {
+        jcz_command()
+            .arg("-c")
+            .arg("gzip")
+            .args(&args)
+            .arg(&test_file)
+            .assert()
+            .failure();
+    }
+
+    let outputs: Vec<_> = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".gz"))
+        .collect();
+    assert!(outputs.is_empty());
+}
+
 // Move-to Directory Option Tests
 // NOTE: These tests are currently skipped due to cross-filesystem issues
 // The -C option uses rename() which fails across different filesystems
@@ -0,0 +1,185 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+#[test]
+fn test_output_single_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+    let out_dir = temp_dir.path().join("out");
+    fs::create_dir(&out_dir).unwrap();
+
+    let output = out_dir.join("renamed.gz");
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-o")
+        .arg(&output)
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(file_exists(&output));
+    assert!(!file_exists(&temp_dir.path().join("test.txt.gz")));
+    assert!(verify_decompressed_content(&output, TEST_DATA_MEDIUM));
+
+    // Decompression writes exactly the named file
+    let restored = temp_dir.path().join("restored.txt");
+    jcz_command()
+        .arg("-d")
+        .arg("-o")
+        .arg(&restored)
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(read_file(&restored), TEST_DATA_MEDIUM);
+    assert!(!file_exists(&out_dir.join("renamed")));
+}
+
+#[test]
+fn test_output_compound_any_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    create_test_file(&data_dir, "file.txt", TEST_DATA_SMALL);
+
+    let output = temp_dir.path().join("backup.tgz");
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("-o")
+        .arg(&output)
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(file_exists(&output));
+    assert!(!file_exists(&temp_dir.path().join("data.tar.gz")));
+
+    let listing = StdCommand::new("tar")
+        .arg("-tzf")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(listing.status.success());
+    assert!(String::from_utf8(listing.stdout)
+        .unwrap()
+        .lines()
+        .any(|l| l == "data/file.txt"));
+}
+
+#[test]
+fn test_output_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+    let out_dir = temp_dir.path().join("releases");
+    fs::create_dir(&out_dir).unwrap();
+
+    let output = out_dir.join("release-1.0.tar.xz");
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("txz")
+        .arg("-a")
+        .arg("pkg")
+        .arg("-o")
+        .arg(&output)
+        .args(&files)
+        .assert()
+        .success();
+
+    assert!(file_exists(&output));
+    assert!(!file_exists(&temp_dir.path().join("pkg.tar.xz")));
+
+    // The extraction directory is named by -o as well
+    let restored = temp_dir.path().join("restored");
+    jcz_command()
+        .arg("-d")
+        .arg("-o")
+        .arg(&restored)
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(read_file(&restored.join("b.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_output_directory_for_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+    let out_dir = temp_dir.path().join("out");
+    fs::create_dir(&out_dir).unwrap();
+
+    // A file name cannot be given to several outputs
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-o")
+        .arg(out_dir.join("both.xz"))
+        .args(&files)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("existing directory"));
+    assert!(!file_exists(&out_dir.join("both.xz")));
+
+    // A directory works like -C
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-o")
+        .arg(&out_dir)
+        .args(&files)
+        .assert()
+        .success();
+    assert!(file_exists(&out_dir.join("a.txt.xz")));
+    assert!(file_exists(&out_dir.join("b.txt.xz")));
+}
+
+#[test]
+fn test_invalid_output_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    let output = temp_dir.path().join("out.gz");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-o")
+        .arg(&output)
+        .arg("-C")
+        .arg(temp_dir.path())
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-o")
+        .arg(&output)
+        .arg("-t")
+        .arg("1")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-o")
+        .arg(temp_dir.path().join("missing/out.gz"))
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&output));
+}
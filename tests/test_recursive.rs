@@ -0,0 +1,250 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_recursive_compress_in_place() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt", "sub/deep/c.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-r")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    for name in ["a.txt", "sub/b.txt", "sub/deep/c.txt"] {
+        let compressed = data_dir.join(format!("{}.gz", name));
+        let expected = format!("Content of {}", name);
+        assert!(
+            verify_decompressed_content(&compressed, expected.as_bytes()),
+            "{} should be compressed",
+            name
+        );
+        assert!(file_exists(&data_dir.join(name)), "Original should remain");
+    }
+}
+
+#[test]
+fn test_recursive_compress_skips_existing_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "b.txt.xz"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-r")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(file_exists(&data_dir.join("a.txt.xz")));
+    assert!(!data_dir.join("b.txt.xz.xz").exists());
+    assert_eq!(
+        read_file(&data_dir.join("b.txt.xz")),
+        b"Content of b.txt.xz"
+    );
+}
+
+#[test]
+fn test_recursive_compress_rerun_skips_earlier_outputs() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt"]);
+    create_test_file(&data_dir, "big.bin", &noise(150 * 1024, 1));
+
+    jcz_command()
+        .args([
+            "-c",
+            "gzip",
+            "-r",
+            "--remove-source",
+            "--checksum",
+            "sha256",
+        ])
+        .args(["--split-size", "100K"])
+        .arg(&data_dir)
+        .assert()
+        .success();
+    create_test_dir_structure(&data_dir, &["sub/c.txt"]);
+    jcz_command()
+        .args(["-c", "gzip", "--remove-source", "--checksum", "blake3"])
+        .arg(data_dir.join("sub/c.txt"))
+        .assert()
+        .success();
+    assert_eq!(
+        names_in(&data_dir),
+        [
+            "SHA256SUMS",
+            "a.txt.gz.001",
+            "big.bin.gz.001",
+            "big.bin.gz.002",
+            "sub"
+        ]
+    );
+    assert_eq!(
+        names_in(&data_dir.join("sub")),
+        ["c.txt.gz", "c.txt.gz.blake3"]
+    );
+
+    // Volumes, sidecars and sums files of the earlier runs are not inputs
+    jcz_command()
+        .args(["-c", "gzip", "-r"])
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert_eq!(names_in(&data_dir).len(), 5);
+    assert_eq!(names_in(&data_dir.join("sub")).len(), 2);
+}
+
+#[test]
+fn test_recursive_compress_mirrors_tree_into_move_to() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    let output_dir = temp_dir.path().join("output");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg("-r")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(file_exists(&output_dir.join("data/a.txt.bz2")));
+    assert!(file_exists(&output_dir.join("data/sub/b.txt.bz2")));
+    assert!(!data_dir.join("a.txt.bz2").exists());
+}
+
+#[test]
+fn test_recursive_compress_with_remove_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-r")
+        .arg("--remove-source")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(file_exists(&data_dir.join("a.txt.gz")));
+    assert!(file_exists(&data_dir.join("sub/b.txt.gz")));
+    assert!(!data_dir.join("a.txt").exists());
+    assert!(!data_dir.join("sub/b.txt").exists());
+}
+
+#[test]
+fn test_recursive_decompress_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-r")
+        .arg("--remove-source")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("-d")
+        .arg("-r")
+        .arg("--remove-archive")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&data_dir.join("a.txt")), b"Content of a.txt");
+    assert_eq!(
+        read_file(&data_dir.join("sub/b.txt")),
+        b"Content of sub/b.txt"
+    );
+    assert!(!data_dir.join("a.txt.gz").exists());
+    assert!(!data_dir.join("sub/b.txt.gz").exists());
+}
+
+#[test]
+fn test_recursive_decompress_mirrors_tree_into_move_to() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    let output_dir = temp_dir.path().join("output");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt", "notes.md"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-r")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    fs::remove_file(data_dir.join("a.txt")).unwrap();
+    fs::remove_file(data_dir.join("sub/b.txt")).unwrap();
+    fs::remove_file(data_dir.join("notes.md")).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("-r")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert_eq!(
+        read_file(&output_dir.join("data/a.txt")),
+        b"Content of a.txt"
+    );
+    assert_eq!(
+        read_file(&output_dir.join("data/sub/b.txt")),
+        b"Content of sub/b.txt"
+    );
+}
+
+#[test]
+fn test_recursive_rejects_tar_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("-r")
+        .arg(&data_dir)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_recursive_rejects_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("gzip")
+        .arg("-r")
+        .arg("-A")
+        .arg("archive")
+        .arg(&test_file)
+        .assert()
+        .failure();
+}
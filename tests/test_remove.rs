@@ -0,0 +1,190 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+// Source removal tests
+
+#[test]
+fn test_compress_remove_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--remove-source")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(!test_file.exists(), "Source should be removed");
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("test.txt.gz"),
+        TEST_DATA_MEDIUM
+    ));
+}
+
+#[test]
+fn test_compress_remove_source_multiple_formats() {
+    for format in ["bzip2", "xz", "tar"] {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+        jcz_command()
+            .arg("-c")
+            .arg(format)
+            .arg("--remove-source")
+            .arg(&test_file)
+            .assert()
+            .success();
+
+        assert!(
+            !test_file.exists(),
+            "Source should be removed for {}",
+            format
+        );
+    }
+}
+
+#[test]
+fn test_compound_remove_source_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["a.txt", "sub/b.txt"]);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--remove-source")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(!data_dir.exists(), "Source directory should be removed");
+    let archive = temp_dir.path().join("data.tar.gz");
+    assert!(file_exists(&archive));
+
+    // Round trip restores the directory
+    jcz_command().arg("-d").arg(&archive).assert().success();
+    assert_eq!(
+        read_file(&data_dir.join("sub/b.txt")),
+        b"Content of sub/b.txt"
+    );
+}
+
+#[test]
+fn test_collect_remove_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = create_test_file(temp_dir.path(), "file1.txt", b"Content 1");
+    let file2 = create_test_file(temp_dir.path(), "file2.txt", b"Content 2");
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("txz")
+        .arg("-A")
+        .arg("archive")
+        .arg("--remove-source")
+        .arg(&file1)
+        .arg(&file2)
+        .assert()
+        .success();
+
+    assert!(file_exists(&temp_dir.path().join("archive.tar.xz")));
+    assert!(!file1.exists());
+    assert!(!file2.exists());
+}
+
+#[test]
+fn test_compress_failure_keeps_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+    create_test_file(temp_dir.path(), "test.txt.gz", b"existing");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--remove-source")
+        .arg("--on-conflict")
+        .arg("skip")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(file_exists(&test_file), "Skipped source must be kept");
+}
+
+#[test]
+fn test_remove_source_rejected_with_decompress() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt.gz", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-d")
+        .arg("--remove-source")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(file_exists(&test_file));
+}
+
+// Archive removal tests
+
+#[test]
+fn test_decompress_remove_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tbz2")
+        .arg(&test_file)
+        .assert()
+        .success();
+    fs::remove_file(&test_file).unwrap();
+
+    let archive = temp_dir.path().join("test.txt.tar.bz2");
+    jcz_command()
+        .arg("-d")
+        .arg("--remove-archive")
+        .arg(&archive)
+        .assert()
+        .success();
+
+    assert!(!archive.exists(), "Archive should be removed");
+    assert_eq!(read_file(&test_file), TEST_DATA_SMALL);
+}
+
+#[test]
+fn test_decompress_failure_keeps_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive = create_test_file(temp_dir.path(), "corrupt.gz", b"not gzip data");
+
+    jcz_command()
+        .arg("-d")
+        .arg("--remove-archive")
+        .arg(&archive)
+        .assert()
+        .failure();
+
+    assert!(file_exists(&archive), "Corrupt archive must be kept");
+}
+
+#[test]
+fn test_remove_archive_requires_decompress() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--remove-archive")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(file_exists(&test_file));
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use common::*;
+use std::path::{Path, PathBuf};
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+/// 2023-11-14T22:13:20Z
+const EPOCH: &str = "1700000000";
+
+/// Create `data/` below `root`, adding its files in the given order
+fn create_data(root: &Path, reverse: bool) -> PathBuf {
+    let mut files: Vec<(&str, &[u8])> = vec![
+        ("a.txt", TEST_DATA_SMALL),
+        ("b.txt", TEST_DATA_MEDIUM),
+        ("sub/c.bin", TEST_DATA_BINARY),
+        ("z.txt", TEST_DATA_SMALL),
+    ];
+    if reverse {
+        files.reverse();
+    }
+    create_tree(root, &files)
+}
+
+/// Compress `data/` in two independently created trees and return both outputs
+fn compress_twice(format: &str, output_name: &str) -> (Vec<u8>, Vec<u8>) {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for (dir, reverse) in [(&first, false), (&second, true)] {
+        let data_dir = create_data(dir.path(), reverse);
+
+        jcz_command()
+            .env("SOURCE_DATE_EPOCH", EPOCH)
+            .arg("-c")
+            .arg(format)
+            .arg("--reproducible")
+            .arg(&data_dir)
+            .assert()
+            .success();
+
+        outputs.push(read_file(&dir.path().join(output_name)));
+    }
+
+    let second = outputs.pop().unwrap();
+    (outputs.pop().unwrap(), second)
+}
+
+#[test]
+fn test_reproducible_compound_formats() {
+    for (format, output_name) in [
+        ("tgz", "data.tar.gz"),
+        ("tbz2", "data.tar.bz2"),
+        ("txz", "data.tar.xz"),
+        ("tar", "data.tar"),
+    ] {
+        let (first, second) = compress_twice(format, output_name);
+        assert!(first == second, "{} output differs between runs", format);
+    }
+}
+
+#[test]
+fn test_reproducible_single_file() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for dir in [&first, &second] {
+        let test_file = create_test_file(dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+        for format in ["gzip", "bzip2", "xz"] {
+            jcz_command()
+                .env("SOURCE_DATE_EPOCH", EPOCH)
+                .arg("-c")
+                .arg(format)
+                .arg("--reproducible")
+                .arg(&test_file)
+                .assert()
+                .success();
+        }
+
+        outputs.push(
+            ["gz", "bz2", "xz"].map(|ext| read_file(&dir.path().join(format!("test.txt.{}", ext)))),
+        );
+    }
+
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn test_reproducible_entries_normalised() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = create_data(temp_dir.path(), true);
+
+    jcz_command()
+        .env("SOURCE_DATE_EPOCH", EPOCH)
+        .arg("-c")
+        .arg("tgz")
+        .arg("--reproducible")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let output = StdCommand::new("tar")
+        .env("TZ", "UTC")
+        .arg("-tvzf")
+        .arg(temp_dir.path().join("data.tar.gz"))
+        .arg("--numeric-owner")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let listing = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = listing
+        .lines()
+        .map(|l| l.split_whitespace().last().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "data/",
+            "data/a.txt",
+            "data/b.txt",
+            "data/sub/",
+            "data/sub/c.bin",
+            "data/z.txt"
+        ]
+    );
+
+    // Files written just now are clamped to SOURCE_DATE_EPOCH
+    for line in listing.lines() {
+        assert!(line.contains(" 0/0 "), "owner not normalised: {}", line);
+        assert!(
+            line.contains("2023-11-14 22:13"),
+            "mtime not clamped: {}",
+            line
+        );
+    }
+}
+
+#[test]
+fn test_reproducible_collection_with_manifest() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for (dir, reverse) in [(&first, false), (&second, true)] {
+        create_data(dir.path(), reverse);
+        create_test_file(dir.path(), "loose.txt", TEST_DATA_SMALL);
+
+        jcz_command()
+            .current_dir(dir.path())
+            .env("SOURCE_DATE_EPOCH", EPOCH)
+            .arg("-c")
+            .arg("tgz")
+            .arg("-A")
+            .arg("bundle")
+            .arg("--manifest")
+            .arg("--reproducible")
+            .arg("loose.txt")
+            .arg("data")
+            .assert()
+            .success();
+
+        outputs.push(read_file(&dir.path().join("bundle.tar.gz")));
+    }
+
+    assert!(outputs[0] == outputs[1], "collection differs between runs");
+
+    let output = StdCommand::new("tar")
+        .arg("-xOzf")
+        .arg(first.path().join("bundle.tar.gz"))
+        .arg("MANIFEST.json")
+        .output()
+        .unwrap();
+    let manifest = String::from_utf8(output.stdout).unwrap();
+    assert!(manifest.contains("\"created\": \"2023-11-14T22:13:20+00:00\""));
+    assert!(manifest.contains("\"host\": \"unknown\""));
+}
+
+#[test]
+fn test_reproducible_with_parent_collection_identical() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for dir in [&first, &second] {
+        let files = create_test_files(
+            dir.path(),
+            &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+        );
+
+        jcz_command()
+            .current_dir(dir.path())
+            .env("SOURCE_DATE_EPOCH", EPOCH)
+            .arg("-c")
+            .arg("txz")
+            .arg("-a")
+            .arg("pkg")
+            .arg("--reproducible")
+            .arg(&files[1])
+            .arg(&files[0])
+            .assert()
+            .success();
+
+        outputs.push(read_file(&dir.path().join("pkg.tar.xz")));
+    }
+
+    assert!(outputs[0] == outputs[1], "collection differs between runs");
+}
+
+#[test]
+fn test_invalid_reproducible_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .env("SOURCE_DATE_EPOCH", "yesterday")
+        .arg("-c")
+        .arg("gzip")
+        .arg("--reproducible")
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid SOURCE_DATE_EPOCH"));
+
+    jcz_command()
+        .env("JCZ_PASSPHRASE", "x")
+        .arg("-c")
+        .arg("gzip")
+        .arg("--reproducible")
+        .arg("--encrypt")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(!file_exists(&temp_dir.path().join("test.txt.gz")));
+    assert!(!file_exists(&temp_dir.path().join("test.txt.gz.age")));
+}
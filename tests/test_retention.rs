@@ -0,0 +1,232 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Create a backup directory holding earlier outputs with the given names
+fn create_backups(root: &Path, names: &[&str]) -> PathBuf {
+    let backups = root.join("backups");
+    fs::create_dir(&backups).unwrap();
+    for name in names {
+        fs::write(backups.join(name), b"old").unwrap();
+    }
+    backups
+}
+
+#[test]
+fn test_keep_last() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_MEDIUM);
+    let backups = create_backups(
+        temp_dir.path(),
+        &[
+            "data.txt_20250101.gz",
+            "data.txt_20250102.gz",
+            "data.txt_20250103.gz",
+            "data.txt_old.gz",
+            "other.txt_20250101.gz",
+        ],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-t")
+        .arg("1")
+        .arg("-C")
+        .arg(&backups)
+        .arg("--keep-last")
+        .arg("2")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let names = names_in(&backups);
+    assert_eq!(names.len(), 4, "unexpected outputs {:?}", names);
+    assert!(names.contains(&"data.txt_20250103.gz".to_string()));
+    assert!(names.contains(&"data.txt_old.gz".to_string()));
+    assert!(names.contains(&"other.txt_20250101.gz".to_string()));
+
+    // The new output is kept
+    let new = names
+        .iter()
+        .find(|n| n.starts_with("data.txt_2") && *n != "data.txt_20250103.gz")
+        .unwrap();
+    assert!(verify_decompressed_content(
+        &backups.join(new),
+        TEST_DATA_MEDIUM
+    ));
+}
+
+#[test]
+fn test_prune_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+    let backups = create_backups(
+        temp_dir.path(),
+        &["data.txt_20250101.xz", "data.txt_20250102.xz"],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("-t")
+        .arg("1")
+        .arg("-C")
+        .arg(&backups)
+        .arg("--keep-last")
+        .arg("1")
+        .arg("--prune-dry-run")
+        .arg(&test_file)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Would remove"))
+        .stderr(predicates::str::contains("data.txt_20250101.xz"));
+
+    assert_eq!(names_in(&backups).len(), 3);
+}
+
+#[test]
+fn test_max_age_removes_volumes_and_sidecars() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    create_test_file(&data_dir, "file.txt", TEST_DATA_MEDIUM);
+    let backups = create_backups(
+        temp_dir.path(),
+        &[
+            "data_20200101_000000.tar.gz",
+            "data_20200101_000000.tar.gz.sha256",
+            "data_20200102_000000.tar.gz.001",
+            "data_20200102_000000.tar.gz.002",
+        ],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("-t")
+        .arg("2")
+        .arg("-C")
+        .arg(&backups)
+        .arg("--max-age")
+        .arg("30d")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let names = names_in(&backups);
+    assert_eq!(names.len(), 1, "unexpected outputs {:?}", names);
+    assert!(names[0].starts_with("data_") && names[0].ends_with(".tar.gz"));
+}
+
+#[test]
+fn test_keep_daily_with_template() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+    let backups = create_backups(
+        temp_dir.path(),
+        &[
+            "data-2025-01-01.bz2",
+            "data-2025-01-02.bz2",
+            "data-2025-01-03.bz2",
+        ],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg("--output-template")
+        .arg("{stem}-{timestamp}")
+        .arg("--timestamp-format")
+        .arg("%Y-%m-%d")
+        .arg("-C")
+        .arg(&backups)
+        .arg("--keep-daily")
+        .arg("2")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let names = names_in(&backups);
+    assert_eq!(names.len(), 2, "unexpected outputs {:?}", names);
+    assert_eq!(names[0], "data-2025-01-03.bz2");
+}
+
+#[test]
+fn test_retention_for_collections() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+    let backups = create_backups(
+        temp_dir.path(),
+        &["pkg_20250101.tar.xz", "pkg_20250102.tar.xz"],
+    );
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("txz")
+        .arg("-a")
+        .arg("pkg")
+        .arg("-t")
+        .arg("1")
+        .arg("-C")
+        .arg(&backups)
+        .arg("--keep-last")
+        .arg("1")
+        .args(&files)
+        .assert()
+        .success();
+
+    let names = names_in(&backups);
+    assert_eq!(names.len(), 1, "unexpected outputs {:?}", names);
+    assert!(names[0].starts_with("pkg_2") && names[0] != "pkg_20250102.tar.xz");
+}
+
+#[test]
+fn test_invalid_retention_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    let invalid: [&[&str]; 7] = [
+        &["-c", "gzip", "--keep-last", "2"],
+        &["-c", "gzip", "-t", "1", "--keep-last", "0"],
+        &["-c", "gzip", "-t", "1", "--max-age", "5x"],
+        &[
+            "-c",
+            "gzip",
+            "--timestamp-format",
+            "%H%M",
+            "--keep-last",
+            "2",
+        ],
+        &[
+            "-c",
+            "gzip",
+            "--output-template",
+            "{stem}-{hash}-{date}",
+            "--keep-last",
+            "2",
+        ],
+        &["-c", "gzip", "-t", "1", "--prune-dry-run"],
+        &["-d", "-t", "1", "--keep-last", "2"],
+    ];
+    for args in invalid {
+        jcz_command().args(args).arg(&test_file).assert().failure();
+    }
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-c", "tgz", "-A", "pkg", "-t", "1", "--keep-last", "2"])
+        .arg(&test_file)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--output-template"));
+
+    assert_eq!(names_in(temp_dir.path()), vec!["data.txt"]);
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::*;
+use tempfile::TempDir;
+
+#[test]
+fn test_skip_already_compressed() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[
+            ("notes.txt", TEST_DATA_SMALL),
+            ("photo.jpg", b"JPEG data"),
+            ("data.bin", &[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3]),
+        ],
+    );
+
+    jcz_command()
+        .args(["-c", "gzip"])
+        .args(&files)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "photo.jpg: .jpg files are compressed already",
+        ))
+        .stderr(predicates::str::contains("data.bin: already gzip data"))
+        .stderr(predicates::str::contains("Left 2 input(s) uncompressed"));
+
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("notes.txt.gz"),
+        TEST_DATA_SMALL
+    ));
+    assert!(!file_exists(&temp_dir.path().join("photo.jpg.gz")));
+    assert!(!file_exists(&temp_dir.path().join("data.bin.gz")));
+
+    // Forced, every input is compressed; TAR bundles without being asked
+    jcz_command()
+        .args(["-c", "gzip", "-f", "--force-compress"])
+        .args(&files)
+        .assert()
+        .success();
+    assert!(file_exists(&temp_dir.path().join("photo.jpg.gz")));
+    assert!(file_exists(&temp_dir.path().join("data.bin.gz")));
+
+    jcz_command()
+        .args(["-c", "tar"])
+        .arg(&files[1])
+        .assert()
+        .success();
+    assert!(file_exists(&temp_dir.path().join("photo.jpg.tar")));
+}
+
+#[test]
+fn test_skip_incompressible() {
+    let temp_dir = TempDir::new().unwrap();
+    let random = create_test_file(temp_dir.path(), "random.raw", &noise(128 * 1024, 1));
+    let text = create_test_file(temp_dir.path(), "notes.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .args(["compress", "-c", "xz", "--skip-incompressible"])
+        .arg(&random)
+        .arg(&text)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "random.raw: looks incompressible",
+        ));
+    assert!(!file_exists(&temp_dir.path().join("random.raw.xz")));
+    assert!(file_exists(&temp_dir.path().join("notes.txt.xz")));
+
+    // Without the probe, only extensions and magic bytes count
+    jcz_command()
+        .args(["-c", "xz"])
+        .arg(&random)
+        .assert()
+        .success();
+    assert!(verify_decompressed_content(
+        &temp_dir.path().join("random.raw.xz"),
+        &noise(128 * 1024, 1)
+    ));
+
+    jcz_command()
+        .args(["-c", "xz", "--force-compress", "--skip-incompressible"])
+        .arg(&text)
+        .assert()
+        .failure();
+    jcz_command()
+        .args(["-d", "--force-compress"])
+        .arg(temp_dir.path().join("notes.txt.xz"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "only apply when compressing inputs one by one",
+        ));
+}
+
+#[test]
+fn test_skip_recursive_and_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "sub/song.mp3"]);
+
+    let output = jcz_command()
+        .args(["-c", "gzip", "-r", "--remove-source", "--dry-run"])
+        .arg(&data_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let plan = String::from_utf8_lossy(&output.stdout);
+    let song = data_dir.join("sub/song.mp3");
+    assert!(plan.contains(&format!(
+        "skip        {} (.mp3 files are compressed already)",
+        song.display()
+    )));
+    assert!(!plan.contains("song.mp3.gz"));
+    assert!(!plan.contains(&format!("remove      {}", song.display())));
+
+    jcz_command()
+        .args(["-c", "gzip", "-r"])
+        .arg(&data_dir)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("Left 1 input(s) uncompressed"));
+    assert!(file_exists(&data_dir.join("one.txt.gz")));
+    assert!(!file_exists(&data_dir.join("sub/song.mp3.gz")));
+}
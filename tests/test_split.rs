@@ -0,0 +1,327 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+/// List the volumes `<base>.001`, `<base>.002`, ... that exist
+fn volumes(base: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|n| PathBuf::from(format!("{}.{:03}", base.display(), n)))
+        .take_while(|p| p.exists())
+        .collect()
+}
+
+#[test]
+fn test_split_gzip_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = noise(250 * 1024, 1);
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("100K")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let base = temp_dir.path().join("data.bin.gz");
+    let parts = volumes(&base);
+    assert_eq!(parts.len(), 3);
+    assert!(!base.exists(), "Unsplit output should not be written");
+    assert_eq!(file_size(&parts[0]), 100 * 1024);
+    assert_eq!(file_size(&parts[1]), 100 * 1024);
+
+    // The concatenated volumes form a regular gzip stream
+    let joined = temp_dir.path().join("joined.gz");
+    let mut content = Vec::new();
+    for part in &parts {
+        content.extend(read_file(part));
+    }
+    fs::write(&joined, content).unwrap();
+    assert!(verify_decompressed_content(&joined, &data));
+}
+
+#[test]
+fn test_split_compound_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    let data = noise(300 * 1024, 1);
+    create_test_file(&data_dir, "blob.bin", &data);
+
+    jcz_command()
+        .arg("-c")
+        .arg("txz")
+        .arg("--split-size")
+        .arg("128K")
+        .arg("--remove-source")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    assert!(!data_dir.exists());
+    assert_eq!(volumes(&temp_dir.path().join("data.tar.xz")).len(), 3);
+
+    jcz_command()
+        .arg("-d")
+        .arg("--remove-archive")
+        .arg(temp_dir.path().join("data.tar.xz.001"))
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&data_dir.join("blob.bin")), data);
+    assert!(volumes(&temp_dir.path().join("data.tar.xz")).is_empty());
+}
+
+#[test]
+fn test_split_decompress_streams_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = noise(250 * 1024, 2);
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("100K")
+        .arg("--remove-source")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    // The volumes go straight into gzip, without a joined copy on disk
+    let output = jcz_command()
+        .arg("-d")
+        .arg(temp_dir.path().join("data.bin.gz.001"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(log.contains("Streaming"));
+    assert!(!log.contains("Joining"));
+
+    assert_eq!(read_file(&test_file), data);
+}
+
+#[test]
+fn test_split_collection() {
+    let temp_dir = TempDir::new().unwrap();
+    let file1 = create_test_file(temp_dir.path(), "file1.bin", &noise(90 * 1024, 1));
+    let file2 = create_test_file(temp_dir.path(), "file2.bin", &noise(90 * 1024, 2));
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("tgz")
+        .arg("-A")
+        .arg("bundle")
+        .arg("--split-size")
+        .arg("64K")
+        .arg(&file1)
+        .arg(&file2)
+        .assert()
+        .success();
+
+    let parts = volumes(&temp_dir.path().join("bundle.tar.gz"));
+    assert!(parts.len() >= 3);
+
+    let output_dir = temp_dir.path().join("output");
+    fs::create_dir(&output_dir).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg("-C")
+        .arg(&output_dir)
+        .arg(&parts[0])
+        .assert()
+        .success();
+
+    assert_eq!(read_file(&output_dir.join("file1.bin")), read_file(&file1));
+}
+
+#[test]
+fn test_split_tar_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = noise(40 * 1024, 1);
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tar")
+        .arg("--split-size")
+        .arg("16K")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+    let parts = volumes(&temp_dir.path().join("data.bin.tar"));
+    assert!(parts.len() >= 3);
+
+    jcz_command().arg("-d").arg(&parts[0]).assert().success();
+    assert_eq!(read_file(&test_file), data);
+}
+
+#[test]
+fn test_missing_middle_volume_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &noise(250 * 1024, 1));
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg("--split-size")
+        .arg("64K")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+    fs::remove_file(temp_dir.path().join("data.bin.bz2.002")).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg(temp_dir.path().join("data.bin.bz2.001"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("data.bin.bz2.002"));
+
+    assert!(!test_file.exists());
+}
+
+#[test]
+fn test_missing_last_volume_fails() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &noise(250 * 1024, 1));
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("100K")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    fs::remove_file(&test_file).unwrap();
+    fs::remove_file(temp_dir.path().join("data.bin.gz.003")).unwrap();
+
+    jcz_command()
+        .arg("-d")
+        .arg("--remove-archive")
+        .arg(temp_dir.path().join("data.bin.gz.001"))
+        .assert()
+        .failure();
+
+    assert!(!test_file.exists());
+    assert!(file_exists(&temp_dir.path().join("data.bin.gz.001")));
+}
+
+#[test]
+fn test_split_overwrite_removes_stale_volumes() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &noise(250 * 1024, 1));
+    let base = temp_dir.path().join("data.bin.gz");
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("50K")
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert_eq!(volumes(&base).len(), 6);
+
+    // An existing volume set counts as an existing output
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("100K")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("--split-size")
+        .arg("100K")
+        .arg("-f")
+        .arg(&test_file)
+        .assert()
+        .success();
+    assert_eq!(volumes(&base).len(), 3);
+}
+
+#[test]
+fn test_split_overwrite_keeps_file_with_whole_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &noise(150 * 1024, 1));
+    let empty = create_test_file(temp_dir.path(), "data.bin.gz", b"");
+
+    // Only a name jcz reserved itself is removed once the volumes are written
+    jcz_command()
+        .args(["-c", "gzip", "--split-size", "100K", "-f"])
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert_eq!(volumes(&empty).len(), 2);
+    assert!(file_exists(&empty));
+}
+
+#[test]
+fn test_convert_split_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let data = noise(200 * 1024, 1);
+    let test_file = create_test_file(temp_dir.path(), "data.bin", &data);
+
+    jcz_command()
+        .arg("-c")
+        .arg("tbz2")
+        .arg("--split-size")
+        .arg("64K")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("--convert")
+        .arg("tgz")
+        .arg("--remove-source")
+        .arg(temp_dir.path().join("data.bin.tar.bz2.001"))
+        .assert()
+        .success();
+
+    assert!(volumes(&temp_dir.path().join("data.bin.tar.bz2")).is_empty());
+    let output = StdCommand::new("tar")
+        .arg("-xOzf")
+        .arg(temp_dir.path().join("data.bin.tar.gz"))
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, data);
+}
+
+#[test]
+fn test_invalid_split_size() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    for size in ["0", "abc", "10X"] {
+        jcz_command()
+            .arg("-c")
+            .arg("gzip")
+            .arg("--split-size")
+            .arg(size)
+            .arg(&test_file)
+            .assert()
+            .failure();
+    }
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::*;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_compress_and_decompress_subcommands() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_MEDIUM);
+    let out = temp_dir.path().join("out");
+
+    jcz_command()
+        .arg("compress")
+        .args(["--format", "xz", "-l", "9", "-C"])
+        .arg(&out)
+        .arg(&test_file)
+        .assert()
+        .success();
+    let archive = out.join("data.txt.xz");
+    assert!(file_exists(&archive));
+
+    fs::remove_file(&test_file).unwrap();
+    jcz_command()
+        .arg("decompress")
+        .arg("--remove-archive")
+        .arg(&archive)
+        .assert()
+        .success();
+    assert_eq!(read_file(&out.join("data.txt")), TEST_DATA_MEDIUM);
+    assert!(!file_exists(&archive));
+}
+
+#[test]
+fn test_collect_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["collect", "pkg", "-c", "tbz2", "--manifest"])
+        .args(&files)
+        .assert()
+        .success();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["collect", "flat", "--flat"])
+        .args(&files)
+        .assert()
+        .success();
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["list", "pkg.tar.bz2", "flat.tar.gz"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("pkg/a.txt"))
+        .stdout(predicates::str::contains("pkg/MANIFEST.json"))
+        .stdout(predicates::str::contains("flat.tar.gz:\na.txt"));
+}
+
+#[test]
+fn test_test_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "sub/two.txt"]);
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["-c", "txz", "data"])
+        .assert()
+        .success();
+    let archive = temp_dir.path().join("data.tar.xz");
+
+    jcz_command()
+        .arg("test")
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("data.tar.xz: OK"));
+
+    // A truncated archive fails without anything being extracted
+    let content = fs::read(&archive).unwrap();
+    let truncated = temp_dir.path().join("truncated.tar.xz");
+    fs::write(&truncated, &content[..content.len() / 2]).unwrap();
+    jcz_command()
+        .arg("test")
+        .arg(&archive)
+        .arg(&truncated)
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("data.tar.xz: OK"));
+    assert!(!dir_exists(&temp_dir.path().join("truncated")));
+}
+
+#[test]
+fn test_info_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["one.txt", "sub/two.txt"]);
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-c", "tgz", "data"])
+        .assert()
+        .success();
+
+    jcz_command()
+        .arg("info")
+        .arg(temp_dir.path().join("data.tar.gz"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("layers:       gzip > tar"))
+        .stdout(predicates::str::contains("entries:      4"));
+}
+
+#[test]
+fn test_info_layer_details() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_MEDIUM);
+
+    for (format, level) in [("gzip", "1"), ("bzip2", "3"), ("xz", "9")] {
+        jcz_command()
+            .args(["compress", "-c", format, "-l", level])
+            .arg(&test_file)
+            .assert()
+            .success();
+    }
+
+    jcz_command()
+        .arg("info")
+        .arg(temp_dir.path().join("data.txt.gz"))
+        .arg(temp_dir.path().join("data.txt.bz2"))
+        .arg(temp_dir.path().join("data.txt.xz"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("level 1, name data.txt"))
+        .stdout(predicates::str::contains("os Unix"))
+        .stdout(predicates::str::contains("level 3, block size 300 kB"))
+        .stdout(predicates::str::contains("level 9, check CRC64, blocks 1"));
+
+    let output = jcz_command()
+        .args(["info", "--json"])
+        .arg(temp_dir.path().join("data.txt.xz"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let layer = &reports[0]["layers"][0];
+    assert_eq!(layer["format"], "xz");
+    assert_eq!(layer["level"], "9");
+    assert_eq!(layer["dictionary"], 64 << 20);
+    assert_eq!(
+        reports[0]["uncompressed_size"],
+        TEST_DATA_MEDIUM.len() as u64
+    );
+}
+
+#[test]
+fn test_subcommands_keep_flag_form() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "data.txt", TEST_DATA_SMALL);
+
+    // Flags before a subcommand are rejected rather than ignored
+    jcz_command()
+        .args(["-l", "9", "compress"])
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    // Subcommands use the same configuration settings as the flags
+    fs::write(temp_dir.path().join(".jcz.toml"), "command = \"bzip2\"\n").unwrap();
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["compress", "data.txt"])
+        .assert()
+        .success();
+    assert!(file_exists(&temp_dir.path().join("data.txt.bz2")));
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .args(["compress", "-c", "gzip", "--dry-run", "data.txt"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("data.txt.gz"));
+    assert!(!file_exists(&temp_dir.path().join("data.txt.gz")));
+}
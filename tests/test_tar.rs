@@ -215,3 +215,30 @@ fn test_tar_with_compression_level_ignored() {
     let archive_file = temp_dir.path().join("test.txt.tar");
     assert!(file_exists(&archive_file));
 }
+
+#[test]
+fn test_tar_archive_with_many_warnings() {
+    use std::os::unix::net::UnixListener;
+
+    // tar warns once per socket it leaves out, more than a pipe buffer holds
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    create_test_dir_structure(&data_dir, &["keep.txt"]);
+    let _sockets: Vec<UnixListener> = (0..3000)
+        .map(|i| UnixListener::bind(data_dir.join(format!("socket-{:04}", i))).unwrap())
+        .collect();
+
+    jcz_command()
+        .args(["-c", "tar"])
+        .arg(&data_dir)
+        .timeout(std::time::Duration::from_secs(60))
+        .assert()
+        .success();
+
+    let output = Command::new("tar")
+        .arg("-tf")
+        .arg(temp_dir.path().join("data.tar"))
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("data/keep.txt"));
+}
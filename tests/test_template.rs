@@ -0,0 +1,220 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command as StdCommand;
+use tempfile::TempDir;
+
+/// Names of the files in a directory that end with `suffix`
+fn names_ending_with(dir: &Path, suffix: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.ends_with(suffix))
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_template_single_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_MEDIUM);
+
+    jcz_command()
+        .arg("-c")
+        .arg("gzip")
+        .arg("-l")
+        .arg("9")
+        .arg("--output-template")
+        .arg("{stem}-{format}-{level}.{ext}")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    let output = temp_dir.path().join("test-gzip-9.gz");
+    assert!(file_exists(&output));
+    assert!(verify_decompressed_content(&output, TEST_DATA_MEDIUM));
+}
+
+#[test]
+fn test_template_extension_always_added() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    jcz_command()
+        .arg("-c")
+        .arg("xz")
+        .arg("--output-template")
+        .arg("{basename}.bak")
+        .arg(&test_file)
+        .assert()
+        .success();
+
+    assert!(file_exists(&temp_dir.path().join("test.txt.bak.xz")));
+}
+
+#[test]
+fn test_template_compound_with_hash_and_date() {
+    let temp_dir = TempDir::new().unwrap();
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    create_test_file(&data_dir, "file.txt", TEST_DATA_MEDIUM);
+
+    let status = StdCommand::new("touch")
+        .arg("-d")
+        .arg("@1700000000")
+        .arg(&data_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--output-template")
+        .arg("{stem}-{hash}-{date}.{ext}")
+        .arg("--utc")
+        .arg("--timestamp-from-mtime")
+        .arg(&data_dir)
+        .assert()
+        .success();
+
+    let names = names_ending_with(temp_dir.path(), ".tar.gz");
+    assert_eq!(names.len(), 1);
+    let name = &names[0];
+    assert!(name.starts_with("data-"), "unexpected name {}", name);
+    assert!(
+        name.ends_with("-20231114.tar.gz"),
+        "unexpected name {}",
+        name
+    );
+    assert_eq!(name.len(), "data-".len() + 8 + "-20231114.tar.gz".len());
+
+    // The same content gives the same hash
+    fs::remove_file(temp_dir.path().join(name)).unwrap();
+    jcz_command()
+        .arg("-c")
+        .arg("tgz")
+        .arg("--output-template")
+        .arg("{stem}-{hash}-{date}.{ext}")
+        .arg("--utc")
+        .arg("--timestamp-from-mtime")
+        .arg(&data_dir)
+        .assert()
+        .success();
+    assert_eq!(names_ending_with(temp_dir.path(), ".tar.gz"), names);
+
+    // Like timestamped archives, it extracts to a directory named after itself
+    fs::remove_dir_all(&data_dir).unwrap();
+    jcz_command()
+        .arg("-d")
+        .arg(temp_dir.path().join(name))
+        .assert()
+        .success();
+    let extracted = temp_dir.path().join(name.trim_end_matches(".tar.gz"));
+    assert_eq!(read_file(&extracted.join("file.txt")), TEST_DATA_MEDIUM);
+}
+
+#[test]
+fn test_template_counter_in_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[
+            ("a.txt", TEST_DATA_SMALL),
+            ("b.txt", TEST_DATA_SMALL),
+            ("c.txt", TEST_DATA_SMALL),
+        ],
+    );
+
+    jcz_command()
+        .arg("-c")
+        .arg("bzip2")
+        .arg("--output-template")
+        .arg("{counter}-{stem}")
+        .args(&files)
+        .assert()
+        .success();
+
+    assert_eq!(
+        names_ending_with(temp_dir.path(), ".bz2"),
+        vec!["1-a.bz2", "2-b.bz2", "3-c.bz2"]
+    );
+}
+
+#[test]
+fn test_template_collection_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = create_test_files(
+        temp_dir.path(),
+        &[("a.txt", TEST_DATA_SMALL), ("b.txt", TEST_DATA_MEDIUM)],
+    );
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("txz")
+        .arg("-a")
+        .arg("pkg")
+        .arg("--output-template")
+        .arg("{stem}-{format}.{ext}")
+        .args(&files)
+        .assert()
+        .success();
+
+    let archive = temp_dir.path().join("pkg-txz.tar.xz");
+    assert!(file_exists(&archive));
+
+    // The directory inside keeps the package name
+    let output = StdCommand::new("tar")
+        .arg("-tJf")
+        .arg(&archive)
+        .output()
+        .unwrap();
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(listing.lines().any(|l| l == "pkg/a.txt"));
+
+    jcz_command()
+        .current_dir(temp_dir.path())
+        .arg("-c")
+        .arg("tgz")
+        .arg("-A")
+        .arg("bundle")
+        .arg("--output-template")
+        .arg("{stem}.v{counter}")
+        .args(&files)
+        .assert()
+        .success();
+
+    assert!(file_exists(&temp_dir.path().join("bundle.v1.tar.gz")));
+}
+
+#[test]
+fn test_invalid_output_templates() {
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = create_test_file(temp_dir.path(), "test.txt", TEST_DATA_SMALL);
+
+    for template in ["{stem}-{nope}", "{stem", "out/{stem}", ".{ext}"] {
+        jcz_command()
+            .arg("-c")
+            .arg("gzip")
+            .arg("--output-template")
+            .arg(template)
+            .arg(&test_file)
+            .assert()
+            .failure();
+    }
+
+    jcz_command()
+        .arg("-d")
+        .arg("--output-template")
+        .arg("{stem}")
+        .arg(&test_file)
+        .assert()
+        .failure();
+
+    assert!(names_ending_with(temp_dir.path(), ".gz").is_empty());
+}